@@ -0,0 +1,279 @@
+use crate::errors::*;
+use crate::files::{self, Download};
+use crate::journal::UploadJournal;
+use crate::requests::{delete_request, file_url, get_request_json, put_request, query_url};
+use crate::streams::{self, RemoteReader, RemoteWriter};
+use actix_web::client::Client;
+use ccfs_commons::{result::CCFSResult, FileMetadata};
+use snafu::ResultExt;
+use std::path::Path;
+use tokio::io::AsyncWrite;
+use uuid::Uuid;
+
+/// Client of a CCFS cluster, which talks to its metadata server at `meta_url`
+/// and to the chunk servers the metadata server points it to
+#[derive(Clone)]
+pub struct CcfsClient {
+    client: Client,
+    meta_url: String,
+}
+
+impl CcfsClient {
+    pub fn new<T: Into<String>>(meta_url: T) -> Self {
+        Self::with_client(Client::new(), meta_url)
+    }
+
+    pub fn with_client<T: Into<String>>(client: Client, meta_url: T) -> Self {
+        Self {
+            client,
+            meta_url: meta_url.into(),
+        }
+    }
+
+    pub fn meta_url(&self) -> &str {
+        &self.meta_url
+    }
+
+    /// Returns the metadata of the file or directory (with its whole subtree) at the path.
+    /// Its `version` can be passed to the `_if_version` methods, which fail with a conflict
+    /// when the file was changed in the meantime
+    pub async fn stat(&self, path: &str) -> CCFSResult<FileMetadata> {
+        get_request_json(&self.client, &file_url(&self.meta_url, path, None)).await
+    }
+
+    /// Returns the metadata of the version of the file at the path,
+    /// which is either its current version or one of the kept previous versions
+    pub async fn stat_version(&self, path: &str, version: usize) -> CCFSResult<FileMetadata> {
+        let url = file_url(&self.meta_url, path, Some(version));
+        get_request_json(&self.client, &url).await
+    }
+
+    /// Returns the content of the directory at the path
+    pub async fn list(&self, path: &str) -> CCFSResult<Vec<FileMetadata>> {
+        let dir = self.stat(path).await?;
+        Ok(dir.children()?.values().cloned().collect())
+    }
+
+    /// Uploads the local file (or the directory with all of its content) to the CCFS root.
+    /// Each chunk is stored `replicas` times, or as many times as the metadata server default is
+    pub async fn upload<T: AsRef<Path>>(
+        &self,
+        local_path: T,
+        replicas: Option<usize>,
+    ) -> CCFSResult<FileMetadata> {
+        files::upload(
+            &self.client,
+            &self.meta_url,
+            local_path.as_ref(),
+            replicas,
+            None,
+        )
+        .await
+    }
+
+    /// Uploads like `upload`, recording the progress in a journal in the `journal_dir`, which is
+    /// removed once the upload completes. With `resume`, the files and chunks which the journal
+    /// of the interrupted upload of the same path records as uploaded are skipped
+    pub async fn upload_resumable<T: AsRef<Path>>(
+        &self,
+        local_path: T,
+        replicas: Option<usize>,
+        journal_dir: &Path,
+        resume: bool,
+    ) -> CCFSResult<FileMetadata> {
+        let path = local_path.as_ref();
+        let path = path
+            .canonicalize()
+            .map_err(|_| FileNotExist { path }.build())?;
+        let mut journal = match resume {
+            true => UploadJournal::load(journal_dir, &path).await?,
+            false => UploadJournal::new(journal_dir, &path),
+        };
+        let file = files::upload(
+            &self.client,
+            &self.meta_url,
+            &path,
+            replicas,
+            Some(&mut journal),
+        )
+        .await?;
+        journal.remove().await?;
+        Ok(file)
+    }
+
+    /// Cancels the upload of the file which is still in progress,
+    /// and returns the canceled file. Its uploaded chunks are deleted
+    pub async fn cancel_upload(&self, file_id: Uuid) -> CCFSResult<FileMetadata> {
+        let url = format!("{}/api/files/{}/upload", self.meta_url, file_id);
+        let mut resp = delete_request(&self.client, &url, None).await?;
+        Ok(resp.json().await.context(ParseJson)?)
+    }
+
+    /// Cancels the uploads of the files which the journal of the interrupted upload of the
+    /// local path records, and removes the journal. The completed files are left as they are,
+    /// only the canceled ones are returned
+    pub async fn cancel_resumable<T: AsRef<Path>>(
+        &self,
+        local_path: T,
+        journal_dir: &Path,
+    ) -> CCFSResult<Vec<FileMetadata>> {
+        let path = local_path.as_ref();
+        let path = path
+            .canonicalize()
+            .map_err(|_| FileNotExist { path }.build())?;
+        let journal = UploadJournal::load(journal_dir, &path).await?;
+        let mut canceled = Vec::new();
+        for file_id in journal.items.values().filter_map(|entry| entry.file_id) {
+            if let Ok(file) = self.cancel_upload(file_id).await {
+                canceled.push(file);
+            }
+        }
+        journal.remove().await?;
+        Ok(canceled)
+    }
+
+    /// Downloads the file (or the directory with all of its content) into the local dir.
+    /// An existing file with the same name is replaced only when `force` is set
+    pub async fn download<T: AsRef<Path>>(
+        &self,
+        path: &str,
+        target_dir: T,
+        force: bool,
+    ) -> CCFSResult<Download> {
+        let target_dir = target_dir.as_ref();
+        files::download(&self.client, &self.meta_url, path, None, target_dir, force).await
+    }
+
+    /// Downloads the version of the file into the local dir, like `download`
+    pub async fn download_version<T: AsRef<Path>>(
+        &self,
+        path: &str,
+        version: usize,
+        target_dir: T,
+        force: bool,
+    ) -> CCFSResult<Download> {
+        let (target_dir, version) = (target_dir.as_ref(), Some(version));
+        files::download(
+            &self.client,
+            &self.meta_url,
+            path,
+            version,
+            target_dir,
+            force,
+        )
+        .await
+    }
+
+    /// Writes `len` bytes of the file starting at `offset` to the writer, and returns the number
+    /// of written bytes. Only the needed ranges of the chunks are downloaded, which can't be
+    /// verified against the chunk checksums
+    pub async fn read_range<W: AsyncWrite + Unpin>(
+        &self,
+        path: &str,
+        offset: u64,
+        len: u64,
+        writer: &mut W,
+    ) -> CCFSResult<u64> {
+        let file = self.stat(path).await?;
+        files::read_range(&self.client, &self.meta_url, &file, offset, len, writer).await
+    }
+
+    /// Opens the file for reading, its chunks are downloaded as they're read
+    pub async fn open(&self, path: &str) -> CCFSResult<RemoteReader> {
+        streams::open(&self.client, &self.meta_url, path, None).await
+    }
+
+    /// Opens the version of the file for reading, like `open`
+    pub async fn open_version(&self, path: &str, version: usize) -> CCFSResult<RemoteReader> {
+        streams::open(&self.client, &self.meta_url, path, Some(version)).await
+    }
+
+    /// Creates the file at the path, whose content is written through the returned writer.
+    /// The file shows up in its directory once the writer is shut down
+    pub async fn create(&self, path: &str, replicas: Option<usize>) -> CCFSResult<RemoteWriter> {
+        self.create_if_version(path, replicas, None).await
+    }
+
+    /// Creates the file like `create`, the upload fails if the file (or 0 when there's no such
+    /// file) isn't at the expected version by the time it's completed
+    pub async fn create_if_version(
+        &self,
+        path: &str,
+        replicas: Option<usize>,
+        expected_version: Option<usize>,
+    ) -> CCFSResult<RemoteWriter> {
+        let meta_url = &self.meta_url;
+        streams::create(&self.client, meta_url, path, replicas, expected_version).await
+    }
+
+    /// Removes the file, or the directory with all of its content when `recursive` is set
+    pub async fn remove(&self, path: &str, recursive: bool) -> CCFSResult<FileMetadata> {
+        self.remove_if_version(path, recursive, None).await
+    }
+
+    /// Removes the file like `remove`, only if it's at the expected version
+    pub async fn remove_if_version(
+        &self,
+        path: &str,
+        recursive: bool,
+        expected_version: Option<usize>,
+    ) -> CCFSResult<FileMetadata> {
+        let url = format!("{}/api/files", self.meta_url);
+        let url = query_url(
+            &url,
+            &[("path", path), ("recursive", &recursive.to_string())],
+        );
+        let mut resp = delete_request(&self.client, &url, expected_version).await?;
+        Ok(resp.json().await.context(ParseJson)?)
+    }
+
+    /// Changes the number of replicas of each file chunk, the metadata server default is used
+    /// when it's not set
+    pub async fn set_replicas(
+        &self,
+        path: &str,
+        replicas: Option<usize>,
+    ) -> CCFSResult<FileMetadata> {
+        self.set_replicas_if_version(path, replicas, None).await
+    }
+
+    /// Changes the number of replicas like `set_replicas`, only if the file is at the expected
+    /// version
+    pub async fn set_replicas_if_version(
+        &self,
+        path: &str,
+        replicas: Option<usize>,
+        expected_version: Option<usize>,
+    ) -> CCFSResult<FileMetadata> {
+        let url = format!("{}/api/files/replicas", self.meta_url);
+        let url = match replicas {
+            Some(replicas) => {
+                query_url(&url, &[("path", path), ("replicas", &replicas.to_string())])
+            }
+            None => query_url(&url, &[("path", path)]),
+        };
+        let mut resp = put_request(&self.client, &url, expected_version).await?;
+        Ok(resp.json().await.context(ParseJson)?)
+    }
+
+    /// Changes the number of the previous versions which are kept when the file is overwritten,
+    /// the kept versions over the new number are removed
+    pub async fn set_kept_versions(&self, path: &str, versions: usize) -> CCFSResult<FileMetadata> {
+        self.set_kept_versions_if_version(path, versions, None)
+            .await
+    }
+
+    /// Changes the number of the kept versions like `set_kept_versions`, only if the file
+    /// is at the expected version
+    pub async fn set_kept_versions_if_version(
+        &self,
+        path: &str,
+        versions: usize,
+        expected_version: Option<usize>,
+    ) -> CCFSResult<FileMetadata> {
+        let url = format!("{}/api/files/versions", self.meta_url);
+        let url = query_url(&url, &[("path", path), ("versions", &versions.to_string())]);
+        let mut resp = put_request(&self.client, &url, expected_version).await?;
+        Ok(resp.json().await.context(ParseJson)?)
+    }
+}
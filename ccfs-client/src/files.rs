@@ -0,0 +1,600 @@
+//! Uploads and downloads of the files, split into the chunks stored on the chunk servers
+
+use crate::errors::*;
+use crate::journal::{DownloadJournal, UploadJournal};
+use crate::requests::{file_url, get_request, get_request_json, post_request, query_url};
+use actix_web::body::BodyStream;
+use actix_web::client::Client;
+use actix_web::http::header::{CONTENT_TYPE, RANGE};
+use actix_web::http::StatusCode;
+use actix_web::web::{Bytes, BytesMut};
+use ccfs_commons::checksum::{checksum_reader, Hasher};
+use ccfs_commons::http_utils::{create_ccfs_multipart, read_body};
+use ccfs_commons::range::{chunk_ranges, range_header, ByteRange};
+use ccfs_commons::{errors::Error as BaseError, result::CCFSResult};
+use ccfs_commons::{Chunk, ChunkMetadata, ChunkPlacement, ChunkServer};
+use ccfs_commons::{FileInfo, FileMetadata, FileUpload, UploadStatus, CHUNK_SIZE};
+use futures::future::{join_all, ready, Future};
+use futures::stream;
+use snafu::ResultExt;
+use std::collections::HashMap;
+use std::io::{Cursor, SeekFrom};
+use std::path::{Path, PathBuf};
+use tempfile::tempdir_in;
+use tokio::fs::{
+    create_dir, create_dir_all, remove_dir, remove_dir_all, rename, File, OpenOptions,
+};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeekExt, AsyncWrite, AsyncWriteExt, Take};
+use tokio_stream::StreamExt;
+use tokio_util::io::ReaderStream;
+use uuid::Uuid;
+
+/// Dir of the download target dir where the files are staged until the whole download completes
+pub const STAGING_DIR: &str = ".ccfs-download";
+
+/// Number of the file chunks which are downloaded at the same time
+const PARALLEL_DOWNLOADS: usize = 4;
+
+/// Downloaded file or directory
+#[derive(Debug, Clone, PartialEq)]
+pub struct Download {
+    pub file: FileMetadata,
+    /// Local path of the downloaded file
+    pub path: PathBuf,
+    /// Replicas whose content didn't match the chunk checksum, they were replaced by the other replicas
+    pub corrupted_replicas: Vec<Chunk>,
+}
+
+/// Uploads the local file (or the directory with all of its content) to the CCFS
+/// root, and returns the metadata of the uploaded file. The progress is recorded in
+/// the journal, and the items which it records as uploaded are skipped
+pub(crate) async fn upload(
+    c: &Client,
+    meta_url: &str,
+    path: &Path,
+    replicas: Option<usize>,
+    mut journal: Option<&mut UploadJournal>,
+) -> CCFSResult<FileMetadata> {
+    if !path.exists() {
+        let path = path.to_path_buf();
+        return Err(FileNotExist { path }.build().into());
+    }
+    let path_prefix = path.parent().map(|p| p.to_path_buf()).unwrap_or_default();
+    let mut uploaded = None;
+    let mut paths = vec![path.to_path_buf()];
+    while let Some(curr) = paths.pop() {
+        let item = (curr.as_path(), path_prefix.as_path());
+        let file = upload_item(c, meta_url, item, replicas, journal.as_deref_mut()).await?;
+        uploaded.get_or_insert(file);
+        if curr.is_dir() {
+            paths.extend(
+                curr.read_dir()
+                    .map_err(|source| BaseError::Open { path: curr, source })?
+                    .filter_map(|item| item.ok())
+                    .map(|item| item.path()),
+            );
+        }
+    }
+    Ok(uploaded.expect("the uploaded path is always visited"))
+}
+
+async fn upload_item(
+    c: &Client,
+    meta_url: &str,
+    item: (&Path, &Path),
+    replicas: Option<usize>,
+    mut journal: Option<&mut UploadJournal>,
+) -> CCFSResult<FileMetadata> {
+    let (path, prefix) = item;
+    let file_meta = path.metadata().map_err(|source| BaseError::Read {
+        path: path.into(),
+        source,
+    })?;
+    let file_name = path.file_name().unwrap().to_str().unwrap().to_string();
+    if let Some(entry) = journal.as_ref().and_then(|j| j.entry(path, &file_meta)) {
+        match entry.file_id {
+            // the directory is already created
+            None => return Ok(FileMetadata::create_dir(file_name)),
+            Some(file_id) => {
+                let url = format!("{}/api/files/{}/upload", meta_url, file_id);
+                // the file is uploaded again if the metadata server doesn't have it anymore
+                if let Ok(status) = get_request_json::<UploadStatus>(c, &url).await {
+                    upload_file(c, &status.upload, path, journal).await?;
+                    return Ok(status.upload.file);
+                }
+            }
+        }
+    }
+
+    let file_data = match file_meta.is_dir() {
+        true => FileMetadata::create_dir(file_name),
+        false => {
+            let chunks = generate_chunk_ids(file_meta.len());
+            FileMetadata::create_file(file_name, file_meta.len(), chunks).with_replicas(replicas)
+        }
+    };
+    let relative_path = path.strip_prefix(prefix).unwrap();
+    let target_dir = relative_path.parent().unwrap().display().to_string();
+    let upload_url = format!("{}/api/files/upload", meta_url);
+    let upload_url = query_url(&upload_url, &[("path", &target_dir)]);
+    let mut resp = post_request(c, &upload_url, file_data, None).await?;
+    let upload: FileUpload = resp.json().await.context(ParseJson)?;
+    if let Some(journal) = journal.as_deref_mut() {
+        let file_id = match &upload.file.file_info {
+            FileInfo::File { id, .. } => Some(*id),
+            FileInfo::Directory { .. } => None,
+        };
+        journal.record(path, &file_meta, file_id);
+        journal.save().await?;
+    }
+    upload_file(c, &upload, path, journal).await?;
+    Ok(upload.file)
+}
+
+fn generate_chunk_ids(size: u64) -> Vec<Uuid> {
+    (0..size / CHUNK_SIZE + 1).map(|_| Uuid::new_v4()).collect()
+}
+
+/// Uploads the file chunks to the servers picked by the metadata server, along with
+/// the file metadata which is recorded next to each chunk. The placement may list
+/// only some of the file chunks, the uploaded ones are recorded in the journal
+async fn upload_file(
+    c: &Client,
+    upload: &FileUpload,
+    path: &Path,
+    journal: Option<&mut UploadJournal>,
+) -> CCFSResult<()> {
+    let (file_id, chunk_ids) = match &upload.file.file_info {
+        FileInfo::File { id, chunks, .. } => (id, chunks),
+        FileInfo::Directory { .. } => return Ok(()),
+    };
+    if upload.placement.iter().any(|p| p.servers.is_empty()) {
+        return Err(NoAvailableServers.build().into());
+    }
+
+    let targets = upload
+        .placement
+        .iter()
+        .filter_map(|chunk| {
+            Some((
+                chunk_ids.iter().position(|id| id == &chunk.chunk_id)?,
+                chunk,
+            ))
+        })
+        .collect::<Vec<_>>();
+    let requests = targets.iter().map(|(i, chunk)| {
+        let metadata = ChunkMetadata::new(&upload.path, &upload.file, *i);
+        upload_file_chunk(c, chunk, path, (file_id, *i), metadata)
+    });
+    let responses = join_all(requests).await;
+    if let Some(journal) = journal {
+        let uploaded = targets
+            .iter()
+            .zip(responses.iter())
+            .filter(|(_, resp)| resp.is_ok())
+            .map(|((_, chunk), _)| chunk.chunk_id);
+        journal.record_chunks(path, uploaded);
+        journal.save().await?;
+    }
+    if responses.iter().any(|resp| resp.is_err()) {
+        return Err(UploadChunks.build().into());
+    }
+    Ok(())
+}
+
+/// Uploads the `part` chunk of the local file
+async fn upload_file_chunk(
+    c: &Client,
+    placement: &ChunkPlacement,
+    path: &Path,
+    data: (&Uuid, usize),
+    metadata: ChunkMetadata,
+) -> CCFSResult<()> {
+    let checksum = checksum_reader(open_chunk(path, data.1).await?)
+        .await
+        .map_err(|source| BaseError::Read {
+            path: path.into(),
+            source,
+        })?;
+    upload_chunk(c, placement, data, checksum, metadata, || {
+        open_chunk(path, data.1)
+    })
+    .await
+}
+
+/// Uploads the chunk content which is kept in memory
+pub(crate) async fn upload_buffer(
+    c: &Client,
+    placement: &ChunkPlacement,
+    data: (&Uuid, usize),
+    metadata: ChunkMetadata,
+    content: Bytes,
+) -> CCFSResult<()> {
+    let mut hasher = Hasher::new();
+    hasher.update(&content);
+    upload_chunk(c, placement, data, hasher.finalize(), metadata, || {
+        ready(Ok(Cursor::new(content.clone())))
+    })
+    .await
+}
+
+/// Uploads the chunk to its primary server (or the next target if it fails),
+/// which then replicates it to the remaining targets. The content is reopened for each attempt
+async fn upload_chunk<T, F, Fut>(
+    c: &Client,
+    placement: &ChunkPlacement,
+    data: (&Uuid, usize),
+    checksum: u32,
+    metadata: ChunkMetadata,
+    open: F,
+) -> CCFSResult<()>
+where
+    T: AsyncRead + Unpin + 'static,
+    F: Fn() -> Fut,
+    Fut: Future<Output = CCFSResult<T>>,
+{
+    let (file_id, part) = data;
+    let chunk_id = placement.chunk_id;
+    let file_id_str = file_id.to_string();
+    let chunk_id_str = chunk_id.to_string();
+    for (i, server) in placement.servers.iter().enumerate() {
+        let stream = ReaderStream::new(open().await?);
+        let mpart = create_ccfs_multipart(
+            &chunk_id_str,
+            &file_id_str,
+            checksum,
+            Some(&metadata),
+            stream,
+        );
+        let url = format!("{}/api/upload", server.address);
+        let resp = c
+            .post(&url)
+            .insert_header((
+                CONTENT_TYPE,
+                format!("multipart/form-data; boundary={}", &mpart.get_boundary()),
+            ))
+            .send_body(BodyStream::new(Box::new(mpart)))
+            .await
+            .map_err(|source| BaseError::FailedRequest { url, source })?;
+        if resp.status().is_success() {
+            let targets = placement.servers.iter().skip(i + 1);
+            let requests = targets.map(|target| {
+                c.post(format!("{}/api/replicate", server.address))
+                    .insert_header(("x-ccfs-chunk-id", chunk_id_str.clone()))
+                    .insert_header(("x-ccfs-file-id", file_id_str.clone()))
+                    .insert_header(("x-ccfs-server-url", target.address.clone()))
+                    .send()
+            });
+            // missing replicas are created later by the metadata server replication job
+            join_all(requests).await;
+            return Ok(());
+        }
+    }
+    Err(UploadSingleChunk { part, chunk_id }.build().into())
+}
+
+/// Opens the file, limited to the content of its `part` chunk
+async fn open_chunk(path: &Path, part: usize) -> CCFSResult<Take<File>> {
+    let mut f = File::open(path).await.map_err(|source| BaseError::Open {
+        path: path.into(),
+        source,
+    })?;
+    f.seek(SeekFrom::Start(part as u64 * CHUNK_SIZE))
+        .await
+        .map_err(|source| BaseError::Open {
+            path: path.into(),
+            source,
+        })?;
+    Ok(f.take(CHUNK_SIZE))
+}
+
+/// Downloads the file (or the directory with all of its content) into the target dir.
+/// An existing file with the same name is replaced only when `force` is set.
+/// The files are staged in the `STAGING_DIR` of the target dir, keyed by their id and version,
+/// so an interrupted download skips the already verified files and chunks when it's repeated
+pub(crate) async fn download(
+    c: &Client,
+    meta_url: &str,
+    path: &str,
+    version: Option<usize>,
+    target_dir: &Path,
+    force: bool,
+) -> CCFSResult<Download> {
+    let file: FileMetadata = get_request_json(c, &file_url(meta_url, path, version)).await?;
+    let to = target_dir.join(&file.name);
+    if to.exists() {
+        if !force {
+            return Err(AlreadyExists { path: to.clone() }.build().into());
+        } else if to.is_dir() {
+            remove_dir_all(&to)
+                .await
+                .map_err(|source| BaseError::Remove {
+                    path: to.clone(),
+                    source,
+                })?;
+        }
+    }
+    let staging = target_dir.join(STAGING_DIR);
+    create_dir_all(&staging)
+        .await
+        .map_err(|source| BaseError::Create {
+            path: staging.clone(),
+            source,
+        })?;
+
+    let mut corrupted_replicas = Vec::new();
+    for curr_f in file.bfs_iter() {
+        let corrupted = download_file(c, meta_url, curr_f, &staging).await?;
+        corrupted_replicas.extend(corrupted);
+    }
+
+    // all the files are staged, the tree is assembled and moved into the target dir
+    let tmp = tempdir_in(&staging).context(TempDir)?;
+    for (curr_f, parent_dir) in file.bfs_iter().zip(file.bfs_paths_iter()) {
+        let curr_path = tmp.path().join(parent_dir).join(&curr_f.name);
+        match &curr_f.file_info {
+            FileInfo::Directory { .. } => {
+                create_dir(&curr_path)
+                    .await
+                    .map_err(|source| BaseError::Create {
+                        path: curr_path.clone(),
+                        source,
+                    })?
+            }
+            FileInfo::File { id, .. } => {
+                let journal = DownloadJournal::new(&staging, *id, curr_f.version);
+                let staged = journal.staged_path();
+                rename(&staged, &curr_path)
+                    .await
+                    .map_err(|source| BaseError::Rename {
+                        from: staged,
+                        to: curr_path.clone(),
+                        source,
+                    })?;
+                journal.remove().await?;
+            }
+        }
+    }
+    let from = tmp.path().join(&file.name);
+    rename(&from, &to)
+        .await
+        .map_err(|source| BaseError::Rename {
+            from,
+            to: to.clone(),
+            source,
+        })?;
+    drop(tmp);
+    // the staging dir is kept while it holds the files of other interrupted downloads
+    let _ = remove_dir(&staging).await;
+    Ok(Download {
+        file,
+        path: to,
+        corrupted_replicas,
+    })
+}
+
+/// Downloads the file chunks into its staged file, skipping the chunks which were verified by
+/// the previous download of the same file version. Returns the corrupted replicas
+async fn download_file(
+    c: &Client,
+    meta_url: &str,
+    file: &FileMetadata,
+    staging: &Path,
+) -> CCFSResult<Vec<Chunk>> {
+    let mut corrupted = Vec::new();
+    if let FileInfo::File { id, chunks, .. } = &file.file_info {
+        let mut journal = DownloadJournal::load(staging, *id, file.version).await?;
+        journal.remove_stale().await?;
+        let target_path = journal.staged_path();
+        let path = target_path.as_path();
+        if !path.exists() || !chunks.starts_with(&journal.completed) {
+            journal.completed.clear();
+        }
+        let done = journal.completed.len();
+        if done == chunks.len() {
+            return Ok(corrupted);
+        }
+        let groups = get_chunk_replicas(c, meta_url, id).await?;
+        if chunks[done..].iter().any(|id| !groups.contains_key(id)) {
+            return Err(SomeChunksNotAvailable.build().into());
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .open(path)
+            .await
+            .map_err(|source| BaseError::Create {
+                path: path.into(),
+                source,
+            })?;
+        // the content of the chunk which wasn't verified is discarded
+        let start = done as u64 * CHUNK_SIZE;
+        file.set_len(start)
+            .await
+            .map_err(|source| BaseError::Write {
+                path: path.into(),
+                source,
+            })?;
+        file.seek(SeekFrom::Start(start))
+            .await
+            .map_err(|source| BaseError::Write {
+                path: path.into(),
+                source,
+            })?;
+        let groups = &groups;
+        let downloads = chunks[done..].iter().map(|chunk_id| async move {
+            let result = fetch_checked_chunk(c, meta_url, &groups[chunk_id]).await;
+            (chunk_id, result)
+        });
+        // the chunks are downloaded concurrently, but written in the file order
+        let mut downloads =
+            futures::StreamExt::buffered(stream::iter(downloads), PARALLEL_DOWNLOADS);
+        while let Some((curr_chunk_id, result)) = downloads.next().await {
+            let (content, replicas) = result.map_err(|_| SomeChunksNotAvailable.build())?;
+            corrupted.extend(replicas);
+            file.write_all(&content)
+                .await
+                .map_err(|source| BaseError::Write {
+                    path: path.into(),
+                    source,
+                })?;
+            file.flush().await.map_err(|source| BaseError::Write {
+                path: path.into(),
+                source,
+            })?;
+            journal.completed.push(*curr_chunk_id);
+            journal.save().await?;
+        }
+    }
+    Ok(corrupted)
+}
+
+/// Returns the replicas of each file chunk, by the chunk id
+pub(crate) async fn get_chunk_replicas(
+    c: &Client,
+    meta_url: &str,
+    file_id: &Uuid,
+) -> CCFSResult<HashMap<Uuid, Vec<Chunk>>> {
+    let chunks_url = format!("{}/api/chunks/file/{}", meta_url, file_id);
+    let chunk_groups: Vec<Vec<Chunk>> = get_request_json(c, &chunks_url).await?;
+    Ok(chunk_groups
+        .into_iter()
+        .filter_map(|chunks| Some((chunks.first()?.id, chunks)))
+        .collect())
+}
+
+/// Writes `len` bytes of the file starting at `offset` to the writer, and returns
+/// the number of written bytes. Only the needed ranges of the chunks are downloaded.
+///
+/// The partial chunks can't be verified against the chunk checksums
+pub(crate) async fn read_range<W: AsyncWrite + Unpin>(
+    c: &Client,
+    meta_url: &str,
+    file: &FileMetadata,
+    offset: u64,
+    len: u64,
+    writer: &mut W,
+) -> CCFSResult<u64> {
+    let (id, size, chunks) = match &file.file_info {
+        FileInfo::File {
+            id, size, chunks, ..
+        } => (id, *size, chunks),
+        FileInfo::Directory { .. } => {
+            let path = file.name.clone().into();
+            return Err(BaseError::NotAFile { path }.into());
+        }
+    };
+    let ranges = chunk_ranges(offset, len, size);
+    if ranges.is_empty() {
+        return Ok(0);
+    }
+    let groups = get_chunk_replicas(c, meta_url, id).await?;
+    let mut written = 0;
+    for (index, range) in ranges {
+        let replicas = chunks
+            .get(index)
+            .and_then(|chunk_id| groups.get(chunk_id))
+            .ok_or_else(|| SomeChunksNotAvailable.build())?;
+        let content = download_chunk_range(c, replicas, meta_url, range).await?;
+        writer.write_all(&content).await.context(WriteOutput)?;
+        written += content.len() as u64;
+    }
+    Ok(written)
+}
+
+/// Downloads the range of the chunk, falling back to the next replica when it fails
+async fn download_chunk_range(
+    c: &Client,
+    chunks: &[Chunk],
+    meta_url: &str,
+    range: ByteRange,
+) -> CCFSResult<Vec<u8>> {
+    for chunk in chunks {
+        match download_replica_range(c, chunk, meta_url, range).await {
+            Ok(content) if content.len() as u64 == range.len() => return Ok(content),
+            _ => {}
+        }
+    }
+    let chunk_name = chunks[0].chunk_name();
+    Err(ChunkNotAvailable { chunk_name }.build().into())
+}
+
+async fn download_replica_range(
+    c: &Client,
+    chunk: &Chunk,
+    meta_url: &str,
+    range: ByteRange,
+) -> CCFSResult<Vec<u8>> {
+    let chunk_servers_url = format!("{}/api/servers/{}", meta_url, &chunk.server_id);
+    let server: ChunkServer = get_request_json(c, &chunk_servers_url).await?;
+    let url = format!("{}/api/download/{}", server.address, chunk.chunk_name());
+    let mut resp = c
+        .get(&url)
+        .insert_header((RANGE, range_header(&[range])))
+        .send()
+        .await
+        .map_err(|source| BaseError::FailedRequest { url, source })?;
+    // the servers which don't support ranges send the whole chunk
+    let skip = match resp.status() {
+        StatusCode::PARTIAL_CONTENT => 0,
+        StatusCode::OK => range.start as usize,
+        _ => {
+            let response = read_body(resp).await?;
+            return Err(BaseError::Unsuccessful { response }.into());
+        }
+    };
+    let mut content = Vec::new();
+    while let Some(bytes) = resp.next().await {
+        content.extend(bytes.context(ParseBytes)?);
+    }
+    Ok(content
+        .into_iter()
+        .skip(skip)
+        .take(range.len() as usize)
+        .collect())
+}
+
+/// Downloads the whole chunk into memory, falling back to the next replica
+/// when the download fails or the content doesn't match the chunk checksum
+pub(crate) async fn fetch_chunk(
+    c: Client,
+    meta_url: String,
+    chunks: Vec<Chunk>,
+) -> CCFSResult<Bytes> {
+    let (content, _) = fetch_checked_chunk(&c, &meta_url, &chunks).await?;
+    Ok(content)
+}
+
+/// Downloads the whole chunk into memory like `fetch_chunk`,
+/// and also returns the replicas whose content didn't match the checksum
+async fn fetch_checked_chunk(
+    c: &Client,
+    meta_url: &str,
+    chunks: &[Chunk],
+) -> CCFSResult<(Bytes, Vec<Chunk>)> {
+    let mut corrupted = Vec::new();
+    for chunk in chunks {
+        if let Ok(content) = fetch_replica(c, chunk, meta_url).await {
+            let mut hasher = Hasher::new();
+            hasher.update(&content);
+            match chunk.checksum {
+                Some(expected) if expected != hasher.finalize() => corrupted.push(*chunk),
+                _ => return Ok((content.freeze(), corrupted)),
+            }
+        }
+    }
+    let chunk_name = chunks.first().map(Chunk::chunk_name).unwrap_or_default();
+    Err(ChunkNotAvailable { chunk_name }.build().into())
+}
+
+async fn fetch_replica(c: &Client, chunk: &Chunk, meta_url: &str) -> CCFSResult<BytesMut> {
+    let chunk_servers_url = format!("{}/api/servers/{}", meta_url, &chunk.server_id);
+    let server: ChunkServer = get_request_json(c, &chunk_servers_url).await?;
+    let download_url = format!("{}/api/download/{}", server.address, chunk.chunk_name());
+    let mut payload = get_request(c, &download_url).await?;
+    let mut content = BytesMut::new();
+    while let Some(bytes) = payload.next().await {
+        content.extend_from_slice(&bytes.context(ParseBytes)?);
+    }
+    Ok(content)
+}
@@ -0,0 +1,253 @@
+//! Local records of the upload and download progress, which let the interrupted transfers be resumed
+
+use crate::errors::*;
+use ccfs_commons::checksum::Hasher;
+use ccfs_commons::{errors::Error as BaseError, result::CCFSResult};
+use serde::{Deserialize, Serialize};
+use snafu::ResultExt;
+use std::collections::BTreeMap;
+use std::fs::Metadata;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use tokio::fs::{create_dir_all, read_dir, read_to_string, remove_file, write};
+use uuid::Uuid;
+
+/// Uploaded local file or directory
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct JournalEntry {
+    /// Size of the local file when it was uploaded, the changed files are uploaded again
+    pub size: u64,
+    /// Modification time of the local file (in seconds) when it was uploaded
+    pub modified: u64,
+    /// Id of the created file, it isn't set for the directories
+    pub file_id: Option<Uuid>,
+    /// Chunks which were uploaded successfully
+    #[serde(default)]
+    pub completed: Vec<Uuid>,
+}
+
+impl JournalEntry {
+    fn new(metadata: &Metadata, file_id: Option<Uuid>) -> Self {
+        Self {
+            size: metadata.len(),
+            modified: modified_secs(metadata),
+            file_id,
+            completed: Vec::new(),
+        }
+    }
+}
+
+fn modified_secs(metadata: &Metadata) -> u64 {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |duration| duration.as_secs())
+}
+
+/// Progress of the upload of a local file or directory, stored in the journal dir
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct UploadJournal {
+    /// Local path of the uploaded file or directory
+    pub root: PathBuf,
+    /// Created files and directories, by their local paths
+    pub items: BTreeMap<PathBuf, JournalEntry>,
+    #[serde(skip)]
+    location: PathBuf,
+}
+
+impl UploadJournal {
+    /// Creates an empty journal of the upload of the root path
+    pub fn new(journal_dir: &Path, root: &Path) -> Self {
+        let mut hasher = Hasher::new();
+        hasher.update(root.to_string_lossy().as_bytes());
+        Self {
+            root: root.into(),
+            items: BTreeMap::new(),
+            location: journal_dir.join(format!("{:08x}.json", hasher.finalize())),
+        }
+    }
+
+    /// Loads the journal of the previous upload of the root path,
+    /// an empty journal is returned if there is no such upload
+    pub async fn load(journal_dir: &Path, root: &Path) -> CCFSResult<Self> {
+        let mut journal = Self::new(journal_dir, root);
+        if !journal.location.exists() {
+            return Ok(journal);
+        }
+        let path = journal.location.clone();
+        let content = read_to_string(&path)
+            .await
+            .map_err(|source| BaseError::Read {
+                path: path.clone(),
+                source,
+            })?;
+        let loaded: Self = serde_json::from_str(&content).context(InvalidJournal { path })?;
+        // a different root path with the same hash
+        if loaded.root == root {
+            journal.items = loaded.items;
+        }
+        Ok(journal)
+    }
+
+    pub async fn save(&self) -> CCFSResult<()> {
+        if let Some(dir) = self.location.parent() {
+            create_dir_all(dir)
+                .await
+                .map_err(|source| BaseError::Create {
+                    path: dir.into(),
+                    source,
+                })?;
+        }
+        let content = serde_json::to_string(self).context(InvalidJournal {
+            path: self.location.clone(),
+        })?;
+        write(&self.location, content)
+            .await
+            .map_err(|source| BaseError::Write {
+                path: self.location.clone(),
+                source,
+            })?;
+        Ok(())
+    }
+
+    /// Removes the journal of the completed upload
+    pub async fn remove(self) -> CCFSResult<()> {
+        if self.location.exists() {
+            remove_file(&self.location)
+                .await
+                .map_err(|source| BaseError::Remove {
+                    path: self.location,
+                    source,
+                })?;
+        }
+        Ok(())
+    }
+
+    /// Returns the recorded upload of the local path, unless the file was changed since then.
+    /// The directories are created only once, even if their content was changed
+    pub fn entry(&self, path: &Path, metadata: &Metadata) -> Option<&JournalEntry> {
+        let entry = self.items.get(path)?;
+        let unchanged = match entry.file_id {
+            Some(_) => entry.size == metadata.len() && entry.modified == modified_secs(metadata),
+            None => metadata.is_dir(),
+        };
+        match unchanged {
+            true => Some(entry),
+            false => None,
+        }
+    }
+
+    /// Records the created file (or directory, without the file id)
+    pub fn record(&mut self, path: &Path, metadata: &Metadata, file_id: Option<Uuid>) {
+        self.items
+            .insert(path.into(), JournalEntry::new(metadata, file_id));
+    }
+
+    /// Records the uploaded chunks of the file
+    pub fn record_chunks<T: IntoIterator<Item = Uuid>>(&mut self, path: &Path, chunks: T) {
+        if let Some(entry) = self.items.get_mut(path) {
+            entry.completed.extend(chunks);
+        }
+    }
+}
+
+/// Progress of the download of a file version into the staging dir. The chunks are downloaded
+/// in order, so the staged file holds the content of the completed chunks
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct DownloadJournal {
+    pub file_id: Uuid,
+    pub version: usize,
+    /// Chunks which were downloaded and verified
+    pub completed: Vec<Uuid>,
+    #[serde(skip)]
+    location: PathBuf,
+}
+
+impl DownloadJournal {
+    /// Creates an empty journal of the download of the file version
+    pub fn new(staging_dir: &Path, file_id: Uuid, version: usize) -> Self {
+        Self {
+            file_id,
+            version,
+            completed: Vec::new(),
+            location: staging_dir.join(format!("{}-{}.json", file_id, version)),
+        }
+    }
+
+    /// Loads the journal of the previous download of the file version,
+    /// an empty journal is returned if there is no such download
+    pub async fn load(staging_dir: &Path, file_id: Uuid, version: usize) -> CCFSResult<Self> {
+        let mut journal = Self::new(staging_dir, file_id, version);
+        if !journal.location.exists() {
+            return Ok(journal);
+        }
+        let path = journal.location.clone();
+        let content = read_to_string(&path)
+            .await
+            .map_err(|source| BaseError::Read {
+                path: path.clone(),
+                source,
+            })?;
+        let loaded: Self = serde_json::from_str(&content).context(InvalidJournal { path })?;
+        journal.completed = loaded.completed;
+        Ok(journal)
+    }
+
+    /// Local path of the staged file content
+    pub fn staged_path(&self) -> PathBuf {
+        self.location.with_extension("part")
+    }
+
+    pub async fn save(&self) -> CCFSResult<()> {
+        let content = serde_json::to_string(self).context(InvalidJournal {
+            path: self.location.clone(),
+        })?;
+        write(&self.location, content)
+            .await
+            .map_err(|source| BaseError::Write {
+                path: self.location.clone(),
+                source,
+            })?;
+        Ok(())
+    }
+
+    /// Removes the journal of the completed download
+    pub async fn remove(self) -> CCFSResult<()> {
+        if self.location.exists() {
+            remove_file(&self.location)
+                .await
+                .map_err(|source| BaseError::Remove {
+                    path: self.location,
+                    source,
+                })?;
+        }
+        Ok(())
+    }
+
+    /// Removes the staged content and journals of the other versions of the file
+    pub async fn remove_stale(&self) -> CCFSResult<()> {
+        let staging_dir = match self.location.parent() {
+            Some(dir) => dir,
+            None => return Ok(()),
+        };
+        let prefix = format!("{}-", self.file_id);
+        let current = format!("{}{}.", prefix, self.version);
+        let mut entries = read_dir(staging_dir)
+            .await
+            .map_err(|source| BaseError::Read {
+                path: staging_dir.into(),
+                source,
+            })?;
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if name.starts_with(&prefix) && !name.starts_with(&current) {
+                let path = entry.path();
+                remove_file(&path)
+                    .await
+                    .map_err(|source| BaseError::Remove { path, source })?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,13 @@
+//! Async client of the CCFS, used by the CLI and the services which embed the CCFS
+
+mod client;
+pub mod errors;
+mod files;
+mod journal;
+mod requests;
+mod streams;
+
+pub use client::CcfsClient;
+pub use files::{Download, STAGING_DIR};
+pub use journal::{DownloadJournal, JournalEntry, UploadJournal};
+pub use streams::{RemoteReader, RemoteWriter};
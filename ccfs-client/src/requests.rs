@@ -0,0 +1,114 @@
+//! Requests to the metadata and chunk servers, the writes sent to a metadata
+//! server which isn't the cluster leader are redirected to the leader
+
+use crate::errors::*;
+use actix_web::client::Client;
+use actix_web::http::header::{HeaderName, IF_MATCH};
+use actix_web::http::Method;
+use ccfs_commons::http_utils::{read_body, send_to_leader, Response};
+use ccfs_commons::{errors::Error as BaseError, result::CCFSResult};
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use serde::{de::DeserializeOwned, Serialize};
+use snafu::ResultExt;
+
+/// Characters which are encoded in the query params, all except the unreserved ones
+const QUERY_PARAM: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+/// Appends the query params to the url, they're encoded so that the paths
+/// with `&`, `#`, `%` or spaces reach the server as they are
+pub(crate) fn query_url(url: &str, params: &[(&str, &str)]) -> String {
+    let query = params
+        .iter()
+        .map(|(key, value)| format!("{}={}", key, utf8_percent_encode(value, QUERY_PARAM)))
+        .collect::<Vec<_>>()
+        .join("&");
+    format!("{}?{}", url, query)
+}
+
+/// Returns the url of the file metadata, or of the metadata of its previous version
+pub(crate) fn file_url(meta_url: &str, path: &str, version: Option<usize>) -> String {
+    let url = format!("{}/api/files", meta_url);
+    match version {
+        Some(version) => query_url(&url, &[("path", path), ("version", &version.to_string())]),
+        None => query_url(&url, &[("path", path)]),
+    }
+}
+
+/// Returns the header which makes the write conditional on the target file being
+/// at the expected version, otherwise the metadata server rejects it with a conflict
+fn if_match(expected_version: Option<usize>) -> Vec<(HeaderName, String)> {
+    match expected_version {
+        Some(version) => vec![(IF_MATCH, format!("\"{}\"", version))],
+        None => Vec::new(),
+    }
+}
+
+pub(crate) async fn get_request(c: &Client, url: &str) -> CCFSResult<Response> {
+    let resp = c
+        .get(url)
+        .send()
+        .await
+        .map_err(|source| BaseError::FailedRequest {
+            url: url.into(),
+            source,
+        })?;
+    match resp.status().is_success() {
+        true => Ok(resp),
+        false => Err(BaseError::Unsuccessful {
+            response: read_body(resp).await?,
+        }
+        .into()),
+    }
+}
+
+pub(crate) async fn get_request_json<T: DeserializeOwned>(c: &Client, url: &str) -> CCFSResult<T> {
+    let mut resp = get_request(c, url).await?;
+    Ok(resp.json().await.context(ParseJson)?)
+}
+
+pub(crate) async fn post_request<T: Serialize>(
+    c: &Client,
+    url: &str,
+    data: T,
+    expected_version: Option<usize>,
+) -> CCFSResult<Response> {
+    write_request(c, Method::POST, url, Some(&data), expected_version).await
+}
+
+pub(crate) async fn put_request(
+    c: &Client,
+    url: &str,
+    expected_version: Option<usize>,
+) -> CCFSResult<Response> {
+    write_request(c, Method::PUT, url, None::<&()>, expected_version).await
+}
+
+pub(crate) async fn delete_request(
+    c: &Client,
+    url: &str,
+    expected_version: Option<usize>,
+) -> CCFSResult<Response> {
+    write_request(c, Method::DELETE, url, None::<&()>, expected_version).await
+}
+
+/// Sends the write to the metadata cluster leader, and fails when it's unsuccessful
+async fn write_request<T: Serialize>(
+    c: &Client,
+    method: Method,
+    url: &str,
+    data: Option<&T>,
+    expected_version: Option<usize>,
+) -> CCFSResult<Response> {
+    let resp = send_to_leader(c, method, url, &if_match(expected_version), data).await?;
+    match resp.status().is_success() {
+        true => Ok(resp),
+        false => Err(BaseError::Unsuccessful {
+            response: read_body(resp).await?,
+        }
+        .into()),
+    }
+}
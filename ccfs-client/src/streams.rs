@@ -0,0 +1,378 @@
+//! Handles which read and write the CCFS files like the local ones, through the tokio io traits
+
+use crate::errors::*;
+use crate::files::{fetch_chunk, get_chunk_replicas, upload_buffer};
+use crate::requests::{file_url, get_request_json, post_request, query_url};
+use actix_web::client::Client;
+use actix_web::web::Bytes;
+use ccfs_commons::{errors::Error as BaseError, result::CCFSResult};
+use ccfs_commons::{Chunk, ChunkMetadata, ChunkPlacement, CHUNK_SIZE};
+use ccfs_commons::{FileInfo, FileMetadata, FileStatus, FileUpload};
+use futures::future::{FutureExt, LocalBoxFuture};
+use snafu::ResultExt;
+use std::collections::HashMap;
+use std::io::{self, SeekFrom};
+use std::path::Path;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncSeek, AsyncWrite, ReadBuf};
+use uuid::Uuid;
+
+fn io_error<T: std::fmt::Display>(err: T) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, err.to_string())
+}
+
+/// Opens the file at the path for reading
+pub(crate) async fn open(
+    c: &Client,
+    meta_url: &str,
+    path: &str,
+    version: Option<usize>,
+) -> CCFSResult<RemoteReader> {
+    let file: FileMetadata = get_request_json(c, &file_url(meta_url, path, version)).await?;
+    let (id, size, chunks) = match &file.file_info {
+        FileInfo::File {
+            id, size, chunks, ..
+        } => (*id, *size, chunks.clone()),
+        FileInfo::Directory { .. } => {
+            let path = path.into();
+            return Err(BaseError::NotAFile { path }.into());
+        }
+    };
+    let replicas = get_chunk_replicas(c, meta_url, &id).await?;
+    Ok(RemoteReader {
+        client: c.clone(),
+        meta_url: meta_url.into(),
+        file,
+        size,
+        chunks,
+        replicas,
+        position: 0,
+        current: None,
+        pending: None,
+    })
+}
+
+/// Chunk download, the finished ones are kept until they're read
+enum Fetch {
+    Pending(LocalBoxFuture<'static, CCFSResult<Bytes>>),
+    Done(CCFSResult<Bytes>),
+}
+
+/// Reader of a CCFS file. The chunks are downloaded whole when they're first read
+/// (and verified against their checksums), while the next chunk is downloaded ahead
+pub struct RemoteReader {
+    client: Client,
+    meta_url: String,
+    file: FileMetadata,
+    size: u64,
+    chunks: Vec<Uuid>,
+    replicas: HashMap<Uuid, Vec<Chunk>>,
+    position: u64,
+    current: Option<(usize, Bytes)>,
+    pending: Option<(usize, Fetch)>,
+}
+
+impl RemoteReader {
+    pub fn file(&self) -> &FileMetadata {
+        &self.file
+    }
+
+    fn fetch(&self, index: usize) -> Fetch {
+        let replicas = self.chunks.get(index).and_then(|id| self.replicas.get(id));
+        match replicas {
+            Some(replicas) => {
+                let fetch =
+                    fetch_chunk(self.client.clone(), self.meta_url.clone(), replicas.clone());
+                Fetch::Pending(fetch.boxed_local())
+            }
+            None => Fetch::Done(Err(SomeChunksNotAvailable.build().into())),
+        }
+    }
+
+    /// Drives the pending download, if there is one
+    fn poll_pending(&mut self, cx: &mut Context<'_>) {
+        if let Some((_, Fetch::Pending(fetch))) = &mut self.pending {
+            if let Poll::Ready(result) = fetch.as_mut().poll(cx) {
+                if let Some((_, pending)) = &mut self.pending {
+                    *pending = Fetch::Done(result);
+                }
+            }
+        }
+    }
+
+    /// Makes the `index` chunk the current one, downloading it if it isn't downloaded yet
+    fn poll_chunk(&mut self, cx: &mut Context<'_>, index: usize) -> Poll<io::Result<()>> {
+        loop {
+            if matches!(self.current, Some((i, _)) if i == index) {
+                return Poll::Ready(Ok(()));
+            }
+            match self.pending.take() {
+                Some((i, Fetch::Done(result))) if i == index => {
+                    self.current = Some((index, result.map_err(io_error)?));
+                }
+                Some((i, fetch)) if i == index => {
+                    self.pending = Some((i, fetch));
+                    self.poll_pending(cx);
+                    if let Some((_, Fetch::Pending(_))) = &self.pending {
+                        return Poll::Pending;
+                    }
+                }
+                // the read-ahead of another chunk is dropped after a seek
+                _ => self.pending = Some((index, self.fetch(index))),
+            }
+        }
+    }
+}
+
+impl AsyncRead for RemoteReader {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if this.position >= this.size || buf.remaining() == 0 {
+            return Poll::Ready(Ok(()));
+        }
+        let index = (this.position / CHUNK_SIZE) as usize;
+        futures::ready!(this.poll_chunk(cx, index))?;
+        if let Some((_, content)) = &this.current {
+            let offset = (this.position % CHUNK_SIZE) as usize;
+            let available = content.len().saturating_sub(offset);
+            if available == 0 {
+                let msg = format!("Chunk {} is shorter than expected", index);
+                return Poll::Ready(Err(io::Error::new(io::ErrorKind::UnexpectedEof, msg)));
+            }
+            let len = available.min(buf.remaining());
+            buf.put_slice(&content[offset..offset + len]);
+            this.position += len as u64;
+        }
+        if this.pending.is_none() && index + 1 < this.chunks.len() {
+            this.pending = Some((index + 1, this.fetch(index + 1)));
+        }
+        this.poll_pending(cx);
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncSeek for RemoteReader {
+    fn start_seek(self: Pin<&mut Self>, position: SeekFrom) -> io::Result<()> {
+        let this = self.get_mut();
+        let (base, offset) = match position {
+            SeekFrom::Start(position) => (position, 0),
+            SeekFrom::End(offset) => (this.size, offset),
+            SeekFrom::Current(offset) => (this.position, offset),
+        };
+        let position = match offset >= 0 {
+            true => base.checked_add(offset as u64),
+            false => base.checked_sub(offset.unsigned_abs()),
+        };
+        this.position = position.ok_or_else(|| {
+            let msg = "Invalid seek to a negative or overflowing position";
+            io::Error::new(io::ErrorKind::InvalidInput, msg)
+        })?;
+        Ok(())
+    }
+
+    fn poll_complete(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<u64>> {
+        Poll::Ready(Ok(self.position))
+    }
+}
+
+/// Creates the file at the path, whose content is written through the returned writer.
+/// With the expected version, the upload fails if the file is at another version
+pub(crate) async fn create(
+    c: &Client,
+    meta_url: &str,
+    path: &str,
+    replicas: Option<usize>,
+    expected_version: Option<usize>,
+) -> CCFSResult<RemoteWriter> {
+    let target = Path::new(path);
+    let (dir, name) = match (target.parent(), target.file_name()) {
+        (Some(dir), Some(name)) => (dir.display().to_string(), name.to_string_lossy()),
+        _ => {
+            let msg = format!("'{}' isn't a valid file path", path);
+            return Err(BaseError::InvalidPath { msg }.into());
+        }
+    };
+    let mut file = FileMetadata::create_file(name.into(), 0, Vec::new()).with_replicas(replicas);
+    if let FileInfo::File { status, .. } = &mut file.file_info {
+        *status = FileStatus::Open;
+    }
+    let upload_url = format!("{}/api/files/upload", meta_url);
+    let upload_url = query_url(&upload_url, &[("path", &dir)]);
+    let mut resp = post_request(c, &upload_url, file, expected_version).await?;
+    let upload: FileUpload = resp.json().await.context(ParseJson)?;
+    Ok(RemoteWriter {
+        client: c.clone(),
+        meta_url: meta_url.into(),
+        upload,
+        buffer: Vec::new(),
+        size: 0,
+        num_of_chunks: 0,
+        last_chunk_sent: false,
+        state: WriteState::Idle,
+    })
+}
+
+/// Appends a new chunk with the content to the open file
+async fn append_chunk(
+    c: Client,
+    meta_url: String,
+    file_id: Uuid,
+    metadata: ChunkMetadata,
+    content: Bytes,
+) -> CCFSResult<()> {
+    let chunk_id = Uuid::new_v4();
+    let url = format!("{}/api/files/{}/chunks", meta_url, file_id);
+    let mut resp = post_request(&c, &url, vec![chunk_id], None).await?;
+    let placement: Vec<ChunkPlacement> = resp.json().await.context(ParseJson)?;
+    let placement = match placement.first() {
+        Some(placement) if !placement.servers.is_empty() => placement,
+        _ => return Err(NoAvailableServers.build().into()),
+    };
+    let part = metadata.index;
+    upload_buffer(&c, placement, (&file_id, part), metadata, content).await
+}
+
+async fn commit(c: Client, meta_url: String, file_id: Uuid, size: u64) -> CCFSResult<FileMetadata> {
+    let url = format!("{}/api/files/{}/commit?size={}", meta_url, file_id, size);
+    let mut resp = post_request(&c, &url, (), None).await?;
+    Ok(resp.json().await.context(ParseJson)?)
+}
+
+enum WriteState {
+    Idle,
+    Uploading(LocalBoxFuture<'static, CCFSResult<()>>),
+    Committing(LocalBoxFuture<'static, CCFSResult<FileMetadata>>),
+    Committed(FileMetadata),
+    Failed,
+}
+
+/// Writer of a new CCFS file. The content is buffered into `CHUNK_SIZE` chunks,
+/// which are uploaded as they fill. Shutting the writer down uploads the last chunk
+/// and commits the file, which shows up in its directory after that
+pub struct RemoteWriter {
+    client: Client,
+    meta_url: String,
+    upload: FileUpload,
+    buffer: Vec<u8>,
+    size: u64,
+    num_of_chunks: usize,
+    last_chunk_sent: bool,
+    state: WriteState,
+}
+
+impl RemoteWriter {
+    /// Returns the file metadata, once it's committed
+    pub fn file(&self) -> Option<&FileMetadata> {
+        match &self.state {
+            WriteState::Committed(file) => Some(file),
+            _ => None,
+        }
+    }
+
+    /// Returns the id of the written file, which can be used to cancel its upload
+    pub fn file_id(&self) -> Uuid {
+        match &self.upload.file.file_info {
+            FileInfo::File { id, .. } => *id,
+            FileInfo::Directory { .. } => Uuid::nil(),
+        }
+    }
+
+    /// Starts uploading the buffered content as the next chunk
+    fn send_chunk(&mut self) {
+        let index = self.num_of_chunks;
+        let mut metadata = ChunkMetadata::new(&self.upload.path, &self.upload.file, index);
+        // the final size isn't known yet
+        metadata.size = self.size;
+        let content = Bytes::from(std::mem::take(&mut self.buffer));
+        self.last_chunk_sent = (content.len() as u64) < CHUNK_SIZE;
+        self.num_of_chunks += 1;
+        let (client, meta_url) = (self.client.clone(), self.meta_url.clone());
+        let upload = append_chunk(client, meta_url, self.file_id(), metadata, content);
+        self.state = WriteState::Uploading(upload.boxed_local());
+    }
+
+    /// Waits for the chunk upload, if there is one
+    fn poll_upload(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match &mut self.state {
+            WriteState::Idle => Poll::Ready(Ok(())),
+            WriteState::Uploading(upload) => {
+                let result = futures::ready!(upload.as_mut().poll(cx));
+                self.state = match result {
+                    Ok(()) => WriteState::Idle,
+                    Err(_) => WriteState::Failed,
+                };
+                Poll::Ready(result.map_err(io_error))
+            }
+            WriteState::Committing(_) | WriteState::Committed(_) => {
+                let msg = "The file is already committed";
+                Poll::Ready(Err(io::Error::new(io::ErrorKind::BrokenPipe, msg)))
+            }
+            WriteState::Failed => {
+                let msg = "The file upload failed";
+                Poll::Ready(Err(io::Error::new(io::ErrorKind::BrokenPipe, msg)))
+            }
+        }
+    }
+}
+
+impl AsyncWrite for RemoteWriter {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        futures::ready!(this.poll_upload(cx))?;
+        let len = buf.len().min(CHUNK_SIZE as usize - this.buffer.len());
+        this.buffer.extend_from_slice(&buf[..len]);
+        this.size += len as u64;
+        if this.buffer.len() as u64 == CHUNK_SIZE {
+            this.send_chunk();
+            if let Poll::Ready(Err(err)) = this.poll_upload(cx) {
+                return Poll::Ready(Err(err));
+            }
+        }
+        Poll::Ready(Ok(len))
+    }
+
+    /// Waits for the filled chunks to be uploaded, the last chunk is uploaded on shutdown
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.get_mut().poll_upload(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        loop {
+            match &mut this.state {
+                WriteState::Committing(commit) => {
+                    let result = futures::ready!(commit.as_mut().poll(cx));
+                    match result {
+                        Ok(file) => this.state = WriteState::Committed(file),
+                        Err(err) => {
+                            this.state = WriteState::Failed;
+                            return Poll::Ready(Err(io_error(err)));
+                        }
+                    }
+                }
+                WriteState::Committed(_) => return Poll::Ready(Ok(())),
+                _ => {
+                    futures::ready!(this.poll_upload(cx))?;
+                    // the number of chunks always follows the size, so a file whose size
+                    // is a multiple of the chunk size ends with an empty chunk
+                    if !this.last_chunk_sent {
+                        this.send_chunk();
+                        continue;
+                    }
+                    let (client, meta_url) = (this.client.clone(), this.meta_url.clone());
+                    let commit = commit(client, meta_url, this.file_id(), this.size);
+                    this.state = WriteState::Committing(commit.boxed_local());
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,430 @@
+use ccfs_client::{CcfsClient, DownloadJournal, STAGING_DIR};
+use ccfs_commons::{Chunk, ChunkServer, FileInfo, FileMetadata, CHUNK_SIZE};
+use httpmock::{Method, MockServer};
+use std::fs::{create_dir, read, write, File};
+use std::time::{Duration, Instant};
+use tempfile::tempdir;
+use tokio::fs::read_to_string;
+use uuid::Uuid;
+
+#[actix_rt::test]
+async fn test_stat_and_list() -> Result<(), Box<dyn std::error::Error>> {
+    let mut dir_resp = FileMetadata::create_dir("dir".into());
+    dir_resp.insert_dir("dir1")?;
+    dir_resp.insert_file("some.zip", 10, vec![Uuid::new_v4()])?;
+    let meta_server = MockServer::start();
+    let stat_mock = meta_server.mock(|when, then| {
+        when.method(Method::GET)
+            .path("/api/files")
+            .query_param("path", "/dir");
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body_obj(&dir_resp);
+    });
+
+    let client = CcfsClient::new(meta_server.base_url());
+    assert_eq!(client.stat("/dir").await?, dir_resp);
+    let names: Vec<String> = client
+        .list("/dir")
+        .await?
+        .into_iter()
+        .map(|f| f.name)
+        .collect();
+    assert_eq!(names, vec!["dir1", "some.zip"]);
+    stat_mock.assert_hits(2);
+    Ok(())
+}
+
+#[actix_rt::test]
+async fn test_list_file() -> Result<(), Box<dyn std::error::Error>> {
+    let file_resp = FileMetadata::create_file("test.txt".into(), 10, vec![Uuid::new_v4()]);
+    let meta_server = MockServer::start();
+    meta_server.mock(|when, then| {
+        when.method(Method::GET).path("/api/files");
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body_obj(&file_resp);
+    });
+
+    let client = CcfsClient::new(meta_server.base_url());
+    assert!(client.list("/test.txt").await.is_err());
+    Ok(())
+}
+
+#[actix_rt::test]
+async fn test_remove_and_set_replicas() -> Result<(), Box<dyn std::error::Error>> {
+    let file_resp = FileMetadata::create_file("test.txt".into(), 10, vec![Uuid::new_v4()]);
+    let meta_server = MockServer::start();
+    let remove_mock = meta_server.mock(|when, then| {
+        when.method(Method::DELETE)
+            .path("/api/files")
+            .query_param("path", "/test.txt")
+            .query_param("recursive", "false");
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body_obj(&file_resp);
+    });
+    let replicas_mock = meta_server.mock(|when, then| {
+        when.method(Method::PUT)
+            .path("/api/files/replicas")
+            .query_param("path", "/test.txt")
+            .query_param("replicas", "2");
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body_obj(&file_resp);
+    });
+
+    let client = CcfsClient::new(meta_server.base_url());
+    assert_eq!(client.set_replicas("/test.txt", Some(2)).await?, file_resp);
+    assert_eq!(client.remove("/test.txt", false).await?, file_resp);
+    replicas_mock.assert();
+    remove_mock.assert();
+    Ok(())
+}
+
+#[actix_rt::test]
+async fn test_paths_are_url_encoded() -> Result<(), Box<dyn std::error::Error>> {
+    const TEST_PATH: &str = "/dir #1/a&b 100%?.txt";
+    let file_resp = FileMetadata::create_file("a&b 100%?.txt".into(), 10, vec![Uuid::new_v4()]);
+    let meta_server = MockServer::start();
+    let remove_mock = meta_server.mock(|when, then| {
+        when.method(Method::DELETE)
+            .path("/api/files")
+            .query_param("path", TEST_PATH)
+            .query_param("recursive", "false");
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body_obj(&file_resp);
+    });
+
+    let replicas_mock = meta_server.mock(|when, then| {
+        when.method(Method::PUT)
+            .path("/api/files/replicas")
+            .query_param("path", TEST_PATH)
+            .query_param("replicas", "2");
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body_obj(&file_resp);
+    });
+
+    let versions_mock = meta_server.mock(|when, then| {
+        when.method(Method::PUT)
+            .path("/api/files/versions")
+            .query_param("path", TEST_PATH)
+            .query_param("versions", "1");
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body_obj(&file_resp);
+    });
+    let stat_mock = meta_server.mock(|when, then| {
+        when.method(Method::GET)
+            .path("/api/files")
+            .query_param("path", TEST_PATH)
+            .query_param("version", "1");
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body_obj(&file_resp);
+    });
+
+    let client = CcfsClient::new(meta_server.base_url());
+    assert_eq!(client.remove(TEST_PATH, false).await?, file_resp);
+    assert_eq!(client.set_replicas(TEST_PATH, Some(2)).await?, file_resp);
+    assert_eq!(client.set_kept_versions(TEST_PATH, 1).await?, file_resp);
+    assert_eq!(client.stat_version(TEST_PATH, 1).await?, file_resp);
+    remove_mock.assert();
+    replicas_mock.assert();
+    versions_mock.assert();
+    stat_mock.assert();
+    Ok(())
+}
+
+#[actix_rt::test]
+async fn test_redirect_loop_fails() -> Result<(), Box<dyn std::error::Error>> {
+    let meta_server = MockServer::start();
+    // a follower with stale leader info which points at itself
+    let location = meta_server.url("/api/files?path=%2Ftest.txt&recursive=false");
+    let redirect_mock = meta_server.mock(|when, then| {
+        when.method(Method::DELETE).path("/api/files");
+        then.status(307).header("location", &location);
+    });
+
+    let client = CcfsClient::new(meta_server.base_url());
+    let err = client.remove("/test.txt", false).await.unwrap_err();
+    assert!(err.to_string().contains("redirected too many times"));
+    redirect_mock.assert_hits(4);
+    Ok(())
+}
+
+#[actix_rt::test]
+async fn test_conditional_changes() -> Result<(), Box<dyn std::error::Error>> {
+    let file_resp = FileMetadata::create_file("test.txt".into(), 10, vec![Uuid::new_v4()]);
+    let meta_server = MockServer::start();
+    let remove_mock = meta_server.mock(|when, then| {
+        when.method(Method::DELETE)
+            .path("/api/files")
+            .query_param("path", "/test.txt")
+            .header("if-match", "\"3\"");
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body_obj(&file_resp);
+    });
+    let versions_mock = meta_server.mock(|when, then| {
+        when.method(Method::PUT)
+            .path("/api/files/versions")
+            .header("if-match", "\"2\"");
+        then.status(409)
+            .body("Expected version 2 of the file, but it's at version 3");
+    });
+
+    let client = CcfsClient::new(meta_server.base_url());
+    let set_versions = client.set_kept_versions_if_version("/test.txt", 1, Some(2));
+    let err = set_versions.await.unwrap_err();
+    assert!(err.to_string().contains("but it's at version 3"));
+    let removed = client
+        .remove_if_version("/test.txt", false, Some(3))
+        .await?;
+    assert_eq!(removed, file_resp);
+    versions_mock.assert();
+    remove_mock.assert();
+    Ok(())
+}
+
+#[actix_rt::test]
+async fn test_download_reports_corrupted_replicas() -> Result<(), Box<dyn std::error::Error>> {
+    let chunk_id = Uuid::new_v4();
+    let file_resp = FileMetadata::create_file("test.txt".into(), 17, vec![chunk_id]);
+    let file_id = match &file_resp.file_info {
+        FileInfo::File { id, .. } => *id,
+        _ => unreachable!(),
+    };
+    let (bad_server_id, good_server_id) = (Uuid::new_v4(), Uuid::new_v4());
+    let mut corrupted = Chunk::new(chunk_id, file_id, bad_server_id);
+    corrupted.checksum = Some(0);
+    let valid = Chunk::new(chunk_id, file_id, good_server_id);
+
+    let bad_server = MockServer::start();
+    let good_server = MockServer::start();
+    for server in [&bad_server, &good_server].iter() {
+        server.mock(|when, then| {
+            when.method(Method::GET)
+                .path(format!("/api/download/{}", valid.chunk_name()));
+            then.status(200).body("Test file content");
+        });
+    }
+    let meta_server = MockServer::start();
+    meta_server.mock(|when, then| {
+        when.method(Method::GET)
+            .path("/api/files")
+            .query_param("path", "test.txt");
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body_obj(&file_resp);
+    });
+    meta_server.mock(|when, then| {
+        when.method(Method::GET)
+            .path(format!("/api/chunks/file/{}", file_id));
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body_obj(&vec![vec![corrupted, valid]]);
+    });
+    for (id, server) in [(bad_server_id, &bad_server), (good_server_id, &good_server)].iter() {
+        let server_val = ChunkServer::new(*id, server.base_url());
+        meta_server.mock(|when, then| {
+            when.method(Method::GET)
+                .path(format!("/api/servers/{}", id));
+            then.status(200)
+                .header("content-type", "application/json")
+                .json_body_obj(&server_val);
+        });
+    }
+
+    let target_dir = tempdir()?;
+    let client = CcfsClient::new(meta_server.base_url());
+    let download = client
+        .download("test.txt", target_dir.path(), false)
+        .await?;
+    assert_eq!(download.file, file_resp);
+    assert_eq!(download.path, target_dir.path().join("test.txt"));
+    assert_eq!(download.corrupted_replicas, vec![corrupted]);
+    assert_eq!(read_to_string(&download.path).await?, "Test file content");
+
+    // the existing file isn't replaced without force
+    assert!(client
+        .download("test.txt", target_dir.path(), false)
+        .await
+        .is_err());
+    Ok(())
+}
+
+#[actix_rt::test]
+async fn test_download_resumes_staged_chunks() -> Result<(), Box<dyn std::error::Error>> {
+    let chunk_ids = vec![Uuid::new_v4(), Uuid::new_v4()];
+    let file_resp = FileMetadata::create_file("test.txt".into(), CHUNK_SIZE + 5, chunk_ids.clone());
+    let file_id = match &file_resp.file_info {
+        FileInfo::File { id, .. } => *id,
+        _ => unreachable!(),
+    };
+    let server_id = Uuid::new_v4();
+    let (first, second) = (
+        Chunk::new(chunk_ids[0], file_id, server_id),
+        Chunk::new(chunk_ids[1], file_id, server_id),
+    );
+
+    let chunk_server = MockServer::start();
+    let first_mock = chunk_server.mock(|when, then| {
+        when.method(Method::GET)
+            .path(format!("/api/download/{}", first.chunk_name()));
+        then.status(500);
+    });
+    let second_mock = chunk_server.mock(|when, then| {
+        when.method(Method::GET)
+            .path(format!("/api/download/{}", second.chunk_name()));
+        then.status(200).body("Hello");
+    });
+    let meta_server = MockServer::start();
+    meta_server.mock(|when, then| {
+        when.method(Method::GET)
+            .path("/api/files")
+            .query_param("path", "test.txt");
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body_obj(&file_resp);
+    });
+    meta_server.mock(|when, then| {
+        when.method(Method::GET)
+            .path(format!("/api/chunks/file/{}", file_id));
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body_obj(&vec![vec![first], vec![second]]);
+    });
+    let server_val = ChunkServer::new(server_id, chunk_server.base_url());
+    meta_server.mock(|when, then| {
+        when.method(Method::GET)
+            .path(format!("/api/servers/{}", server_id));
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body_obj(&server_val);
+    });
+
+    // the first chunk was verified by the interrupted download, which also staged a part of
+    // the second chunk and an older version of the file
+    let target_dir = tempdir()?;
+    let staging = target_dir.path().join(STAGING_DIR);
+    create_dir(&staging)?;
+    let mut journal = DownloadJournal::new(&staging, file_id, file_resp.version);
+    journal.completed.push(chunk_ids[0]);
+    journal.save().await?;
+    File::create(journal.staged_path())?.set_len(CHUNK_SIZE + 2)?;
+    let stale = staging.join(format!("{}-0.part", file_id));
+    write(&stale, "Old content")?;
+
+    let client = CcfsClient::new(meta_server.base_url());
+    let download = client
+        .download("test.txt", target_dir.path(), false)
+        .await?;
+    assert_eq!(download.path, target_dir.path().join("test.txt"));
+    let content = read(&download.path)?;
+    assert_eq!(content.len() as u64, CHUNK_SIZE + 5);
+    assert_eq!(&content[CHUNK_SIZE as usize..], b"Hello");
+    first_mock.assert_hits(0);
+    second_mock.assert_hits(1);
+    // the staging dir is removed with the completed download
+    assert!(!staging.exists());
+    Ok(())
+}
+
+#[actix_rt::test]
+async fn test_download_fetches_chunks_concurrently() -> Result<(), Box<dyn std::error::Error>> {
+    let chunk_ids = (0..4).map(|_| Uuid::new_v4()).collect::<Vec<_>>();
+    let file_resp = FileMetadata::create_file("test.txt".into(), 4, chunk_ids.clone());
+    let file_id = match &file_resp.file_info {
+        FileInfo::File { id, .. } => *id,
+        _ => unreachable!(),
+    };
+    let server_id = Uuid::new_v4();
+    let chunks = chunk_ids
+        .iter()
+        .map(|id| Chunk::new(*id, file_id, server_id))
+        .collect::<Vec<_>>();
+
+    let chunk_server = MockServer::start();
+    // the first chunks take the longest, so they'd be written out of order if they weren't
+    // written in the file order
+    for (index, (chunk, content)) in chunks.iter().zip(["a", "b", "c", "d"].iter()).enumerate() {
+        chunk_server.mock(|when, then| {
+            when.method(Method::GET)
+                .path(format!("/api/download/{}", chunk.chunk_name()));
+            then.status(200)
+                .body(content)
+                .delay(Duration::from_millis(200 * (4 - index as u64)));
+        });
+    }
+    let meta_server = MockServer::start();
+    meta_server.mock(|when, then| {
+        when.method(Method::GET).path("/api/files");
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body_obj(&file_resp);
+    });
+    let groups = chunks.iter().map(|c| vec![*c]).collect::<Vec<_>>();
+    meta_server.mock(|when, then| {
+        when.method(Method::GET)
+            .path(format!("/api/chunks/file/{}", file_id));
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body_obj(&groups);
+    });
+    let server_val = ChunkServer::new(server_id, chunk_server.base_url());
+    meta_server.mock(|when, then| {
+        when.method(Method::GET)
+            .path(format!("/api/servers/{}", server_id));
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body_obj(&server_val);
+    });
+
+    let target_dir = tempdir()?;
+    let client = CcfsClient::new(meta_server.base_url());
+    let started = Instant::now();
+    let download = client
+        .download("test.txt", target_dir.path(), false)
+        .await?;
+    // the chunks take 2s when they're downloaded one by one
+    assert!(started.elapsed() < Duration::from_millis(1600));
+    assert_eq!(read_to_string(&download.path).await?, "abcd");
+    Ok(())
+}
+
+#[actix_rt::test]
+async fn test_failed_download_keeps_staging_dir() -> Result<(), Box<dyn std::error::Error>> {
+    let chunk_id = Uuid::new_v4();
+    let file_resp = FileMetadata::create_file("test.txt".into(), 17, vec![chunk_id]);
+    let file_id = match &file_resp.file_info {
+        FileInfo::File { id, .. } => *id,
+        _ => unreachable!(),
+    };
+    let meta_server = MockServer::start();
+    meta_server.mock(|when, then| {
+        when.method(Method::GET).path("/api/files");
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body_obj(&file_resp);
+    });
+    meta_server.mock(|when, then| {
+        when.method(Method::GET)
+            .path(format!("/api/chunks/file/{}", file_id));
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body_obj(&Vec::<Vec<Chunk>>::new());
+    });
+
+    let target_dir = tempdir()?;
+    let client = CcfsClient::new(meta_server.base_url());
+    assert!(client
+        .download("test.txt", target_dir.path(), false)
+        .await
+        .is_err());
+    assert!(target_dir.path().join(STAGING_DIR).exists());
+    assert!(!target_dir.path().join("test.txt").exists());
+    Ok(())
+}
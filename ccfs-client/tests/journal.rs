@@ -0,0 +1,53 @@
+use ccfs_client::UploadJournal;
+use std::fs::{metadata, write};
+use tempfile::tempdir;
+use uuid::Uuid;
+
+#[actix_rt::test]
+async fn test_journal_save_and_load() -> Result<(), Box<dyn std::error::Error>> {
+    let journal_dir = tempdir()?;
+    let files_dir = tempdir()?;
+    let file_path = files_dir.path().join("test.txt");
+    write(&file_path, "Test file content")?;
+    let (file_id, chunk_id) = (Uuid::new_v4(), Uuid::new_v4());
+
+    let mut journal = UploadJournal::new(journal_dir.path(), files_dir.path());
+    journal.record(files_dir.path(), &metadata(files_dir.path())?, None);
+    journal.record(&file_path, &metadata(&file_path)?, Some(file_id));
+    journal.record_chunks(&file_path, vec![chunk_id]);
+    journal.save().await?;
+
+    let loaded = UploadJournal::load(journal_dir.path(), files_dir.path()).await?;
+    assert_eq!(loaded, journal);
+    let entry = loaded.entry(&file_path, &metadata(&file_path)?).unwrap();
+    assert_eq!(entry.file_id, Some(file_id));
+    assert_eq!(entry.completed, vec![chunk_id]);
+    // the directories are matched even when their content changes
+    write(files_dir.path().join("new.txt"), "New file")?;
+    assert!(loaded
+        .entry(files_dir.path(), &metadata(files_dir.path())?)
+        .is_some());
+
+    // the journals of the other paths are separate
+    let other = UploadJournal::load(journal_dir.path(), &file_path).await?;
+    assert!(other.items.is_empty());
+
+    loaded.remove().await?;
+    assert_eq!(journal_dir.path().read_dir()?.count(), 0);
+    Ok(())
+}
+
+#[actix_rt::test]
+async fn test_journal_changed_file() -> Result<(), Box<dyn std::error::Error>> {
+    let journal_dir = tempdir()?;
+    let files_dir = tempdir()?;
+    let file_path = files_dir.path().join("test.txt");
+    write(&file_path, "Test file content")?;
+
+    let mut journal = UploadJournal::new(journal_dir.path(), &file_path);
+    journal.record(&file_path, &metadata(&file_path)?, Some(Uuid::new_v4()));
+    assert!(journal.entry(&file_path, &metadata(&file_path)?).is_some());
+    write(&file_path, "Changed content")?;
+    assert!(journal.entry(&file_path, &metadata(&file_path)?).is_none());
+    Ok(())
+}
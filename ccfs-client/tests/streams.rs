@@ -0,0 +1,192 @@
+use ccfs_client::CcfsClient;
+use ccfs_commons::{Chunk, ChunkPlacement, ChunkServer, FileInfo, FileMetadata, FileUpload};
+use httpmock::{Method, MockServer};
+use std::io::SeekFrom;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use uuid::Uuid;
+
+#[actix_rt::test]
+async fn test_read_and_seek() -> Result<(), Box<dyn std::error::Error>> {
+    let chunk_id = Uuid::new_v4();
+    let server_id = Uuid::new_v4();
+    let file_resp = FileMetadata::create_file("test.txt".into(), 17, vec![chunk_id]);
+    let file_id = match &file_resp.file_info {
+        FileInfo::File { id, .. } => *id,
+        _ => unreachable!(),
+    };
+    let chunk = Chunk::new(chunk_id, file_id, server_id);
+
+    let chunk_server = MockServer::start();
+    let download_mock = chunk_server.mock(|when, then| {
+        when.method(Method::GET)
+            .path(format!("/api/download/{}", chunk.chunk_name()));
+        then.status(200).body("Test file content");
+    });
+    let meta_server = MockServer::start();
+    meta_server.mock(|when, then| {
+        when.method(Method::GET)
+            .path("/api/files")
+            .query_param("path", "/test.txt");
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body_obj(&file_resp);
+    });
+    meta_server.mock(|when, then| {
+        when.method(Method::GET)
+            .path(format!("/api/chunks/file/{}", file_id));
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body_obj(&vec![vec![chunk]]);
+    });
+    let chunk_server_val = ChunkServer::new(server_id, chunk_server.base_url());
+    meta_server.mock(|when, then| {
+        when.method(Method::GET)
+            .path(format!("/api/servers/{}", server_id));
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body_obj(&chunk_server_val);
+    });
+
+    let client = CcfsClient::new(meta_server.base_url());
+    let mut reader = client.open("/test.txt").await?;
+    assert_eq!(reader.file(), &file_resp);
+    let mut content = String::new();
+    reader.read_to_string(&mut content).await?;
+    assert_eq!(content, "Test file content");
+
+    assert_eq!(reader.seek(SeekFrom::Start(5)).await?, 5);
+    let mut word = [0; 4];
+    reader.read_exact(&mut word).await?;
+    assert_eq!(&word, b"file");
+    assert_eq!(reader.seek(SeekFrom::End(-7)).await?, 10);
+    content.clear();
+    reader.read_to_string(&mut content).await?;
+    assert_eq!(content, "content");
+    assert!(reader.seek(SeekFrom::Current(-20)).await.is_err());
+    // the downloaded chunk is kept for the following reads
+    download_mock.assert_hits(1);
+    Ok(())
+}
+
+#[actix_rt::test]
+async fn test_open_dir() -> Result<(), Box<dyn std::error::Error>> {
+    let meta_server = MockServer::start();
+    meta_server.mock(|when, then| {
+        when.method(Method::GET).path("/api/files");
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body_obj(&FileMetadata::create_dir("dir".into()));
+    });
+
+    let client = CcfsClient::new(meta_server.base_url());
+    assert!(client.open("/dir").await.is_err());
+    Ok(())
+}
+
+#[actix_rt::test]
+async fn test_write_and_commit() -> Result<(), Box<dyn std::error::Error>> {
+    let file = FileMetadata::create_file("test.txt".into(), 0, Vec::new());
+    let file_id = match &file.file_info {
+        FileInfo::File { id, .. } => *id,
+        _ => unreachable!(),
+    };
+    let chunk_server = MockServer::start();
+    let upload_mock = chunk_server.mock(|when, then| {
+        when.method(Method::POST)
+            .path("/api/upload")
+            .body_contains("Test file content")
+            .body_contains("\"path\":\"/dir\"")
+            .body_contains("\"size\":17");
+        then.status(200);
+    });
+    let meta_server = MockServer::start();
+    let create_mock = meta_server.mock(|when, then| {
+        when.method(Method::POST)
+            .path("/api/files/upload")
+            .query_param("path", "/dir")
+            .body_contains("\"Open\"");
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body_obj(&FileUpload {
+                file: file.clone(),
+                path: "/dir".into(),
+                placement: Vec::new(),
+            });
+    });
+    let placement = vec![ChunkPlacement {
+        chunk_id: Uuid::new_v4(),
+        servers: vec![ChunkServer::new(Uuid::new_v4(), chunk_server.base_url())],
+    }];
+    let append_mock = meta_server.mock(|when, then| {
+        when.method(Method::POST)
+            .path(format!("/api/files/{}/chunks", file_id));
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body_obj(&placement);
+    });
+    let committed = FileMetadata::create_file("test.txt".into(), 17, vec![Uuid::new_v4()]);
+    let commit_mock = meta_server.mock(|when, then| {
+        when.method(Method::POST)
+            .path(format!("/api/files/{}/commit", file_id))
+            .query_param("size", "17");
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body_obj(&committed);
+    });
+
+    let client = CcfsClient::new(meta_server.base_url());
+    let mut writer = client.create("/dir/test.txt", None).await?;
+    writer.write_all(b"Test file ").await?;
+    writer.write_all(b"content").await?;
+    writer.flush().await?;
+    // the last chunk is uploaded on shutdown
+    append_mock.assert_hits(0);
+    assert!(writer.file().is_none());
+    writer.shutdown().await?;
+    assert_eq!(writer.file(), Some(&committed));
+    assert!(writer.write_all(b"more").await.is_err());
+
+    create_mock.assert();
+    append_mock.assert();
+    upload_mock.assert();
+    commit_mock.assert();
+    Ok(())
+}
+
+#[actix_rt::test]
+async fn test_write_upload_failure() -> Result<(), Box<dyn std::error::Error>> {
+    let file = FileMetadata::create_file("test.txt".into(), 0, Vec::new());
+    let meta_server = MockServer::start();
+    meta_server.mock(|when, then| {
+        when.method(Method::POST).path("/api/files/upload");
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body_obj(&FileUpload {
+                file: file.clone(),
+                path: "".into(),
+                placement: Vec::new(),
+            });
+    });
+    let no_servers: Vec<ChunkPlacement> = vec![ChunkPlacement {
+        chunk_id: Uuid::new_v4(),
+        servers: Vec::new(),
+    }];
+    meta_server.mock(|when, then| {
+        when.method(Method::POST).path_contains("/chunks");
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body_obj(&no_servers);
+    });
+    let commit_mock = meta_server.mock(|when, then| {
+        when.method(Method::POST).path_contains("/commit");
+        then.status(200);
+    });
+
+    let client = CcfsClient::new(meta_server.base_url());
+    let mut writer = client.create("test.txt", Some(2)).await?;
+    writer.write_all(b"Test file content").await?;
+    assert!(writer.shutdown().await.is_err());
+    assert!(writer.file().is_none());
+    commit_mock.assert_hits(0);
+    Ok(())
+}
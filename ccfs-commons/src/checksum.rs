@@ -0,0 +1,40 @@
+//! CRC32 checksums of the chunk contents, which are recorded when the chunk is uploaded
+//! and verified whenever it's transferred
+
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+pub use crc32fast::Hasher;
+
+const BUFFER_SIZE: usize = 64 * 1024;
+
+/// Computes the checksum of the whole reader content
+pub async fn checksum_reader<R: AsyncRead + Unpin>(mut reader: R) -> std::io::Result<u32> {
+    let mut hasher = Hasher::new();
+    let mut buffer = vec![0; BUFFER_SIZE];
+    loop {
+        match reader.read(&mut buffer).await? {
+            0 => return Ok(hasher.finalize()),
+            read => hasher.update(&buffer[..read]),
+        }
+    }
+}
+
+/// Formats the checksum the way it's sent in the multipart forms and stored on the chunk servers
+///
+/// Examples:
+/// ```
+/// use ccfs_commons::checksum::{format_checksum, parse_checksum};
+///
+/// assert_eq!(format_checksum(0x1c291ca3), "1c291ca3");
+/// assert_eq!(format_checksum(0xabcd), "0000abcd");
+/// assert_eq!(parse_checksum("1c291ca3"), Some(0x1c291ca3));
+/// assert_eq!(parse_checksum(" 1c291ca3\n"), Some(0x1c291ca3));
+/// assert_eq!(parse_checksum("checksum"), None);
+/// ```
+pub fn format_checksum(checksum: u32) -> String {
+    format!("{:08x}", checksum)
+}
+
+pub fn parse_checksum(text: &str) -> Option<u32> {
+    u32::from_str_radix(text.trim(), 16).ok()
+}
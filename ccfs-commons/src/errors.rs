@@ -71,6 +71,9 @@ pub enum Error {
         url: String,
     },
 
+    #[snafu(display("Request to {} was redirected too many times", url))]
+    TooManyRedirects { url: String },
+
     #[snafu(display("'{}' is not a directory", path.display()))]
     NotADir { path: PathBuf },
 
@@ -99,6 +102,7 @@ impl ResponseError for Error {
             | NotAFile { .. }
             | NotExist { .. }
             | FailedRequest { .. }
+            | TooManyRedirects { .. }
             | Unsuccessful { .. } => ErrorInternalServerError(display).into(),
             InvalidPath { .. } | ParseString { .. } | ParseUuid { .. } => {
                 ErrorBadRequest(display).into()
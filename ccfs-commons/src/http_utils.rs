@@ -1,11 +1,14 @@
+use crate::checksum::{format_checksum, Hasher};
 use crate::result::CCFSResult;
-use crate::{chunk_name, errors::*};
+use crate::{chunk_name, errors::*, ChunkMetadata};
 use actix_multipart::Field;
-use actix_web::client::ClientResponse;
+use actix_web::client::{Client, ClientResponse};
 use actix_web::dev::{Decompress, Payload};
-use actix_web::http::HeaderMap;
+use actix_web::http::header::{HeaderName, LOCATION};
+use actix_web::http::{HeaderMap, Method, StatusCode};
 use futures_util::StreamExt;
 use mpart_async::client::MultipartRequest;
+use serde::Serialize;
 use snafu::ResultExt;
 use std::path::Path;
 use tokio::fs::File;
@@ -30,27 +33,85 @@ pub async fn handle_string(mut data: Field) -> CCFSResult<String> {
     Ok(String::from_utf8(content).context(ParseString)?)
 }
 
-pub async fn handle_file(mut data: Field, path: &Path) -> CCFSResult<()> {
+/// Writes the field content to the file, and returns its checksum
+pub async fn handle_file(mut data: Field, path: &Path) -> CCFSResult<u32> {
     let mut f = File::create(path).await.context(Create { path })?;
+    let mut hasher = Hasher::new();
     while let Some(Ok(bytes)) = data.next().await {
+        hasher.update(&bytes);
         f.write_all(&bytes).await.context(Write { path })?;
     }
-    Ok(())
+    Ok(hasher.finalize())
 }
 
 pub fn get_header<'a>(headers: &'a HeaderMap, key: &'a str) -> Option<&'a str> {
     headers.get(key)?.to_str().ok()
 }
 
+/// Number of redirects which are followed before a write fails, so that the followers
+/// with stale leader info which point at each other don't redirect forever
+pub const MAX_REDIRECTS: usize = 3;
+
+/// Returns the target of a `307 Temporary Redirect` response, which the
+/// metadata servers send for writes when they are not the cluster leader
+pub fn get_redirect_location(resp: &Response) -> Option<String> {
+    match resp.status() {
+        StatusCode::TEMPORARY_REDIRECT => {
+            get_header(resp.headers(), LOCATION.as_str()).map(String::from)
+        }
+        _ => None,
+    }
+}
+
+/// Sends the request to a metadata server, and follows the redirects to the cluster
+/// leader up to `MAX_REDIRECTS` times. The headers are sent with every redirected request
+pub async fn send_to_leader<T: Serialize>(
+    client: &Client,
+    method: Method,
+    url: &str,
+    headers: &[(HeaderName, String)],
+    body: Option<&T>,
+) -> CCFSResult<Response> {
+    let mut url = url.to_string();
+    let mut redirects = 0;
+    loop {
+        let mut request = client.request(method.clone(), &url);
+        for header in headers {
+            request = request.insert_header(header.clone());
+        }
+        let sent = match body {
+            Some(body) => request.send_json(body).await,
+            None => request.send().await,
+        };
+        let resp = sent.context(FailedRequest { url: url.clone() })?;
+        match get_redirect_location(&resp) {
+            Some(_) if redirects == MAX_REDIRECTS => {
+                return Err(TooManyRedirects { url }.build().into())
+            }
+            Some(location) => {
+                url = location;
+                redirects += 1;
+            }
+            None => return Ok(resp),
+        }
+    }
+}
+
 pub fn create_ccfs_multipart<T: AsyncRead + Unpin>(
     chunk_id: &str,
     file_id: &str,
+    checksum: u32,
+    metadata: Option<&ChunkMetadata>,
     stream: ReaderStream<T>,
 ) -> MultipartRequest<ReaderStream<T>> {
     let chunk_file_name = chunk_name(&file_id, &chunk_id);
     let mut mpart = MultipartRequest::default();
     mpart.add_field("chunk_id", &chunk_id);
     mpart.add_field("file_id", &file_id);
+    mpart.add_field("checksum", &format_checksum(checksum));
+    if let Some(metadata) = metadata {
+        mpart.add_field("metadata", &serde_json::to_string(metadata).unwrap());
+    }
     mpart.add_stream("file", &chunk_file_name, "application/octet-stream", stream);
     mpart
 }
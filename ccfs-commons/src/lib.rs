@@ -1,7 +1,9 @@
+pub mod checksum;
 pub mod data;
 pub mod errors;
 pub mod http_utils;
 pub mod path;
+pub mod range;
 pub mod result;
 pub mod test_utils;
 
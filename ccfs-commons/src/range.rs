@@ -0,0 +1,139 @@
+//! Byte ranges of the chunks, requested with the `Range` header and
+//! returned along with the `Content-Range` header
+
+use crate::CHUNK_SIZE;
+
+/// Inclusive range of bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ByteRange {
+    pub start: u64,
+    pub end: u64,
+}
+
+impl ByteRange {
+    pub fn new(start: u64, end: u64) -> Self {
+        Self { start, end }
+    }
+
+    #[allow(clippy::len_without_is_empty)]
+    pub fn len(&self) -> u64 {
+        self.end - self.start + 1
+    }
+
+    /// Value of the `Content-Range` header for the range of the `size` bytes long content
+    ///
+    /// Examples:
+    /// ```
+    /// use ccfs_commons::range::ByteRange;
+    ///
+    /// assert_eq!(ByteRange::new(0, 9).content_range(100), "bytes 0-9/100");
+    /// ```
+    pub fn content_range(&self, size: u64) -> String {
+        format!("bytes {}-{}/{}", self.start, self.end, size)
+    }
+}
+
+/// Value of the `Range` header requesting the ranges
+///
+/// Examples:
+/// ```
+/// use ccfs_commons::range::{range_header, ByteRange};
+///
+/// assert_eq!(range_header(&[ByteRange::new(0, 9)]), "bytes=0-9");
+/// assert_eq!(range_header(&[ByteRange::new(0, 9), ByteRange::new(20, 29)]), "bytes=0-9,20-29");
+/// ```
+pub fn range_header(ranges: &[ByteRange]) -> String {
+    let specs = ranges
+        .iter()
+        .map(|r| format!("{}-{}", r.start, r.end))
+        .collect::<Vec<_>>();
+    format!("bytes={}", specs.join(","))
+}
+
+/// Parses the `Range` header value into the ranges of the `size` bytes long content.
+///
+/// Returns `None` when the header is malformed (so it should be ignored), and no ranges
+/// when none of them is satisfiable. The ranges past the end of the content are skipped,
+/// and the ones ending past it are shortened
+///
+/// Examples:
+/// ```
+/// use ccfs_commons::range::{parse_range_header, ByteRange};
+///
+/// assert_eq!(parse_range_header("bytes=0-9", 100), Some(vec![ByteRange::new(0, 9)]));
+/// assert_eq!(parse_range_header("bytes=90-", 100), Some(vec![ByteRange::new(90, 99)]));
+/// assert_eq!(parse_range_header("bytes=-10", 100), Some(vec![ByteRange::new(90, 99)]));
+/// assert_eq!(parse_range_header("bytes=90-200", 100), Some(vec![ByteRange::new(90, 99)]));
+/// assert_eq!(
+///     parse_range_header("bytes=0-0, 200-300, 10-19", 100),
+///     Some(vec![ByteRange::new(0, 0), ByteRange::new(10, 19)])
+/// );
+/// assert_eq!(parse_range_header("bytes=100-", 100), Some(vec![]));
+/// assert_eq!(parse_range_header("bytes=-0", 100), Some(vec![]));
+/// assert_eq!(parse_range_header("bytes=9-0", 100), None);
+/// assert_eq!(parse_range_header("items=0-9", 100), None);
+/// assert_eq!(parse_range_header("bytes=a-b", 100), None);
+/// ```
+pub fn parse_range_header(value: &str, size: u64) -> Option<Vec<ByteRange>> {
+    let specs = value.trim().strip_prefix("bytes=")?;
+    let mut ranges = Vec::new();
+    for spec in specs.split(',').map(str::trim) {
+        let (start, end) = spec.split_at(spec.find('-')?);
+        let end = &end[1..];
+        let range = match (start.is_empty(), end.is_empty()) {
+            // the last `end` bytes
+            (true, false) => {
+                let suffix = end.parse::<u64>().ok()?;
+                (suffix > 0 && size > 0)
+                    .then(|| ByteRange::new(size.saturating_sub(suffix), size - 1))
+            }
+            (false, true) => {
+                let start = start.parse::<u64>().ok()?;
+                (start < size).then(|| ByteRange::new(start, size - 1))
+            }
+            (false, false) => {
+                let start = start.parse::<u64>().ok()?;
+                let end = end.parse::<u64>().ok()?;
+                if end < start {
+                    return None;
+                }
+                (start < size).then(|| ByteRange::new(start, end.min(size - 1)))
+            }
+            (true, true) => return None,
+        };
+        ranges.extend(range);
+    }
+    Some(ranges)
+}
+
+/// Maps the `len` bytes long range starting at `offset` of the `size` bytes long file
+/// onto its chunks, as (chunk index, range within the chunk) pairs. The part of the
+/// range past the end of the file is skipped
+///
+/// Examples:
+/// ```
+/// use ccfs_commons::range::{chunk_ranges, ByteRange};
+/// use ccfs_commons::CHUNK_SIZE;
+///
+/// assert_eq!(chunk_ranges(5, 4, 17), vec![(0, ByteRange::new(5, 8))]);
+/// assert_eq!(chunk_ranges(10, 20, 17), vec![(0, ByteRange::new(10, 16))]);
+/// assert_eq!(
+///     chunk_ranges(CHUNK_SIZE - 2, 6, 2 * CHUNK_SIZE),
+///     vec![(0, ByteRange::new(CHUNK_SIZE - 2, CHUNK_SIZE - 1)), (1, ByteRange::new(0, 3))]
+/// );
+/// assert_eq!(chunk_ranges(17, 4, 17), vec![]);
+/// assert_eq!(chunk_ranges(5, 0, 17), vec![]);
+/// ```
+pub fn chunk_ranges(offset: u64, len: u64, size: u64) -> Vec<(usize, ByteRange)> {
+    let end = offset.saturating_add(len).min(size);
+    let mut ranges = Vec::new();
+    let mut curr = offset;
+    while curr < end {
+        let index = curr / CHUNK_SIZE;
+        let chunk_end = ((index + 1) * CHUNK_SIZE).min(end);
+        let range = ByteRange::new(curr % CHUNK_SIZE, (chunk_end - 1) % CHUNK_SIZE);
+        ranges.push((index as usize, range));
+        curr = chunk_end;
+    }
+    ranges
+}
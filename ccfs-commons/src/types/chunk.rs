@@ -1,11 +1,18 @@
+use crate::{ChunkServer, FileInfo, FileMetadata};
 use serde::{Deserialize, Serialize};
+use std::hash::{Hash, Hasher};
 use uuid::Uuid;
 
-#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+/// Replica of a chunk on a chunk server. The replicas are compared by their ids only,
+/// so the same replica is equal whether its checksum is known or not
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
 pub struct Chunk {
     pub id: Uuid,
     pub file_id: Uuid,
     pub server_id: Uuid,
+    /// CRC32 checksum of the chunk content, computed by the client which uploaded it
+    #[serde(default)]
+    pub checksum: Option<u32>,
 }
 impl Chunk {
     pub fn new(id: Uuid, file_id: Uuid, server_id: Uuid) -> Self {
@@ -13,14 +20,125 @@ impl Chunk {
             id,
             file_id,
             server_id,
+            checksum: None,
         }
     }
 
+    pub fn with_checksum(mut self, checksum: u32) -> Self {
+        self.checksum = Some(checksum);
+        self
+    }
+
     pub fn chunk_name(&self) -> String {
         chunk_name(&self.file_id.to_string(), &self.id.to_string())
     }
 }
+impl PartialEq for Chunk {
+    fn eq(&self, other: &Self) -> bool {
+        (self.id, self.file_id, self.server_id) == (other.id, other.file_id, other.server_id)
+    }
+}
+impl Eq for Chunk {}
+impl Hash for Chunk {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (self.id, self.file_id, self.server_id).hash(state);
+    }
+}
+
+/// Chunk servers where the chunk should be stored. The first one is the primary,
+/// which receives the chunk from the client, the rest are its replica targets
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ChunkPlacement {
+    pub chunk_id: Uuid,
+    pub servers: Vec<ChunkServer>,
+}
+
+/// Inventory of the chunks stored on a chunk server,
+/// which the metadata server reconciles its chunks map against
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ChunkReport {
+    pub server_id: Uuid,
+    /// Whether `stored` lists all of the chunks on the server,
+    /// or only the ones stored since the previous report
+    pub full: bool,
+    pub stored: Vec<Chunk>,
+    /// Chunks removed since the previous report, it's empty in the full reports
+    #[serde(default)]
+    pub removed: Vec<Chunk>,
+}
+
+/// Changes of the chunks map made after a chunk report
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ChunkReportResult {
+    /// Registered replicas which aren't stored on the server anymore
+    pub unregistered: Vec<Chunk>,
+    /// Stored replicas of the known files, which weren't registered yet
+    pub registered: Vec<Chunk>,
+    /// Stored chunks which don't belong to any known file (or don't match its checksum)
+    pub unknown: Vec<Chunk>,
+}
+
+/// Info about the file which the chunk belongs to. The chunk servers store it next
+/// to the chunk, so the metadata can be rebuilt if the metadata server loses it
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ChunkMetadata {
+    /// Path of the directory which contains the file
+    pub path: String,
+    pub name: String,
+    /// Position of the chunk in the file chunks
+    pub index: usize,
+    /// Size of the whole file
+    pub size: u64,
+    pub version: usize,
+    #[serde(default)]
+    pub replicas: Option<usize>,
+}
+impl ChunkMetadata {
+    /// Creates the metadata of the `index` chunk of the file created in the `path` directory
+    pub fn new(path: &str, file: &FileMetadata, index: usize) -> Self {
+        let (size, replicas) = match &file.file_info {
+            FileInfo::File { size, replicas, .. } => (*size, *replicas),
+            FileInfo::Directory { .. } => (0, None),
+        };
+        Self {
+            path: path.into(),
+            name: file.name.clone(),
+            index,
+            size,
+            version: file.version,
+            replicas,
+        }
+    }
+}
+
+/// Chunk stored on a chunk server, along with the metadata recorded next to it
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct StoredChunk {
+    pub chunk: Chunk,
+    pub metadata: ChunkMetadata,
+}
 
 pub fn chunk_name(file_id: &str, chunk_id: &str) -> String {
     format!("{}_{}", file_id, chunk_id)
 }
+
+/// Parses the chunk file name created by `chunk_name` into (file_id, chunk_id)
+///
+/// Examples:
+/// ```
+/// use ccfs_commons::{chunk_name, parse_chunk_name};
+/// use uuid::Uuid;
+///
+/// let file_id = Uuid::new_v4();
+/// let chunk_id = Uuid::new_v4();
+/// let name = chunk_name(&file_id.to_string(), &chunk_id.to_string());
+/// assert_eq!(parse_chunk_name(&name), Some((file_id, chunk_id)));
+/// assert_eq!(parse_chunk_name("some-file.txt"), None);
+/// assert_eq!(parse_chunk_name(&format!("{}_..", file_id)), None);
+/// ```
+pub fn parse_chunk_name(name: &str) -> Option<(Uuid, Uuid)> {
+    let mut parts = name.splitn(2, '_');
+    let file_id = Uuid::parse_str(parts.next()?).ok()?;
+    let chunk_id = Uuid::parse_str(parts.next()?).ok()?;
+    Some((file_id, chunk_id))
+}
@@ -1,5 +1,6 @@
 use actix_web::dev::Payload;
 use actix_web::error::ErrorBadRequest;
+use actix_web::http::HeaderMap;
 use actix_web::{Error as ReqError, FromRequest, HttpRequest};
 use chrono::serde::ts_nanoseconds;
 use chrono::{DateTime, Duration, Utc};
@@ -14,6 +15,20 @@ pub struct ChunkServer {
     pub address: String,
     #[serde(with = "ts_nanoseconds")]
     pub latest_ping_time: DateTime<Utc>,
+    /// Rack or zone label, the replicas of a chunk are spread across distinct zones
+    #[serde(default)]
+    pub zone: Option<String>,
+    /// Free and total disk space (in bytes) of the chunks upload dir
+    #[serde(default)]
+    pub available_space: u64,
+    #[serde(default)]
+    pub total_space: u64,
+    /// Number of chunks stored on the server
+    #[serde(default)]
+    pub chunks_count: u64,
+    /// Number of requests the server was handling when it sent the latest ping
+    #[serde(default)]
+    pub pending_requests: u64,
 }
 impl ChunkServer {
     pub fn new(id: Uuid, address: String) -> Self {
@@ -21,6 +36,11 @@ impl ChunkServer {
             id,
             address,
             latest_ping_time: Utc::now(),
+            zone: None,
+            available_space: 0,
+            total_space: 0,
+            chunks_count: 0,
+            pending_requests: 0,
         }
     }
 
@@ -36,20 +56,51 @@ impl FromRequest for ChunkServer {
 
     fn from_request(request: &HttpRequest, _payload: &mut Payload) -> Self::Future {
         let headers = request.headers();
-        match (
+        let mut server = match (
             headers.get("x-ccfs-chunk-server-id"),
             headers.get("x-ccfs-chunk-server-address"),
         ) {
             (Some(id_header), Some(address_header)) => {
                 match (id_header.to_str(), address_header.to_str()) {
                     (Ok(id_str), Ok(url)) => match Uuid::from_str(id_str) {
-                        Ok(id) => ok(ChunkServer::new(id, url.to_string())),
-                        Err(_) => err(ErrorBadRequest("Not a valid uuid")),
+                        Ok(id) => ChunkServer::new(id, url.to_string()),
+                        Err(_) => return err(ErrorBadRequest("Not a valid uuid")),
                     },
-                    _ => err(ErrorBadRequest("Cannot read header value")),
+                    _ => return err(ErrorBadRequest("Cannot read header value")),
                 }
             }
-            _ => err(ErrorBadRequest("Missing header values")),
+            _ => return err(ErrorBadRequest("Missing header values")),
+        };
+        if let Some(zone_header) = headers.get("x-ccfs-chunk-server-zone") {
+            match zone_header.to_str() {
+                Ok(zone) if !zone.is_empty() => server.zone = Some(zone.to_string()),
+                Ok(_) => {}
+                Err(_) => return err(ErrorBadRequest("Cannot read header value")),
+            }
+        }
+        // the stats headers are optional, servers which don't send them are reported as empty
+        let stats = (
+            parse_stat(headers, "x-ccfs-chunk-server-available-space"),
+            parse_stat(headers, "x-ccfs-chunk-server-total-space"),
+            parse_stat(headers, "x-ccfs-chunk-server-chunks-count"),
+            parse_stat(headers, "x-ccfs-chunk-server-pending-requests"),
+        );
+        match stats {
+            (Ok(available), Ok(total), Ok(chunks), Ok(pending)) => {
+                server.available_space = available;
+                server.total_space = total;
+                server.chunks_count = chunks;
+                server.pending_requests = pending;
+                ok(server)
+            }
+            _ => err(ErrorBadRequest("Not a valid server stats value")),
         }
     }
 }
+
+fn parse_stat(headers: &HeaderMap, name: &str) -> Result<u64, ()> {
+    match headers.get(name) {
+        Some(value) => value.to_str().ok().and_then(|v| v.parse().ok()).ok_or(()),
+        None => Ok(0),
+    }
+}
@@ -1,5 +1,6 @@
+use crate::ROOT_DIR;
 use crate::{errors::Error::*, result::CCFSResult};
-use crate::{BFSPathsIter, BFSTreeIter, DFSTreeIter, TreeNavigator, TreeZipper, ROOT_DIR};
+use crate::{BFSPathsIter, BFSTreeIter, ChunkPlacement, DFSTreeIter, TreeNavigator, TreeZipper};
 use chrono::serde::ts_nanoseconds;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
@@ -12,6 +13,8 @@ pub enum FileStatus {
     Started,
     Completed,
     Canceled,
+    /// Written as a stream of unknown length, its chunks are appended until it's committed
+    Open,
 }
 impl Default for FileStatus {
     fn default() -> Self {
@@ -65,6 +68,25 @@ impl FileMetadata {
         }
     }
 
+    /// Sets the number of replicas of each file chunk, directories are left unchanged
+    pub fn with_replicas(mut self, num_of_replicas: Option<usize>) -> Self {
+        if let FileInfo::File { replicas, .. } = &mut self.file_info {
+            *replicas = num_of_replicas;
+        }
+        self
+    }
+
+    /// Returns the file version, which is either this file or one of its previous versions
+    pub fn version(&self, version: usize) -> Option<&Self> {
+        if self.version == version {
+            return Some(self);
+        }
+        match &self.file_info {
+            FileInfo::File { versions, .. } => versions.iter().find(|v| v.version == version),
+            FileInfo::Directory { .. } => None,
+        }
+    }
+
     pub fn children(&self) -> CCFSResult<&BTreeMap<String, FileMetadata>> {
         if let FileInfo::Directory { ref children } = self.file_info {
             Ok(children)
@@ -135,6 +157,14 @@ impl FileMetadata {
         Ok(())
     }
 
+    pub fn remove_child(&mut self, name: &str) -> CCFSResult<Self> {
+        let removed = self
+            .children_mut()?
+            .remove(name)
+            .ok_or_else(|| NotExist { path: name.into() })?;
+        Ok(removed)
+    }
+
     pub fn print_subtree(&self) -> String {
         let mut s = self.name.to_string();
         if let FileInfo::Directory { children } = &self.file_info {
@@ -207,6 +237,19 @@ pub enum FileInfo {
         num_of_completed_chunks: usize,
         #[serde(default = "FileStatus::default")]
         status: FileStatus,
+        /// Number of replicas of each chunk, the metadata server default is used when it's not set
+        #[serde(default)]
+        replicas: Option<usize>,
+        /// Number of the previous versions which are kept when the file is overwritten
+        #[serde(default)]
+        kept_versions: usize,
+        /// Previous versions of the file, from the newest one
+        #[serde(default)]
+        versions: Vec<FileMetadata>,
+        /// Version of the file which the upload overwrites (0 when there's no such file), the
+        /// upload fails if another one overwrites the file first. It's overwritten anyway if not set
+        #[serde(default)]
+        expected_version: Option<usize>,
     },
 }
 impl FileInfo {
@@ -217,10 +260,35 @@ impl FileInfo {
             chunks,
             num_of_completed_chunks: 0,
             status: FileStatus::Started,
+            replicas: None,
+            kept_versions: 0,
+            versions: Vec::new(),
+            expected_version: None,
         }
     }
 }
 
+/// Created file, along with the servers where each of its chunks should be uploaded
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct FileUpload {
+    #[serde(flatten)]
+    pub file: FileMetadata,
+    /// Path of the directory where the file is created
+    #[serde(default)]
+    pub path: String,
+    #[serde(default)]
+    pub placement: Vec<ChunkPlacement>,
+}
+
+/// Upload of a file which was already created, along with its chunks which are uploaded.
+/// The placement lists only the remaining chunks
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct UploadStatus {
+    #[serde(flatten)]
+    pub upload: FileUpload,
+    pub completed: Vec<Uuid>,
+}
+
 #[cfg(test)]
 pub mod tests {
     use crate::test_utils::{add_dir2, build_tree};
@@ -280,6 +348,25 @@ pub mod tests {
         Ok(())
     }
 
+    #[test]
+    fn tree_remove_child_test() -> CCFSResult<()> {
+        let mut tree = build_tree()?;
+        let dir2 = tree.remove_child("dir2")?;
+        assert_eq!(dir2.name, "dir2");
+        assert_eq!(dir2.children()?.len(), 2);
+        assert_eq!(tree.print_current_dir()?, "dir1\nsome.zip");
+        assert_eq!(
+            tree.remove_child("dir2").unwrap_err().to_string(),
+            "Path 'dir2' doesn't exist"
+        );
+        let mut file = tree.remove_child("some.zip")?;
+        assert_eq!(
+            file.remove_child("test").unwrap_err().to_string(),
+            "'some.zip' is not a directory"
+        );
+        Ok(())
+    }
+
     #[test]
     fn tree_print_subtree_test() -> CCFSResult<()> {
         let tree = build_tree()?;
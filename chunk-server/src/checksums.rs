@@ -0,0 +1,64 @@
+//! Checksums of the stored chunks, kept next to them in `{chunk_name}.crc32` files
+
+use ccfs_commons::checksum::{checksum_reader, format_checksum, parse_checksum};
+use ccfs_commons::{errors::Error as BaseError, result::CCFSResult};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use tokio::fs::{read_to_string, remove_file, write, File};
+
+pub fn checksum_path(dir: &Path, chunk_name: &str) -> PathBuf {
+    dir.join(format!("{}.crc32", chunk_name))
+}
+
+pub async fn write_checksum(dir: &Path, chunk_name: &str, checksum: u32) -> CCFSResult<()> {
+    let path = checksum_path(dir, chunk_name);
+    write(&path, format_checksum(checksum))
+        .await
+        .map_err(|source| BaseError::Write { path, source })?;
+    Ok(())
+}
+
+/// Returns the recorded checksum of the chunk, if there is a valid one
+pub async fn read_recorded_checksum(dir: &Path, chunk_name: &str) -> CCFSResult<Option<u32>> {
+    let path = checksum_path(dir, chunk_name);
+    match read_to_string(&path).await {
+        Ok(text) => match parse_checksum(&text) {
+            Some(checksum) => Ok(Some(checksum)),
+            None => {
+                // TODO: replace with logger
+                println!("Invalid checksum recorded in {}", path.display());
+                Ok(None)
+            }
+        },
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+        Err(source) => Err(BaseError::Read { path, source }.into()),
+    }
+}
+
+/// Returns the recorded checksum of the chunk. It's computed from the chunk content
+/// when it wasn't recorded, e.g. for the chunks stored before the checksums were introduced
+pub async fn read_checksum(dir: &Path, chunk_name: &str) -> CCFSResult<u32> {
+    if let Some(checksum) = read_recorded_checksum(dir, chunk_name).await? {
+        return Ok(checksum);
+    }
+    let path = dir.join(chunk_name);
+    let f = File::open(&path).await.map_err(|source| BaseError::Open {
+        path: path.clone(),
+        source,
+    })?;
+    let checksum = checksum_reader(f)
+        .await
+        .map_err(|source| BaseError::Read { path, source })?;
+    Ok(checksum)
+}
+
+/// Removes the recorded checksum of the chunk, if there is one
+pub async fn remove_checksum(dir: &Path, chunk_name: &str) -> CCFSResult<()> {
+    let path = checksum_path(dir, chunk_name);
+    match remove_file(&path).await {
+        Err(err) if err.kind() != ErrorKind::NotFound => {
+            Err(BaseError::Remove { path, source: err }.into())
+        }
+        _ => Ok(()),
+    }
+}
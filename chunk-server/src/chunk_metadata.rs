@@ -0,0 +1,50 @@
+//! Metadata of the stored chunks' files, kept next to them in `{chunk_name}.meta` files
+
+use ccfs_commons::{errors::Error as BaseError, result::CCFSResult, ChunkMetadata};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use tokio::fs::{read, remove_file, write};
+
+pub fn metadata_path(dir: &Path, chunk_name: &str) -> PathBuf {
+    dir.join(format!("{}.meta", chunk_name))
+}
+
+pub async fn write_metadata(
+    dir: &Path,
+    chunk_name: &str,
+    metadata: &ChunkMetadata,
+) -> CCFSResult<()> {
+    let path = metadata_path(dir, chunk_name);
+    write(&path, serde_json::to_vec(metadata).unwrap())
+        .await
+        .map_err(|source| BaseError::Write { path, source })?;
+    Ok(())
+}
+
+/// Returns the recorded metadata of the chunk, if there is a valid one
+pub async fn read_metadata(dir: &Path, chunk_name: &str) -> CCFSResult<Option<ChunkMetadata>> {
+    let path = metadata_path(dir, chunk_name);
+    match read(&path).await {
+        Ok(content) => match serde_json::from_slice(&content) {
+            Ok(metadata) => Ok(Some(metadata)),
+            Err(_) => {
+                // TODO: replace with logger
+                println!("Invalid chunk metadata recorded in {}", path.display());
+                Ok(None)
+            }
+        },
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+        Err(source) => Err(BaseError::Read { path, source }.into()),
+    }
+}
+
+/// Removes the recorded metadata of the chunk, if there is one
+pub async fn remove_metadata(dir: &Path, chunk_name: &str) -> CCFSResult<()> {
+    let path = metadata_path(dir, chunk_name);
+    match remove_file(&path).await {
+        Err(err) if err.kind() != ErrorKind::NotFound => {
+            Err(BaseError::Remove { path, source: err }.into())
+        }
+        _ => Ok(()),
+    }
+}
@@ -1,4 +1,4 @@
-use actix_web::error::{ErrorBadRequest, ErrorInternalServerError};
+use actix_web::error::{ErrorBadRequest, ErrorInternalServerError, ErrorNotFound};
 use actix_web::{HttpResponse, ResponseError};
 use ccfs_commons::errors::CCFSResponseError;
 use snafu::Snafu;
@@ -20,6 +20,42 @@ pub enum Error {
 
     #[snafu(display("Cannot create temp dir"))]
     TempDir { source: std::io::Error },
+
+    #[snafu(display("Unable to parse to json: {}", source))]
+    ParseJson {
+        source: actix_web::client::JsonPayloadError,
+    },
+
+    #[snafu(display("Invalid chunk name '{}'", chunk_name))]
+    InvalidChunkName { chunk_name: String },
+
+    #[snafu(display("Chunk '{}' doesn't exist", chunk_name))]
+    ChunkNotFound { chunk_name: String },
+
+    #[snafu(display("Invalid chunk metadata: {}", source))]
+    InvalidChunkMetadata { source: serde_json::Error },
+
+    #[snafu(display("Invalid checksum '{}'", text))]
+    InvalidChecksum { text: String },
+
+    #[snafu(display(
+        "Checksum mismatch for chunk '{}': expected {:08x}, got {:08x}",
+        chunk_name,
+        expected,
+        actual
+    ))]
+    ChecksumMismatch {
+        chunk_name: String,
+        expected: u32,
+        actual: u32,
+    },
+
+    #[snafu(display(
+        "Refused to remove {} of the {} stored chunks, the metadata might be incomplete",
+        count,
+        stored
+    ))]
+    TooManyUnreferenced { count: usize, stored: usize },
 }
 
 impl ResponseError for Error {
@@ -28,10 +64,17 @@ impl ResponseError for Error {
         let display = format!("{}", self);
         match self {
             Base { source } => source.error_response(),
-            MetaServerCommunication { .. } | TempDir { .. } => {
-                ErrorInternalServerError(display).into()
-            }
-            MissingPart | MissingHeader => ErrorBadRequest(display).into(),
+            MetaServerCommunication { .. }
+            | TempDir { .. }
+            | ParseJson { .. }
+            | TooManyUnreferenced { .. } => ErrorInternalServerError(display).into(),
+            MissingPart
+            | MissingHeader
+            | InvalidChunkName { .. }
+            | InvalidChunkMetadata { .. }
+            | InvalidChecksum { .. }
+            | ChecksumMismatch { .. } => ErrorBadRequest(display).into(),
+            ChunkNotFound { .. } => ErrorNotFound(display).into(),
         }
     }
 }
@@ -0,0 +1,95 @@
+use crate::checksums::remove_checksum;
+use crate::chunk_metadata::remove_metadata;
+use crate::errors::*;
+use crate::jobs::post_to_metadata;
+use crate::server_config::ServerConfig;
+use ccfs_commons::{errors::Error as BaseError, result::CCFSResult};
+use ccfs_commons::{parse_chunk_name, Chunk};
+use snafu::ResultExt;
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::SystemTime;
+use tokio::fs::{read_dir, remove_file};
+use tokio::time::{sleep, Duration};
+
+/// Max number of chunks sent to the metadata server in a single request
+const GC_BATCH_SIZE: usize = 1000;
+/// Max percentage of the stored chunks which a single pass removes, a larger share
+/// of unreferenced chunks points to incomplete metadata rather than to garbage
+const GC_MAX_REMOVED_PERCENT: usize = 50;
+
+pub async fn start_gc_job(config: Arc<ServerConfig>) {
+    loop {
+        sleep(Duration::from_secs(config.gc_interval)).await;
+        match collect_garbage(&config).await {
+            Ok(0) => {}
+            Ok(count) => println!("Removed {} orphaned chunks", count),
+            // TODO: replace with logger
+            Err(err) => println!("Error while removing orphaned chunks: {:?}", err),
+        }
+    }
+}
+
+/// Removes the stored chunks which are not referenced by any file on the metadata server.
+///
+/// Only chunks older than `gc_grace_period` are considered, since the chunks of
+/// the uploads which are in progress might not be registered yet. Nothing is removed
+/// when the unreferenced chunks are over `GC_MAX_REMOVED_PERCENT` of the stored ones
+pub async fn collect_garbage(config: &ServerConfig) -> CCFSResult<usize> {
+    let (candidates, stored) = get_gc_candidates(config).await?;
+    let mut unreferenced = Vec::new();
+    for batch in candidates.chunks(GC_BATCH_SIZE) {
+        let mut resp = post_to_metadata(config, "/api/chunks/referenced", &batch).await?;
+        let referenced: HashSet<Chunk> = resp.json().await.context(ParseJson)?;
+        unreferenced.extend(batch.iter().filter(|c| !referenced.contains(c)).copied());
+    }
+    if unreferenced.len() * 100 > stored * GC_MAX_REMOVED_PERCENT {
+        let count = unreferenced.len();
+        return Err(TooManyUnreferenced { count, stored }.build().into());
+    }
+    for chunk in unreferenced.iter() {
+        let chunk_name = chunk.chunk_name();
+        let path = config.upload_path.join(&chunk_name);
+        remove_file(&path)
+            .await
+            .map_err(|source| BaseError::Remove { path, source })?;
+        remove_checksum(&config.upload_path, &chunk_name).await?;
+        remove_metadata(&config.upload_path, &chunk_name).await?;
+    }
+    Ok(unreferenced.len())
+}
+
+/// Returns the chunks which are older than the grace period, and the number of all stored chunks
+async fn get_gc_candidates(config: &ServerConfig) -> CCFSResult<(Vec<Chunk>, usize)> {
+    let dir = &config.upload_path;
+    let grace_period = Duration::from_secs(config.gc_grace_period);
+    let mut entries = read_dir(dir).await.map_err(|source| BaseError::Read {
+        path: dir.into(),
+        source,
+    })?;
+    let mut candidates = Vec::new();
+    let mut stored = 0;
+    while let Some(entry) = entries
+        .next_entry()
+        .await
+        .map_err(|source| BaseError::Read {
+            path: dir.into(),
+            source,
+        })?
+    {
+        let ids = entry.file_name().to_str().and_then(parse_chunk_name);
+        let modified = entry.metadata().await.and_then(|m| m.modified());
+        if ids.is_some() {
+            stored += 1;
+        }
+        if let (Some((file_id, chunk_id)), Ok(modified)) = (ids, modified) {
+            let age = SystemTime::now()
+                .duration_since(modified)
+                .unwrap_or_default();
+            if age >= grace_period {
+                candidates.push(Chunk::new(chunk_id, file_id, config.server_id));
+            }
+        }
+    }
+    Ok((candidates, stored))
+}
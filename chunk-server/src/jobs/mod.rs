@@ -1,28 +1,100 @@
+pub mod gc;
+pub mod report;
+pub mod scrub;
+
 use crate::server_config::ServerConfig;
+use crate::stats::{get_server_stats, PendingRequests};
 use actix_web::client::Client;
-use ccfs_commons::http_utils::read_body;
+use actix_web::http::Method;
+use ccfs_commons::http_utils::{read_body, send_to_leader, Response};
+use ccfs_commons::{errors::Error as BaseError, result::CCFSResult};
+use serde::Serialize;
 use std::sync::Arc;
 use tokio::time::{sleep, Duration};
 
-pub async fn start_ping_job(address: String, config: Arc<ServerConfig>) {
+pub async fn start_ping_job(
+    address: String,
+    config: Arc<ServerConfig>,
+    pending_requests: PendingRequests,
+) {
     loop {
-        // TODO: investigate why using a client initialize outside the loop occasionally gives `connector has been disconnected` error
-        let client = Client::new();
-        let res = client
-            .post(&format!("{}/api/ping", config.metadata_url))
-            .insert_header(("x-ccfs-chunk-server-id", config.server_id.to_string()))
-            .insert_header(("x-ccfs-chunk-server-address", address.clone()))
-            .send()
-            .await;
-        match res {
+        ping_metadata_servers(&address, &config, &pending_requests).await;
+        sleep(Duration::from_secs(config.ping_interval)).await;
+    }
+}
+
+/// Pings every metadata server of the cluster, so that the node which is elected
+/// as the leader already knows the live chunk servers. Returns the number of successful pings
+pub async fn ping_metadata_servers(
+    address: &str,
+    config: &ServerConfig,
+    pending_requests: &PendingRequests,
+) -> usize {
+    let mut headers = vec![
+        ("x-ccfs-chunk-server-id", config.server_id.to_string()),
+        ("x-ccfs-chunk-server-address", address.to_string()),
+    ];
+    if let Some(zone) = &config.zone {
+        headers.push(("x-ccfs-chunk-server-zone", zone.clone()));
+    }
+    match get_server_stats(&config.upload_path, pending_requests).await {
+        Ok(stats) => headers.extend(vec![
+            (
+                "x-ccfs-chunk-server-available-space",
+                stats.available_space.to_string(),
+            ),
+            (
+                "x-ccfs-chunk-server-total-space",
+                stats.total_space.to_string(),
+            ),
+            (
+                "x-ccfs-chunk-server-chunks-count",
+                stats.chunks_count.to_string(),
+            ),
+            (
+                "x-ccfs-chunk-server-pending-requests",
+                stats.pending_requests.to_string(),
+            ),
+        ]),
+        // TODO: replace with logger
+        Err(err) => println!("Couldn't collect server stats: {:?}", err),
+    }
+    // TODO: investigate why using a client initialize outside the loop occasionally gives `connector has been disconnected` error
+    let client = Client::new();
+    let mut pinged = 0;
+    for metadata_url in std::iter::once(&config.metadata_url).chain(&config.metadata_peers) {
+        let mut req = client.post(&format!("{}/api/ping", metadata_url));
+        for header in &headers {
+            req = req.insert_header(header.clone());
+        }
+        match req.send().await {
             Ok(s) => match s.status().is_success() {
-                true => println!("successfully pinged meta server"),
+                true => {
+                    pinged += 1;
+                    println!("successfully pinged meta server {}", metadata_url)
+                }
                 false => println!("ping failed: {:?}", read_body(s).await),
             },
             Err(err) => {
-                println!("ping failed: {}", err)
+                println!("ping of {} failed: {}", metadata_url, err)
             }
         }
-        sleep(Duration::from_secs(config.ping_interval)).await;
     }
+    pinged
+}
+
+/// Sends the data to the metadata server, following the redirects to the cluster leader
+async fn post_to_metadata<T: Serialize>(
+    config: &ServerConfig,
+    path: &str,
+    data: &T,
+) -> CCFSResult<Response> {
+    let client = Client::new();
+    let url = format!("{}{}", config.metadata_url, path);
+    let resp = send_to_leader(&client, Method::POST, &url, &[], Some(data)).await?;
+    if !resp.status().is_success() {
+        let response = read_body(resp).await?;
+        return Err(BaseError::Unsuccessful { response }.into());
+    }
+    Ok(resp)
 }
@@ -0,0 +1,104 @@
+use crate::checksums::read_recorded_checksum;
+use crate::errors::*;
+use crate::jobs::post_to_metadata;
+use crate::server_config::ServerConfig;
+use ccfs_commons::{errors::Error as BaseError, result::CCFSResult};
+use ccfs_commons::{parse_chunk_name, Chunk, ChunkReport, ChunkReportResult};
+use snafu::ResultExt;
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::Arc;
+use tokio::fs::read_dir;
+use tokio::time::{sleep, Duration};
+use uuid::Uuid;
+
+/// Sends the full chunk inventory on startup, and the changes since the previous report afterwards
+pub async fn start_report_job(config: Arc<ServerConfig>) {
+    // inventory included in the latest successful report, the full one is sent until there is one
+    let mut reported = None;
+    loop {
+        match list_chunks(&config).await {
+            Ok(inventory) => {
+                let report = build_report(config.server_id, &inventory, reported.as_ref());
+                match send_chunk_report(&config, &report).await {
+                    Ok(result) => {
+                        if !result.unknown.is_empty() {
+                            // TODO: replace with logger
+                            println!(
+                                "{} stored chunks don't belong to any file",
+                                result.unknown.len()
+                            );
+                        }
+                        reported = Some(inventory);
+                    }
+                    Err(err) => println!("Error while reporting chunks: {:?}", err),
+                }
+            }
+            Err(err) => println!("Error while listing chunks: {:?}", err),
+        }
+        sleep(Duration::from_secs(config.report_interval)).await;
+    }
+}
+
+/// Lists the stored chunks, along with their recorded checksums
+pub async fn list_chunks(config: &ServerConfig) -> CCFSResult<HashSet<Chunk>> {
+    list_chunks_in(&config.upload_path, config.server_id).await
+}
+
+/// Lists the chunks stored in the dir, along with their recorded checksums
+pub async fn list_chunks_in(dir: &Path, server_id: Uuid) -> CCFSResult<HashSet<Chunk>> {
+    let mut entries = read_dir(dir).await.map_err(|source| BaseError::Read {
+        path: dir.into(),
+        source,
+    })?;
+    let mut chunks = HashSet::new();
+    while let Some(entry) = entries
+        .next_entry()
+        .await
+        .map_err(|source| BaseError::Read {
+            path: dir.into(),
+            source,
+        })?
+    {
+        let chunk_name = entry.file_name().to_string_lossy().to_string();
+        if let Some((file_id, chunk_id)) = parse_chunk_name(&chunk_name) {
+            let mut chunk = Chunk::new(chunk_id, file_id, server_id);
+            if let Some(checksum) = read_recorded_checksum(dir, &chunk_name).await? {
+                chunk = chunk.with_checksum(checksum);
+            }
+            chunks.insert(chunk);
+        }
+    }
+    Ok(chunks)
+}
+
+/// Creates the report of the changes since the `previous` inventory,
+/// or the full report when there isn't one
+pub fn build_report(
+    server_id: Uuid,
+    inventory: &HashSet<Chunk>,
+    previous: Option<&HashSet<Chunk>>,
+) -> ChunkReport {
+    match previous {
+        Some(previous) => ChunkReport {
+            server_id,
+            full: false,
+            stored: inventory.difference(previous).cloned().collect(),
+            removed: previous.difference(inventory).cloned().collect(),
+        },
+        None => ChunkReport {
+            server_id,
+            full: true,
+            stored: inventory.iter().cloned().collect(),
+            removed: Vec::new(),
+        },
+    }
+}
+
+pub async fn send_chunk_report(
+    config: &ServerConfig,
+    report: &ChunkReport,
+) -> CCFSResult<ChunkReportResult> {
+    let mut resp = post_to_metadata(config, "/api/chunks/report", report).await?;
+    Ok(resp.json().await.context(ParseJson)?)
+}
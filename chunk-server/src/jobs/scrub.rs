@@ -0,0 +1,138 @@
+use crate::checksums::{checksum_path, read_recorded_checksum};
+use crate::chunk_metadata::metadata_path;
+use crate::jobs::post_to_metadata;
+use crate::server_config::ServerConfig;
+use ccfs_commons::checksum::Hasher;
+use ccfs_commons::{errors::Error as BaseError, result::CCFSResult};
+use ccfs_commons::{parse_chunk_name, Chunk};
+use std::path::Path;
+use std::sync::Arc;
+use tokio::fs::{create_dir_all, read_dir, rename, File};
+use tokio::io::AsyncReadExt;
+use tokio::time::{sleep, Duration, Instant};
+
+/// Dir inside the uploads dir where the corrupted chunks are moved
+pub const QUARANTINE_DIR: &str = "quarantine";
+
+const SCRUB_BUFFER_SIZE: usize = 1024 * 1024;
+
+pub async fn start_scrub_job(config: Arc<ServerConfig>) {
+    // corrupted chunks which couldn't be reported yet, they are retried after the next pass
+    let mut unreported = Vec::new();
+    loop {
+        sleep(Duration::from_secs(config.scrub_interval)).await;
+        match scrub_chunks(&config).await {
+            Ok(corrupted) => {
+                if !corrupted.is_empty() {
+                    println!("Quarantined {} corrupted chunks", corrupted.len());
+                }
+                unreported.extend(corrupted);
+            }
+            // TODO: replace with logger
+            Err(err) => println!("Error while scrubbing chunks: {:?}", err),
+        }
+        if !unreported.is_empty() {
+            match report_corrupted_chunks(&config, &unreported).await {
+                Ok(_) => unreported.clear(),
+                Err(err) => println!("Couldn't report corrupted chunks: {:?}", err),
+            }
+        }
+    }
+}
+
+/// Re-reads the stored chunks, and moves the ones which don't match their recorded
+/// checksum to the quarantine dir. Returns the quarantined chunks.
+///
+/// Reading is throttled to `scrub_rate` bytes per second, so the scrubbing
+/// doesn't compete with the uploads and downloads for the disk bandwidth
+pub async fn scrub_chunks(config: &ServerConfig) -> CCFSResult<Vec<Chunk>> {
+    let dir = &config.upload_path;
+    let mut entries = read_dir(dir).await.map_err(|source| BaseError::Read {
+        path: dir.into(),
+        source,
+    })?;
+    let mut corrupted = Vec::new();
+    while let Some(entry) = entries
+        .next_entry()
+        .await
+        .map_err(|source| BaseError::Read {
+            path: dir.into(),
+            source,
+        })?
+    {
+        let chunk_name = entry.file_name().to_string_lossy().to_string();
+        let (file_id, chunk_id) = match parse_chunk_name(&chunk_name) {
+            Some(ids) => ids,
+            None => continue,
+        };
+        let recorded = match read_recorded_checksum(dir, &chunk_name).await? {
+            Some(checksum) => checksum,
+            None => continue,
+        };
+        // the chunk might have been deleted in the meantime
+        let actual = match throttled_checksum(&entry.path(), config.scrub_rate).await {
+            Ok(checksum) => checksum,
+            Err(_) => continue,
+        };
+        if actual != recorded {
+            // the chunk is still reported when it can't be moved, so it gets re-replicated
+            if let Err(err) = quarantine(dir, &chunk_name).await {
+                // TODO: replace with logger
+                println!("Couldn't quarantine chunk {}: {:?}", chunk_name, err);
+            }
+            corrupted.push(Chunk::new(chunk_id, file_id, config.server_id).with_checksum(recorded));
+        }
+    }
+    Ok(corrupted)
+}
+
+async fn throttled_checksum(path: &Path, rate: u64) -> std::io::Result<u32> {
+    let mut f = File::open(path).await?;
+    let mut hasher = Hasher::new();
+    let mut buffer = vec![0; SCRUB_BUFFER_SIZE];
+    loop {
+        let started = Instant::now();
+        let read = f.read(&mut buffer).await?;
+        if read == 0 {
+            return Ok(hasher.finalize());
+        }
+        hasher.update(&buffer[..read]);
+        if rate > 0 {
+            let budget = Duration::from_secs_f64(read as f64 / rate as f64);
+            if let Some(remaining) = budget.checked_sub(started.elapsed()) {
+                sleep(remaining).await;
+            }
+        }
+    }
+}
+
+/// Moves the chunk, its recorded checksum and metadata to the quarantine dir
+async fn quarantine(dir: &Path, chunk_name: &str) -> CCFSResult<()> {
+    let quarantine_dir = dir.join(QUARANTINE_DIR);
+    create_dir_all(&quarantine_dir)
+        .await
+        .map_err(|source| BaseError::Create {
+            path: quarantine_dir.clone(),
+            source,
+        })?;
+    let mut paths = vec![dir.join(chunk_name), checksum_path(dir, chunk_name)];
+    // the chunks stored before the metadata was introduced don't have it
+    let metadata = metadata_path(dir, chunk_name);
+    if metadata.exists() {
+        paths.push(metadata);
+    }
+    for from in paths {
+        let to = quarantine_dir.join(from.file_name().unwrap_or_default());
+        rename(&from, &to)
+            .await
+            .map_err(|source| BaseError::Rename { from, to, source })?;
+    }
+    Ok(())
+}
+
+/// Notifies the metadata server about the corrupted chunks,
+/// so their healthy replicas are copied to the other servers
+pub async fn report_corrupted_chunks(config: &ServerConfig, chunks: &[Chunk]) -> CCFSResult<()> {
+    post_to_metadata(config, "/api/chunks/corrupted", &chunks).await?;
+    Ok(())
+}
@@ -1,7 +1,10 @@
+pub mod checksums;
+pub mod chunk_metadata;
 mod errors;
 pub mod jobs;
 pub mod routes;
 pub mod server_config;
+pub mod stats;
 
 use std::path::PathBuf;
 use uuid::Uuid;
@@ -1,7 +1,9 @@
+use actix_web::dev::Service;
 use actix_web::{web, App, HttpServer};
 use chunk_server::jobs;
-use chunk_server::routes::{download, replicate, upload};
+use chunk_server::routes::{delete_chunk, download, get_chunks_metadata, replicate, upload};
 use chunk_server::server_config::ServerConfig;
+use chunk_server::stats::PendingRequests;
 use std::env;
 use std::sync::Arc;
 use tokio::fs::create_dir_all;
@@ -39,11 +41,29 @@ async fn main() -> std::io::Result<()> {
         .join("ccfs-uploads");
     create_dir_all(&upload_path).await?;
 
-    task::spawn_local(jobs::start_ping_job(server_addr, config.clone()));
+    let pending_requests = PendingRequests::default();
+    task::spawn_local(jobs::start_ping_job(
+        server_addr,
+        config.clone(),
+        pending_requests.clone(),
+    ));
+    task::spawn_local(jobs::gc::start_gc_job(config.clone()));
+    task::spawn_local(jobs::report::start_report_job(config.clone()));
+    task::spawn_local(jobs::scrub::start_scrub_job(config.clone()));
 
     let address = config.address();
     HttpServer::new(move || {
+        let pending_requests = pending_requests.clone();
         App::new()
+            .wrap_fn(move |req, srv| {
+                let guard = pending_requests.start();
+                let res = srv.call(req);
+                async move {
+                    let res = res.await;
+                    drop(guard);
+                    res
+                }
+            })
             .data(config.metadata_url.clone())
             .data(config.server_id)
             .data(config.upload_path.clone())
@@ -51,7 +71,9 @@ async fn main() -> std::io::Result<()> {
                 web::scope("/api")
                     .service(upload)
                     .service(download)
-                    .service(replicate),
+                    .service(replicate)
+                    .service(delete_chunk)
+                    .service(get_chunks_metadata),
             )
     })
     .bind(&address)?
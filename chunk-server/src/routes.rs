@@ -1,20 +1,31 @@
+use crate::checksums::{read_checksum, remove_checksum, write_checksum};
+use crate::chunk_metadata::{read_metadata, remove_metadata, write_metadata};
 use crate::errors::*;
+use crate::jobs::report::list_chunks_in;
 use crate::{MetadataUrl, ServerID, UploadsDir};
 use actix_multipart::Multipart;
-use actix_web::http::header::CONTENT_TYPE;
-use actix_web::{body::BodyStream, client::Client, get, post, HttpResponse};
+use actix_web::http::header::{ACCEPT_RANGES, CONTENT_RANGE, CONTENT_TYPE, RANGE};
+use actix_web::http::Method;
+use actix_web::web::Bytes;
+use actix_web::{body::BodyStream, client::Client, delete, get, post, HttpResponse};
 use actix_web::{web::Data, web::Path, HttpRequest};
+use ccfs_commons::checksum::parse_checksum;
 use ccfs_commons::http_utils::{
-    create_ccfs_multipart, get_header, handle_file, handle_string, read_body,
+    create_ccfs_multipart, get_header, handle_file, handle_string, read_body, send_to_leader,
 };
-use ccfs_commons::{chunk_name, errors::Error as BaseError, result::CCFSResult, Chunk};
-use futures::TryStreamExt;
+use ccfs_commons::range::{parse_range_header, ByteRange};
+use ccfs_commons::{chunk_name, errors::Error as BaseError, result::CCFSResult};
+use ccfs_commons::{parse_chunk_name, Chunk, ChunkMetadata, StoredChunk};
+use futures::future::ready;
+use futures::stream::{self, LocalBoxStream, StreamExt, TryStreamExt};
 use snafu::ResultExt;
 use std::collections::HashMap;
+use std::io::{self, SeekFrom};
 use std::path::PathBuf;
 use std::str::FromStr;
 use tempfile::tempdir;
-use tokio::fs::{rename, File};
+use tokio::fs::{remove_file, rename, File};
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
 use tokio_util::io::ReaderStream;
 use uuid::Uuid;
 
@@ -27,16 +38,17 @@ pub async fn upload(
 ) -> CCFSResult<HttpResponse> {
     let temp = tempdir().context(TempDir)?;
     let mut parts: HashMap<String, String> = HashMap::new();
+    let mut actual_checksum = None;
     while let Ok(Some(field)) = data.try_next().await {
         if let Some(content_disposition) = field.content_disposition() {
             if let Some(name) = content_disposition.get_name() {
                 match name {
-                    "chunk_id" | "file_id" => {
+                    "chunk_id" | "file_id" | "checksum" | "metadata" => {
                         parts.insert(name.into(), handle_string(field).await?);
                     }
                     "file" => {
                         let file_path = temp.path().join(Uuid::new_v4().to_string());
-                        handle_file(field, &file_path).await?;
+                        actual_checksum = Some(handle_file(field, &file_path).await?);
                         parts.insert(name.into(), file_path.display().to_string());
                     }
                     _ => {}
@@ -44,13 +56,22 @@ pub async fn upload(
             }
         }
     }
-    if parts.len() != 3 {
+    // the metadata is optional, older clients don't send it
+    let metadata = match parts.remove("metadata") {
+        Some(text) => {
+            Some(serde_json::from_str::<ChunkMetadata>(&text).context(InvalidChunkMetadata)?)
+        }
+        None => None,
+    };
+    if parts.len() != 4 {
         return Err(MissingPart.build().into());
     }
 
     let id_str = parts.remove("chunk_id").unwrap_or_else(|| unreachable!());
     let file_id_str = parts.remove("file_id").unwrap_or_else(|| unreachable!());
+    let checksum_str = parts.remove("checksum").unwrap_or_else(|| unreachable!());
     let file_path_str = parts.remove("file").unwrap_or_else(|| unreachable!());
+    let actual = actual_checksum.unwrap_or_else(|| unreachable!());
 
     let id = Uuid::from_str(&id_str).map_err(|source| BaseError::ParseUuid {
         text: id_str,
@@ -61,16 +82,33 @@ pub async fn upload(
         source,
     })?;
 
-    let chunk = Chunk::new(id, file_id, **server);
+    let expected = parse_checksum(&checksum_str)
+        .ok_or_else(|| InvalidChecksum { text: checksum_str }.build())?;
+    let chunk = Chunk::new(id, file_id, **server).with_checksum(expected);
+    if actual != expected {
+        let chunk_name = chunk.chunk_name();
+        return Err(ChecksumMismatch {
+            chunk_name,
+            expected,
+            actual,
+        }
+        .build()
+        .into());
+    }
+    // the checksum and metadata are recorded first, so every stored chunk has them
+    write_checksum(target_dir.as_path(), &chunk.chunk_name(), expected).await?;
+    if let Some(metadata) = &metadata {
+        write_metadata(target_dir.as_path(), &chunk.chunk_name(), metadata).await?;
+    }
     let from = PathBuf::from(file_path_str);
     let to = target_dir.join(chunk.chunk_name());
     rename(&from, &to)
         .await
         .map_err(|source| BaseError::Rename { from, to, source })?;
 
-    let resp = Client::new()
-        .post(&format!("{}/api/chunk/completed", **meta_url))
-        .send_json(&chunk)
+    let client = Client::new();
+    let url = format!("{}/api/chunk/completed", **meta_url);
+    let resp = send_to_leader(&client, Method::POST, &url, &[], Some(&chunk))
         .await
         .map_err(|err| {
             let reason = format!("{}", err);
@@ -85,13 +123,79 @@ pub async fn upload(
     }
 }
 
+/// Streams the chunk, or only the byte ranges requested with the `Range` header.
+/// Multiple ranges are sent as a `multipart/byteranges` body
 #[get("/download/{chunk_name}")]
-pub async fn download(info: Path<String>, dir: Data<UploadsDir>) -> CCFSResult<HttpResponse> {
+pub async fn download(
+    request: HttpRequest,
+    info: Path<String>,
+    dir: Data<UploadsDir>,
+) -> CCFSResult<HttpResponse> {
     let path = dir.join(&info.into_inner());
-    let file = File::open(&path)
+    let file = File::open(&path).await.map_err(|source| BaseError::Read {
+        path: path.clone(),
+        source,
+    })?;
+    let size = file
+        .metadata()
         .await
-        .map_err(|source| BaseError::Read { path, source })?;
-    Ok(HttpResponse::Ok().streaming(ReaderStream::new(file)))
+        .map_err(|source| BaseError::Read {
+            path: path.clone(),
+            source,
+        })?
+        .len();
+    // malformed ranges are ignored, and the whole chunk is sent
+    let ranges = get_header(request.headers(), RANGE.as_str())
+        .and_then(|value| parse_range_header(value, size));
+    match ranges.as_deref() {
+        None => Ok(HttpResponse::Ok()
+            .insert_header((ACCEPT_RANGES, "bytes"))
+            .streaming(ReaderStream::new(file))),
+        Some([]) => Ok(HttpResponse::RangeNotSatisfiable()
+            .insert_header((CONTENT_RANGE, format!("bytes */{}", size)))
+            .finish()),
+        Some([range]) => Ok(HttpResponse::PartialContent()
+            .insert_header((CONTENT_RANGE, range.content_range(size)))
+            .streaming(range_stream(path, *range))),
+        Some(ranges) => {
+            let boundary = Uuid::new_v4().to_simple().to_string();
+            let parts = ranges
+                .iter()
+                .map(|range| {
+                    let headers = format!(
+                        "--{}\r\nContent-Type: application/octet-stream\r\nContent-Range: {}\r\n\r\n",
+                        boundary,
+                        range.content_range(size)
+                    );
+                    (headers, *range)
+                })
+                .collect::<Vec<_>>();
+            let body = stream::iter(parts).flat_map(move |(headers, range)| {
+                stream::once(ready(Ok(Bytes::from(headers))))
+                    .chain(range_stream(path.clone(), range))
+                    .chain(stream::once(ready(Ok(Bytes::from_static(b"\r\n")))))
+            });
+            let end = format!("--{}--\r\n", boundary);
+            let body = body.chain(stream::once(ready(Ok(Bytes::from(end)))));
+            Ok(HttpResponse::PartialContent()
+                .insert_header((
+                    CONTENT_TYPE,
+                    format!("multipart/byteranges; boundary={}", boundary),
+                ))
+                .streaming(body))
+        }
+    }
+}
+
+/// Streams the range of the file, which is opened once the stream is polled
+fn range_stream(path: PathBuf, range: ByteRange) -> LocalBoxStream<'static, io::Result<Bytes>> {
+    stream::once(async move {
+        let mut file = File::open(&path).await?;
+        file.seek(SeekFrom::Start(range.start)).await?;
+        Ok::<_, io::Error>(ReaderStream::new(file.take(range.len())))
+    })
+    .try_flatten()
+    .boxed_local()
 }
 
 #[post("/replicate")]
@@ -107,8 +211,10 @@ pub async fn replicate(request: HttpRequest, dir: Data<UploadsDir>) -> CCFSResul
     let f = File::open(&path)
         .await
         .map_err(|source| BaseError::Open { path, source })?;
+    let checksum = read_checksum(dir.as_path(), &chunk_file_name).await?;
+    let metadata = read_metadata(dir.as_path(), &chunk_file_name).await?;
     let stream = ReaderStream::new(f);
-    let mpart = create_ccfs_multipart(chunk_id, file_id, stream);
+    let mpart = create_ccfs_multipart(chunk_id, file_id, checksum, metadata.as_ref(), stream);
 
     let url = format!("{}/api/upload", server_url);
     let resp = Client::new()
@@ -126,3 +232,38 @@ pub async fn replicate(request: HttpRequest, dir: Data<UploadsDir>) -> CCFSResul
     }
     Ok(HttpResponse::Ok().finish())
 }
+
+#[delete("/chunks/{chunk_name}")]
+pub async fn delete_chunk(info: Path<String>, dir: Data<UploadsDir>) -> CCFSResult<HttpResponse> {
+    let chunk_name = info.into_inner();
+    if parse_chunk_name(&chunk_name).is_none() {
+        return Err(InvalidChunkName { chunk_name }.build().into());
+    }
+    let path = dir.join(&chunk_name);
+    if !path.exists() {
+        return Err(ChunkNotFound { chunk_name }.build().into());
+    }
+    remove_file(&path)
+        .await
+        .map_err(|source| BaseError::Remove { path, source })?;
+    remove_checksum(dir.as_path(), &chunk_name).await?;
+    remove_metadata(dir.as_path(), &chunk_name).await?;
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// Returns the stored chunks along with their recorded metadata,
+/// which the metadata server is rebuilt from in the recovery mode
+#[get("/chunks/metadata")]
+pub async fn get_chunks_metadata(
+    server: Data<ServerID>,
+    dir: Data<UploadsDir>,
+) -> CCFSResult<HttpResponse> {
+    let mut stored = Vec::new();
+    for chunk in list_chunks_in(dir.as_path(), **server).await? {
+        // the chunks stored before the metadata was introduced can't be recovered
+        if let Some(metadata) = read_metadata(dir.as_path(), &chunk.chunk_name()).await? {
+            stored.push(StoredChunk { chunk, metadata });
+        }
+    }
+    Ok(HttpResponse::Ok().json(&stored))
+}
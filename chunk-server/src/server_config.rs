@@ -9,9 +9,48 @@ pub struct ServerConfig {
     pub host: String,
     pub port: u32,
     pub metadata_url: String,
+    /// Urls of the other metadata servers of the cluster, which are pinged as well
+    #[serde(default)]
+    pub metadata_peers: Vec<String>,
     pub server_id: Uuid,
+    /// Rack or zone of the server, reported to the metadata server to spread the chunk replicas
+    #[serde(default)]
+    pub zone: Option<String>,
     pub upload_path: PathBuf,
     pub ping_interval: u64,
+    #[serde(default = "default_gc_interval")]
+    pub gc_interval: u64,
+    /// Min age (in seconds) of an unreferenced chunk before it's removed
+    #[serde(default = "default_gc_grace_period")]
+    pub gc_grace_period: u64,
+    #[serde(default = "default_report_interval")]
+    pub report_interval: u64,
+    #[serde(default = "default_scrub_interval")]
+    pub scrub_interval: u64,
+    /// Max number of bytes read per second while scrubbing the chunks, 0 means unlimited
+    #[serde(default = "default_scrub_rate")]
+    pub scrub_rate: u64,
+}
+
+fn default_gc_interval() -> u64 {
+    60 * 60
+}
+
+/// Long enough for the uploads in progress and the metadata restored after an outage
+fn default_gc_grace_period() -> u64 {
+    7 * 24 * 60 * 60
+}
+
+fn default_report_interval() -> u64 {
+    5 * 60
+}
+
+fn default_scrub_interval() -> u64 {
+    24 * 60 * 60
+}
+
+fn default_scrub_rate() -> u64 {
+    8 * 1024 * 1024
 }
 impl ServerConfig {
     pub fn load_config<T: AsRef<Path>>(path: &T) -> std::io::Result<Self> {
@@ -32,6 +71,14 @@ impl ServerConfig {
             error_msg = "metadata_url cannot be empty";
         } else if config.ping_interval == 0 {
             error_msg = "ping_interval cannot must be greater than 0";
+        } else if config.gc_interval == 0 {
+            error_msg = "gc_interval must be greater than 0";
+        } else if config.gc_grace_period == 0 {
+            error_msg = "gc_grace_period must be greater than 0";
+        } else if config.report_interval == 0 {
+            error_msg = "report_interval must be greater than 0";
+        } else if config.scrub_interval == 0 {
+            error_msg = "scrub_interval must be greater than 0";
         }
         if !error_msg.is_empty() {
             return Err(Error::new(ErrorKind::Other, error_msg));
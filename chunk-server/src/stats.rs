@@ -0,0 +1,106 @@
+//! Capacity and load of the chunk server, reported to the metadata server with every ping
+
+use ccfs_commons::{errors::Error as BaseError, parse_chunk_name, result::CCFSResult};
+use std::ffi::CString;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::fs::read_dir;
+
+/// Number of requests which are currently being handled
+#[derive(Debug, Default, Clone)]
+pub struct PendingRequests(Arc<AtomicU64>);
+
+impl PendingRequests {
+    pub fn count(&self) -> u64 {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    /// Registers a new request, which is considered done when the returned guard is dropped
+    pub fn start(&self) -> RequestGuard {
+        self.0.fetch_add(1, Ordering::SeqCst);
+        RequestGuard(self.0.clone())
+    }
+}
+
+pub struct RequestGuard(Arc<AtomicU64>);
+
+impl Drop for RequestGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ServerStats {
+    pub available_space: u64,
+    pub total_space: u64,
+    pub chunks_count: u64,
+    pub pending_requests: u64,
+}
+
+pub async fn get_server_stats(
+    upload_path: &Path,
+    pending_requests: &PendingRequests,
+) -> CCFSResult<ServerStats> {
+    let (available_space, total_space) = get_disk_space(upload_path)?;
+    Ok(ServerStats {
+        available_space,
+        total_space,
+        chunks_count: count_chunks(upload_path).await?,
+        pending_requests: pending_requests.count(),
+    })
+}
+
+/// Returns the free (available to unprivileged users) and total space
+/// of the file system which contains the path
+// statvfs field types differ between the platforms (e.g. u32 block counts on macOS)
+#[allow(clippy::useless_conversion)]
+pub fn get_disk_space(path: &Path) -> CCFSResult<(u64, u64)> {
+    let c_path = CString::new(path.as_os_str().as_bytes()).map_err(|_| BaseError::InvalidPath {
+        msg: format!("'{}' contains a nul byte", path.display()),
+    })?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return Err(BaseError::Read {
+            path: path.into(),
+            source: std::io::Error::last_os_error(),
+        }
+        .into());
+    }
+    let block_size = u64::from(stat.f_frsize);
+    Ok((
+        u64::from(stat.f_bavail) * block_size,
+        u64::from(stat.f_blocks) * block_size,
+    ))
+}
+
+/// Counts the stored chunks, files which are not chunks are skipped
+pub async fn count_chunks(upload_path: &Path) -> CCFSResult<u64> {
+    let mut entries = read_dir(upload_path)
+        .await
+        .map_err(|source| BaseError::Read {
+            path: upload_path.into(),
+            source,
+        })?;
+    let mut count = 0;
+    while let Some(entry) = entries
+        .next_entry()
+        .await
+        .map_err(|source| BaseError::Read {
+            path: upload_path.into(),
+            source,
+        })?
+    {
+        if entry
+            .file_name()
+            .to_str()
+            .and_then(parse_chunk_name)
+            .is_some()
+        {
+            count += 1;
+        }
+    }
+    Ok(count)
+}
@@ -0,0 +1,27 @@
+use chunk_server::server_config::ServerConfig;
+use std::fs::write;
+use tempfile::tempdir;
+
+#[test]
+fn test_load_config_without_new_keys() -> std::io::Result<()> {
+    let temp = tempdir()?;
+    // the config without the keys added with the later features
+    let content = format!(
+        "server_id: 8960fbc3-6ded-4d2b-a269-74fd4fa064fe\n\
+         host: 0.0.0.0\n\
+         port: 5000\n\
+         upload_path: {}\n\
+         metadata_url: http://localhost:4000\n\
+         ping_interval: 5\n",
+        temp.path().join("uploads").display()
+    );
+    let path = temp.path().join("cs_config.yml");
+    write(&path, content)?;
+
+    let config = ServerConfig::load_config(&path)?;
+    assert!(config.gc_interval > 0);
+    // the chunks are kept for a long time unless it's configured otherwise
+    assert!(config.gc_grace_period >= 24 * 60 * 60);
+    assert!(config.metadata_peers.is_empty());
+    Ok(())
+}
@@ -0,0 +1,64 @@
+mod utils;
+
+use actix_http::http::StatusCode;
+use actix_web::test::{call_service, init_service, TestRequest};
+use actix_web::{web, App};
+use ccfs_commons::chunk_name;
+use chunk_server::routes::delete_chunk;
+use std::sync::Arc;
+use tempfile::tempdir;
+use tokio::fs::File;
+use tokio::io::AsyncWriteExt;
+use utils::{is_empty, test_config};
+
+#[actix_rt::test]
+async fn test_delete_chunk() -> std::io::Result<()> {
+    let chunk_id = "1a6e7006-12a7-4935-b8c0-58fa7ea84b09".to_string();
+    let file_id = "6d53a85f-505b-4a1a-ae6d-f7c18761d04a".to_string();
+    let chunk_file_name = chunk_name(&file_id, &chunk_id);
+
+    let temp = tempdir()?;
+    let mut f = File::create(temp.path().join(&chunk_file_name)).await?;
+    f.write_all(b"Test file content").await?;
+
+    let server_config = Arc::new(test_config("url".into(), temp.path()));
+    let server = init_service(
+        App::new()
+            .data(server_config.upload_path.clone())
+            .service(web::scope("/api").service(delete_chunk)),
+    )
+    .await;
+
+    let req = TestRequest::delete()
+        .uri(&format!("/api/chunks/{}", chunk_file_name))
+        .to_request();
+    let resp = call_service(&server, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert!(is_empty(temp.path()).await?);
+
+    let req = TestRequest::delete()
+        .uri(&format!("/api/chunks/{}", chunk_file_name))
+        .to_request();
+    let resp = call_service(&server, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    Ok(())
+}
+
+#[actix_rt::test]
+async fn test_delete_invalid_chunk_name() -> std::io::Result<()> {
+    let temp = tempdir()?;
+    let server_config = Arc::new(test_config("url".into(), temp.path()));
+    let server = init_service(
+        App::new()
+            .data(server_config.upload_path.clone())
+            .service(web::scope("/api").service(delete_chunk)),
+    )
+    .await;
+
+    let req = TestRequest::delete()
+        .uri("/api/chunks/some-file.txt")
+        .to_request();
+    let resp = call_service(&server, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    Ok(())
+}
@@ -1,5 +1,6 @@
 mod utils;
 
+use actix_web::http::{HeaderMap, StatusCode};
 use actix_web::{test, web, App};
 use ccfs_commons::chunk_name;
 use chunk_server::routes::download;
@@ -43,3 +44,76 @@ async fn test_download() -> std::io::Result<()> {
     );
     Ok(())
 }
+
+async fn download_range(range: &str) -> std::io::Result<(StatusCode, HeaderMap, web::Bytes)> {
+    let chunk_file_name = chunk_name(
+        "6d53a85f-505b-4a1a-ae6d-f7c18761d04a",
+        "1a6e7006-12a7-4935-b8c0-58fa7ea84b09",
+    );
+    let temp = tempdir()?;
+    let mut f = File::create(temp.path().join(&chunk_file_name)).await?;
+    f.write_all(b"Test file content").await?;
+
+    let server_config = Arc::new(test_config("url".into(), temp.path()));
+    let server = init_service(
+        App::new()
+            .data(server_config.upload_path.clone())
+            .service(web::scope("/api").service(download)),
+    )
+    .await;
+
+    let req = TestRequest::get()
+        .uri(&format!("/api/download/{}", chunk_file_name))
+        .insert_header(("range", range))
+        .to_request();
+    let mut resp = call_service(&server, req).await;
+    let bytes = test::load_stream(resp.take_body().into_stream()).await;
+    Ok((resp.status(), resp.headers().clone(), bytes.unwrap()))
+}
+
+#[actix_rt::test]
+async fn test_download_range() -> std::io::Result<()> {
+    let (status, headers, bytes) = download_range("bytes=5-8").await?;
+    assert_eq!(status, StatusCode::PARTIAL_CONTENT);
+    assert_eq!(headers.get("content-range").unwrap(), "bytes 5-8/17");
+    assert_eq!(bytes, web::Bytes::from_static(b"file"));
+
+    let (status, headers, bytes) = download_range("bytes=-7").await?;
+    assert_eq!(status, StatusCode::PARTIAL_CONTENT);
+    assert_eq!(headers.get("content-range").unwrap(), "bytes 10-16/17");
+    assert_eq!(bytes, web::Bytes::from_static(b"content"));
+    Ok(())
+}
+
+#[actix_rt::test]
+async fn test_download_multiple_ranges() -> std::io::Result<()> {
+    let (status, headers, bytes) = download_range("bytes=0-3, 10-").await?;
+    assert_eq!(status, StatusCode::PARTIAL_CONTENT);
+    let content_type = headers.get("content-type").unwrap().to_str().unwrap();
+    let boundary = content_type
+        .strip_prefix("multipart/byteranges; boundary=")
+        .unwrap();
+    let expected = format!(
+        "--{0}\r\nContent-Type: application/octet-stream\r\nContent-Range: bytes 0-3/17\r\n\r\nTest\r\n\
+         --{0}\r\nContent-Type: application/octet-stream\r\nContent-Range: bytes 10-16/17\r\n\r\ncontent\r\n\
+         --{0}--\r\n",
+        boundary
+    );
+    assert_eq!(bytes, web::Bytes::from(expected));
+    Ok(())
+}
+
+#[actix_rt::test]
+async fn test_download_invalid_range() -> std::io::Result<()> {
+    let (status, headers, bytes) = download_range("bytes=17-").await?;
+    assert_eq!(status, StatusCode::RANGE_NOT_SATISFIABLE);
+    assert_eq!(headers.get("content-range").unwrap(), "bytes */17");
+    assert!(bytes.is_empty());
+
+    // malformed ranges are ignored
+    let (status, headers, bytes) = download_range("bytes=8-5").await?;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(headers.get("accept-ranges").unwrap(), "bytes");
+    assert_eq!(bytes, web::Bytes::from_static(b"Test file content"));
+    Ok(())
+}
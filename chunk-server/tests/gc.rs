@@ -0,0 +1,106 @@
+mod utils;
+
+use ccfs_commons::Chunk;
+use chunk_server::jobs::gc::collect_garbage;
+use httpmock::{Method, MockServer};
+use tempfile::tempdir;
+use tokio::fs::File;
+use tokio::io::AsyncWriteExt;
+use utils::test_config;
+use uuid::Uuid;
+
+#[actix_rt::test]
+async fn test_collect_garbage() -> std::io::Result<()> {
+    let temp = tempdir()?;
+    let mut config = test_config(String::new(), temp.path());
+    config.gc_grace_period = 0;
+    let referenced = Chunk::new(Uuid::new_v4(), Uuid::new_v4(), config.server_id);
+    let orphaned = Chunk::new(Uuid::new_v4(), Uuid::new_v4(), config.server_id);
+    for chunk in [referenced, orphaned].iter() {
+        let mut f = File::create(temp.path().join(chunk.chunk_name())).await?;
+        f.write_all(b"Test file content").await?;
+    }
+    File::create(temp.path().join("not-a-chunk")).await?;
+
+    let meta = MockServer::start();
+    let referenced_mock = meta.mock(|when, then| {
+        when.method(Method::POST).path("/api/chunks/referenced");
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body_obj(&vec![referenced]);
+    });
+    config.metadata_url = meta.base_url();
+
+    assert_eq!(collect_garbage(&config).await.unwrap(), 1);
+    referenced_mock.assert();
+    assert!(temp.path().join(referenced.chunk_name()).exists());
+    assert!(!temp.path().join(orphaned.chunk_name()).exists());
+    assert!(temp.path().join("not-a-chunk").exists());
+    Ok(())
+}
+
+#[actix_rt::test]
+async fn test_collect_garbage_grace_period() -> std::io::Result<()> {
+    let temp = tempdir()?;
+    let meta = MockServer::start();
+    let referenced_mock = meta.mock(|when, then| {
+        when.method(Method::POST).path("/api/chunks/referenced");
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body_obj(&Vec::<Chunk>::new());
+    });
+    let config = test_config(meta.base_url(), temp.path());
+    let chunk = Chunk::new(Uuid::new_v4(), Uuid::new_v4(), config.server_id);
+    File::create(temp.path().join(chunk.chunk_name())).await?;
+
+    assert_eq!(collect_garbage(&config).await.unwrap(), 0);
+    referenced_mock.assert_hits(0);
+    assert!(temp.path().join(chunk.chunk_name()).exists());
+    Ok(())
+}
+
+#[actix_rt::test]
+async fn test_collect_garbage_meta_fail() -> std::io::Result<()> {
+    let temp = tempdir()?;
+    let meta = MockServer::start();
+    meta.mock(|when, then| {
+        when.method(Method::POST).path("/api/chunks/referenced");
+        then.status(500).body("Metadata communication error");
+    });
+    let mut config = test_config(meta.base_url(), temp.path());
+    config.gc_grace_period = 0;
+    let chunk = Chunk::new(Uuid::new_v4(), Uuid::new_v4(), config.server_id);
+    File::create(temp.path().join(chunk.chunk_name())).await?;
+
+    assert!(collect_garbage(&config).await.is_err());
+    assert!(temp.path().join(chunk.chunk_name()).exists());
+    Ok(())
+}
+
+#[actix_rt::test]
+async fn test_collect_garbage_refuses_most_chunks() -> std::io::Result<()> {
+    let temp = tempdir()?;
+    let meta = MockServer::start();
+    let mut config = test_config(meta.base_url(), temp.path());
+    config.gc_grace_period = 0;
+    let referenced = Chunk::new(Uuid::new_v4(), Uuid::new_v4(), config.server_id);
+    meta.mock(|when, then| {
+        when.method(Method::POST).path("/api/chunks/referenced");
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body_obj(&vec![referenced]);
+    });
+    // a node with incomplete metadata would report most of the chunks as unreferenced
+    let orphaned = (0..2)
+        .map(|_| Chunk::new(Uuid::new_v4(), Uuid::new_v4(), config.server_id))
+        .collect::<Vec<_>>();
+    for chunk in orphaned.iter().chain(std::iter::once(&referenced)) {
+        File::create(temp.path().join(chunk.chunk_name())).await?;
+    }
+
+    assert!(collect_garbage(&config).await.is_err());
+    for chunk in orphaned.iter() {
+        assert!(temp.path().join(chunk.chunk_name()).exists());
+    }
+    Ok(())
+}
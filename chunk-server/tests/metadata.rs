@@ -0,0 +1,51 @@
+mod utils;
+
+use actix_http::http::StatusCode;
+use actix_web::test::{call_service, init_service, read_body_json, TestRequest};
+use actix_web::{web, App};
+use ccfs_commons::{Chunk, ChunkMetadata, FileMetadata, StoredChunk};
+use chunk_server::checksums::write_checksum;
+use chunk_server::chunk_metadata::write_metadata;
+use chunk_server::routes::get_chunks_metadata;
+use tempfile::tempdir;
+use tokio::fs::File;
+use utils::test_config;
+use uuid::Uuid;
+
+#[actix_rt::test]
+async fn test_get_chunks_metadata() -> std::io::Result<()> {
+    let temp = tempdir()?;
+    let config = test_config(String::new(), temp.path());
+    let chunk = Chunk::new(Uuid::new_v4(), Uuid::new_v4(), config.server_id);
+    let legacy = Chunk::new(Uuid::new_v4(), Uuid::new_v4(), config.server_id);
+    for c in [chunk, legacy].iter() {
+        File::create(temp.path().join(c.chunk_name())).await?;
+    }
+    write_checksum(temp.path(), &chunk.chunk_name(), 1)
+        .await
+        .unwrap();
+    let file = FileMetadata::create_file("file.txt".into(), 10, vec![chunk.id]);
+    let metadata = ChunkMetadata::new("/dir", &file, 0);
+    write_metadata(temp.path(), &chunk.chunk_name(), &metadata)
+        .await
+        .unwrap();
+
+    let server = init_service(
+        App::new()
+            .data(config.server_id)
+            .data(config.upload_path.clone())
+            .service(web::scope("/api").service(get_chunks_metadata)),
+    )
+    .await;
+    let req = TestRequest::get().uri("/api/chunks/metadata").to_request();
+    let resp = call_service(&server, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    // the chunks stored without the metadata are skipped
+    let stored: Vec<StoredChunk> = read_body_json(resp).await;
+    let expected = StoredChunk {
+        chunk: chunk.with_checksum(1),
+        metadata,
+    };
+    assert_eq!(stored, vec![expected]);
+    Ok(())
+}
@@ -0,0 +1,37 @@
+mod utils;
+
+use chunk_server::jobs::ping_metadata_servers;
+use chunk_server::stats::PendingRequests;
+use httpmock::{Method, MockServer};
+use tempfile::tempdir;
+use utils::test_config;
+
+#[actix_rt::test]
+async fn test_ping_all_metadata_servers() -> std::io::Result<()> {
+    let temp = tempdir()?;
+    let leader = MockServer::start();
+    let followers = vec![MockServer::start(), MockServer::start()];
+    let mut config = test_config(leader.base_url(), temp.path());
+    config.metadata_peers = followers.iter().map(|f| f.base_url()).collect();
+    let server_id = config.server_id.to_string();
+    let mocks = std::iter::once(&leader)
+        .chain(&followers)
+        .map(|meta| {
+            meta.mock(|when, then| {
+                when.method(Method::POST)
+                    .path("/api/ping")
+                    .header("x-ccfs-chunk-server-id", &server_id)
+                    .header("x-ccfs-chunk-server-address", "127.0.0.1:4567");
+                then.status(200);
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let pending = PendingRequests::default();
+    let pinged = ping_metadata_servers("127.0.0.1:4567", &config, &pending).await;
+    assert_eq!(pinged, 3);
+    for mock in mocks.iter() {
+        mock.assert();
+    }
+    Ok(())
+}
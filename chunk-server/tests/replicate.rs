@@ -4,6 +4,7 @@ use actix_http::http::StatusCode;
 use actix_web::test::{call_service, init_service, TestRequest};
 use actix_web::{web, App};
 use ccfs_commons::chunk_name;
+use chunk_server::checksums::write_checksum;
 use chunk_server::routes::replicate;
 use httpmock::{Method, MockServer};
 use std::sync::Arc;
@@ -21,6 +22,9 @@ async fn test_successful_replication() -> std::io::Result<()> {
     let temp = tempdir()?;
     let mut f = File::create(temp.path().join(&chunk_file_name)).await?;
     f.write_all(b"Test file content").await?;
+    write_checksum(temp.path(), &chunk_file_name, 0x4f7271d5)
+        .await
+        .unwrap();
 
     let server_config = Arc::new(test_config("url".into(), temp.path()));
     // setup chunk server mock
@@ -38,6 +42,7 @@ async fn test_successful_replication() -> std::io::Result<()> {
             .path("/api/upload")
             .body_contains(&chunk_id)
             .body_contains(&file_id)
+            .body_contains("4f7271d5")
             .body_contains("Test file content");
         then.status(204);
     });
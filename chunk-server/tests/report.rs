@@ -0,0 +1,95 @@
+mod utils;
+
+use ccfs_commons::{Chunk, ChunkReportResult};
+use chunk_server::checksums::write_checksum;
+use chunk_server::jobs::report::{build_report, list_chunks, send_chunk_report};
+use httpmock::{Method, MockServer};
+use std::collections::HashSet;
+use tempfile::tempdir;
+use tokio::fs::File;
+use utils::test_config;
+use uuid::Uuid;
+
+#[actix_rt::test]
+async fn test_list_chunks() -> std::io::Result<()> {
+    let temp = tempdir()?;
+    let config = test_config(String::new(), temp.path());
+    let chunk = Chunk::new(Uuid::new_v4(), Uuid::new_v4(), config.server_id);
+    let legacy = Chunk::new(Uuid::new_v4(), Uuid::new_v4(), config.server_id);
+    for c in [chunk, legacy].iter() {
+        File::create(temp.path().join(c.chunk_name())).await?;
+    }
+    write_checksum(temp.path(), &chunk.chunk_name(), 1)
+        .await
+        .unwrap();
+    File::create(temp.path().join("not-a-chunk")).await?;
+
+    let listed = list_chunks(&config).await.unwrap();
+    assert_eq!(listed, vec![chunk, legacy].into_iter().collect());
+    let checksums = listed
+        .iter()
+        .map(|c| (c.id, c.checksum))
+        .collect::<HashSet<_>>();
+    let expected = vec![(chunk.id, Some(1)), (legacy.id, None)];
+    assert_eq!(checksums, expected.into_iter().collect());
+    Ok(())
+}
+
+#[test]
+fn test_build_report() {
+    let server_id = Uuid::new_v4();
+    let chunks = (0..3)
+        .map(|_| Chunk::new(Uuid::new_v4(), Uuid::new_v4(), server_id))
+        .collect::<Vec<_>>();
+    let previous = chunks[..2].iter().cloned().collect::<HashSet<_>>();
+    let inventory = chunks[1..].iter().cloned().collect::<HashSet<_>>();
+
+    let full = build_report(server_id, &inventory, None);
+    assert!(full.full);
+    assert_eq!(
+        full.stored.iter().cloned().collect::<HashSet<_>>(),
+        inventory
+    );
+    assert!(full.removed.is_empty());
+
+    let incremental = build_report(server_id, &inventory, Some(&previous));
+    assert!(!incremental.full);
+    assert_eq!(incremental.stored, vec![chunks[2]]);
+    assert_eq!(incremental.removed, vec![chunks[0]]);
+
+    // the checksum sidecar written after the previous listing doesn't change the replica
+    let with_checksum = inventory
+        .iter()
+        .map(|c| c.with_checksum(1))
+        .collect::<HashSet<_>>();
+    let incremental = build_report(server_id, &with_checksum, Some(&inventory));
+    assert!(incremental.stored.is_empty());
+    assert!(incremental.removed.is_empty());
+}
+
+#[actix_rt::test]
+async fn test_send_chunk_report() -> std::io::Result<()> {
+    let temp = tempdir()?;
+    let mut config = test_config(String::new(), temp.path());
+    let chunk = Chunk::new(Uuid::new_v4(), Uuid::new_v4(), config.server_id);
+    let report = build_report(config.server_id, &vec![chunk].into_iter().collect(), None);
+    let result = ChunkReportResult {
+        unknown: vec![chunk],
+        ..Default::default()
+    };
+
+    let meta = MockServer::start();
+    let report_mock = meta.mock(|when, then| {
+        when.method(Method::POST)
+            .path("/api/chunks/report")
+            .json_body_obj(&report);
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body_obj(&result);
+    });
+    config.metadata_url = meta.base_url();
+
+    assert_eq!(send_chunk_report(&config, &report).await.unwrap(), result);
+    report_mock.assert();
+    Ok(())
+}
@@ -0,0 +1,103 @@
+mod utils;
+
+use ccfs_commons::Chunk;
+use chunk_server::checksums::{checksum_path, write_checksum};
+use chunk_server::jobs::scrub::{report_corrupted_chunks, scrub_chunks, QUARANTINE_DIR};
+use httpmock::{Method, MockServer};
+use tempfile::tempdir;
+use tokio::fs::File;
+use tokio::io::AsyncWriteExt;
+use utils::test_config;
+use uuid::Uuid;
+
+/// CRC32 checksum of "Test file content"
+const CHECKSUM: u32 = 0x4f7271d5;
+
+#[actix_rt::test]
+async fn test_scrub_chunks() -> std::io::Result<()> {
+    let temp = tempdir()?;
+    let config = test_config(String::new(), temp.path());
+    let healthy = Chunk::new(Uuid::new_v4(), Uuid::new_v4(), config.server_id);
+    let corrupted = Chunk::new(Uuid::new_v4(), Uuid::new_v4(), config.server_id);
+    let unchecked = Chunk::new(Uuid::new_v4(), Uuid::new_v4(), config.server_id);
+    let contents = [
+        (healthy, "Test file content"),
+        (corrupted, "Test file c0ntent"),
+        (unchecked, "Test file c0ntent"),
+    ];
+    for (chunk, content) in contents.iter() {
+        let mut f = File::create(temp.path().join(chunk.chunk_name())).await?;
+        f.write_all(content.as_bytes()).await?;
+    }
+    // the chunks stored before the checksums were recorded are skipped
+    for chunk in [healthy, corrupted].iter() {
+        write_checksum(temp.path(), &chunk.chunk_name(), CHECKSUM)
+            .await
+            .unwrap();
+    }
+
+    let quarantined = scrub_chunks(&config).await.unwrap();
+    assert_eq!(quarantined, vec![corrupted.with_checksum(CHECKSUM)]);
+    assert!(temp.path().join(healthy.chunk_name()).exists());
+    assert!(temp.path().join(unchecked.chunk_name()).exists());
+    assert!(!temp.path().join(corrupted.chunk_name()).exists());
+    let quarantine_dir = temp.path().join(QUARANTINE_DIR);
+    assert!(quarantine_dir.join(corrupted.chunk_name()).exists());
+    assert!(checksum_path(&quarantine_dir, &corrupted.chunk_name()).exists());
+
+    // quarantined chunks aren't scrubbed again
+    assert!(scrub_chunks(&config).await.unwrap().is_empty());
+    Ok(())
+}
+
+#[actix_rt::test]
+async fn test_scrub_chunks_quarantine_fails() -> std::io::Result<()> {
+    let temp = tempdir()?;
+    let config = test_config(String::new(), temp.path());
+    // the quarantine dir can't be created
+    File::create(temp.path().join(QUARANTINE_DIR)).await?;
+    let mut chunks = vec![
+        Chunk::new(Uuid::new_v4(), Uuid::new_v4(), config.server_id),
+        Chunk::new(Uuid::new_v4(), Uuid::new_v4(), config.server_id),
+    ];
+    for chunk in chunks.iter() {
+        let mut f = File::create(temp.path().join(chunk.chunk_name())).await?;
+        f.write_all("Test file c0ntent".as_bytes()).await?;
+        write_checksum(temp.path(), &chunk.chunk_name(), CHECKSUM)
+            .await
+            .unwrap();
+    }
+
+    let mut quarantined = scrub_chunks(&config).await.unwrap();
+    quarantined.sort_by_key(|c| c.id);
+    chunks.sort_by_key(|c| c.id);
+    let expected = chunks
+        .iter()
+        .map(|c| c.with_checksum(CHECKSUM))
+        .collect::<Vec<_>>();
+    assert_eq!(quarantined, expected);
+    for chunk in chunks.iter() {
+        assert!(temp.path().join(chunk.chunk_name()).exists());
+    }
+    Ok(())
+}
+
+#[actix_rt::test]
+async fn test_report_corrupted_chunks() -> std::io::Result<()> {
+    let temp = tempdir()?;
+    let mut config = test_config(String::new(), temp.path());
+    let chunk = Chunk::new(Uuid::new_v4(), Uuid::new_v4(), config.server_id);
+
+    let meta = MockServer::start();
+    let report_mock = meta.mock(|when, then| {
+        when.method(Method::POST)
+            .path("/api/chunks/corrupted")
+            .json_body_obj(&vec![chunk]);
+        then.status(200);
+    });
+    config.metadata_url = meta.base_url();
+
+    report_corrupted_chunks(&config, &[chunk]).await.unwrap();
+    report_mock.assert();
+    Ok(())
+}
@@ -0,0 +1,27 @@
+use ccfs_commons::Chunk;
+use chunk_server::stats::{get_server_stats, PendingRequests};
+use tempfile::tempdir;
+use tokio::fs::File;
+use uuid::Uuid;
+
+#[actix_rt::test]
+async fn test_get_server_stats() -> std::io::Result<()> {
+    let temp = tempdir()?;
+    for _ in 0..2 {
+        let chunk = Chunk::new(Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        File::create(temp.path().join(chunk.chunk_name())).await?;
+    }
+    File::create(temp.path().join("not-a-chunk")).await?;
+
+    let pending = PendingRequests::default();
+    let guard = pending.start();
+    let stats = get_server_stats(temp.path(), &pending).await.unwrap();
+    assert_eq!(stats.chunks_count, 2);
+    assert_eq!(stats.pending_requests, 1);
+    assert!(stats.total_space > 0);
+    assert!(stats.available_space <= stats.total_space);
+
+    drop(guard);
+    assert_eq!(pending.count(), 0);
+    Ok(())
+}
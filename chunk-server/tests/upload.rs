@@ -3,12 +3,16 @@ mod utils;
 use actix_http::http::StatusCode;
 use actix_web::test::{call_service, init_service};
 use actix_web::{web, App};
-use ccfs_commons::chunk_name;
+use ccfs_commons::http_utils::MAX_REDIRECTS;
+use ccfs_commons::{chunk_name, ChunkMetadata, FileMetadata};
+use chunk_server::checksums::checksum_path;
+use chunk_server::chunk_metadata::read_metadata;
 use chunk_server::routes::upload;
 use httpmock::{Method, MockServer};
 use std::sync::Arc;
 use tempfile::tempdir;
-use utils::{create_multipart_request, is_empty, test_config};
+use tokio::fs::read_to_string;
+use utils::{create_multipart_request, is_empty, test_config, CONTENT_CHECKSUM};
 
 #[actix_rt::test]
 async fn test_successful_upload() -> std::io::Result<()> {
@@ -19,7 +23,9 @@ async fn test_successful_upload() -> std::io::Result<()> {
     // setup metadata server mock
     let meta = MockServer::start();
     let upload_mock = meta.mock(|when, then| {
-        when.method(Method::POST).path("/api/chunk/completed");
+        when.method(Method::POST)
+            .path("/api/chunk/completed")
+            .body_contains("\"checksum\":1332900309");
         then.status(204);
     });
 
@@ -37,12 +43,25 @@ async fn test_successful_upload() -> std::io::Result<()> {
     )
     .await;
 
-    let req = create_multipart_request("/api/upload", chunk_id.into(), file_id.into()).await;
+    let file = FileMetadata::create_file("file.txt".into(), 17, Vec::new());
+    let metadata = ChunkMetadata::new("/dir", &file, 0);
+    let req = create_multipart_request(
+        "/api/upload",
+        chunk_id.into(),
+        file_id.into(),
+        Some(CONTENT_CHECKSUM),
+        Some(&metadata),
+    )
+    .await;
     let resp = call_service(&server, req).await;
     upload_mock.assert();
     assert_eq!(resp.status(), StatusCode::OK);
 
-    assert!(temp.path().join(chunk_file_name).exists());
+    assert!(temp.path().join(&chunk_file_name).exists());
+    let checksum_file = checksum_path(temp.path(), &chunk_file_name);
+    assert_eq!(read_to_string(checksum_file).await?, CONTENT_CHECKSUM);
+    let recorded = read_metadata(temp.path(), &chunk_file_name).await.unwrap();
+    assert_eq!(recorded, Some(metadata));
     Ok(())
 }
 
@@ -75,7 +94,14 @@ async fn test_meta_fail() -> std::io::Result<()> {
     )
     .await;
 
-    let req = create_multipart_request("/api/upload", chunk_id.into(), file_id.into()).await;
+    let req = create_multipart_request(
+        "/api/upload",
+        chunk_id.into(),
+        file_id.into(),
+        Some(CONTENT_CHECKSUM),
+        None,
+    )
+    .await;
     let resp = call_service(&server, req).await;
     upload_mock.assert();
     assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
@@ -84,6 +110,44 @@ async fn test_meta_fail() -> std::io::Result<()> {
     Ok(())
 }
 
+#[actix_rt::test]
+async fn test_meta_redirect_loop() -> std::io::Result<()> {
+    let chunk_id = "1a6e7006-12a7-4935-b8c0-58fa7ea84b09".to_string();
+    let file_id = "6d53a85f-505b-4a1a-ae6d-f7c18761d04a".to_string();
+
+    // a follower with stale leader info which redirects to itself
+    let meta = MockServer::start();
+    let location = format!("{}/api/chunk/completed", meta.base_url());
+    let upload_mock = meta.mock(|when, then| {
+        when.method(Method::POST).path("/api/chunk/completed");
+        then.status(307).header("Location", &location);
+    });
+
+    let temp = tempdir()?;
+    let server_config = Arc::new(test_config(meta.base_url(), temp.path()));
+    let server = init_service(
+        App::new()
+            .data(server_config.metadata_url.clone())
+            .data(server_config.server_id)
+            .data(server_config.upload_path.clone())
+            .service(web::scope("/api").service(upload)),
+    )
+    .await;
+
+    let req = create_multipart_request(
+        "/api/upload",
+        chunk_id.into(),
+        file_id.into(),
+        Some(CONTENT_CHECKSUM),
+        None,
+    )
+    .await;
+    let resp = call_service(&server, req).await;
+    upload_mock.assert_hits(MAX_REDIRECTS + 1);
+    assert!(!resp.status().is_success());
+    Ok(())
+}
+
 #[actix_rt::test]
 async fn test_missing_form_data() -> std::io::Result<()> {
     let chunk_id = "1a6e7006-12a7-4935-b8c0-58fa7ea84b09".to_string();
@@ -109,11 +173,58 @@ async fn test_missing_form_data() -> std::io::Result<()> {
     )
     .await;
 
-    let req = create_multipart_request("/api/upload", chunk_id.into(), None).await;
+    let req = create_multipart_request(
+        "/api/upload",
+        chunk_id.into(),
+        None,
+        Some(CONTENT_CHECKSUM),
+        None,
+    )
+    .await;
+    let resp = call_service(&server, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    upload_mock.assert_hits(0);
+
+    assert!(is_empty(temp.path()).await?);
+    Ok(())
+}
+
+#[actix_rt::test]
+async fn test_checksum_mismatch() -> std::io::Result<()> {
+    let chunk_id = "1a6e7006-12a7-4935-b8c0-58fa7ea84b09".to_string();
+    let file_id = "6d53a85f-505b-4a1a-ae6d-f7c18761d04a".to_string();
+
+    // setup metadata server mock
+    let meta = MockServer::start();
+    let upload_mock = meta.mock(|when, then| {
+        when.method(Method::POST).path("/api/chunk/completed");
+        then.status(200);
+    });
+
+    let temp = tempdir()?;
+    let server_config = Arc::new(test_config(meta.base_url(), temp.path()));
+    // setup chunk server mock
+    let server = init_service(
+        App::new()
+            .data(server_config.metadata_url.clone())
+            .data(server_config.server_id)
+            .data(server_config.upload_path.clone())
+            .service(web::scope("/api").service(upload)),
+    )
+    .await;
+
+    let req = create_multipart_request(
+        "/api/upload",
+        chunk_id.into(),
+        file_id.into(),
+        Some("0badc0de"),
+        None,
+    )
+    .await;
     let resp = call_service(&server, req).await;
     assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
     upload_mock.assert_hits(0);
 
-    assert_eq!(is_empty(temp.path()).await?, true);
+    assert!(is_empty(temp.path()).await?);
     Ok(())
 }
@@ -3,6 +3,7 @@ use actix_web::body::BodyStream;
 use actix_web::dev::Body;
 use actix_web::test::TestRequest;
 use actix_web::web::{Bytes, BytesMut};
+use ccfs_commons::ChunkMetadata;
 use chunk_server::server_config::ServerConfig;
 use futures_util::future::poll_fn;
 use mpart_async::client::MultipartRequest;
@@ -17,11 +18,17 @@ pub async fn is_empty(path: &Path) -> std::io::Result<bool> {
     Ok(matches!(read_dir(path).await?.next_entry().await?, None))
 }
 
+/// Checksum of the "Test file content" content
+#[allow(dead_code)]
+pub const CONTENT_CHECKSUM: &str = "4f7271d5";
+
 #[allow(dead_code)]
 pub async fn create_multipart_request(
     url: &'static str,
     chunk_id: Option<String>,
     file_id: Option<String>,
+    checksum: Option<&str>,
+    metadata: Option<&ChunkMetadata>,
 ) -> Request {
     let stream = ReaderStream::new("Test file content".as_bytes());
     let mut mpart = MultipartRequest::default();
@@ -31,6 +38,12 @@ pub async fn create_multipart_request(
     if let Some(file_id) = file_id {
         mpart.add_field("file_id", &file_id);
     }
+    if let Some(checksum) = checksum {
+        mpart.add_field("checksum", checksum);
+    }
+    if let Some(metadata) = metadata {
+        mpart.add_field("metadata", &serde_json::to_string(metadata).unwrap());
+    }
     mpart.add_stream("file", "file_name", "application/octet-stream", stream);
     let boundary = mpart.get_boundary().to_string();
     let body = Body::from(BodyStream::new(Box::new(mpart)));
@@ -58,8 +71,15 @@ pub fn test_config(meta_url: String, upload_path: &Path) -> ServerConfig {
         host: "127.0.0.1".into(),
         port: 4567,
         metadata_url: meta_url,
+        metadata_peers: Vec::new(),
         server_id: Uuid::new_v4(),
+        zone: None,
         upload_path: upload_path.into(),
         ping_interval: 5,
+        gc_interval: 60,
+        gc_grace_period: 600,
+        report_interval: 60,
+        scrub_interval: 60,
+        scrub_rate: 0,
     }
 }
@@ -1,83 +1,31 @@
-use actix_web::error::{ErrorBadRequest, ErrorInternalServerError};
-use actix_web::{HttpResponse, ResponseError};
-use ccfs_commons::errors::CCFSResponseError;
 use snafu::Snafu;
+use snafu_cli_debug::SnafuCliDebug;
 use std::path::PathBuf;
-use uuid::Uuid;
 
-#[derive(Debug, Snafu)]
+#[derive(Snafu, SnafuCliDebug)]
 #[snafu(visibility = "pub")]
 pub enum Error {
-    #[snafu(display("{}", source))]
-    Base { source: ccfs_commons::errors::Error },
-
-    #[snafu(display("Unable to parse to json: {}", source))]
-    ParseJson {
-        source: actix_web::client::JsonPayloadError,
-    },
-
-    #[snafu(display("Unable to read payload: {}", source))]
-    ParseBytes {
-        source: actix_web::client::PayloadError,
-    },
-
     #[snafu(display("Unable to parse yaml: {}", source))]
     ParseYaml { source: serde_yaml::Error },
 
-    #[snafu(display("Chunk {} is currently not available", chunk_name))]
-    ChunkNotAvailable { chunk_name: String },
-
-    #[snafu(display("Failed to download some chunks"))]
-    SomeChunksNotAvailable,
-
-    #[snafu(display("Failed to upload some chunks"))]
-    UploadChunks,
-
-    #[snafu(display("Failed to upload chunk {} for file {}", part, chunk_id))]
-    UploadSingleChunk { part: usize, chunk_id: Uuid },
-
     #[snafu(display("File doesn't exist: '{}'", path.display()))]
     FileNotExist { path: PathBuf },
 
-    #[snafu(display("'{}' already exist", path.display()))]
-    AlreadyExists { path: PathBuf },
-
     #[snafu(display("Missing config value '{}'", key))]
     MissingConfigVal { key: String },
 
-    #[snafu(display("There are no available servers, try again later"))]
-    NoAvailableServers,
+    #[snafu(display("Target path can only be set when uploading the stdin"))]
+    UnexpectedTargetPath,
 
-    #[snafu(display("Cannot create temp dir"))]
-    TempDir { source: std::io::Error },
-}
+    #[snafu(display("Uploads of the stdin can't be resumed"))]
+    StdinResume,
 
-impl<'a> ResponseError for Error {
-    fn error_response(&self) -> HttpResponse {
-        use Error::*;
-        let display = format!("{}", self);
-        match self {
-            Base { source } => source.error_response(),
-            ParseJson { .. } | ParseBytes { .. } | ParseYaml { .. } => {
-                ErrorBadRequest(display).into()
-            }
-            ChunkNotAvailable { .. }
-            | SomeChunksNotAvailable { .. }
-            | UploadChunks { .. }
-            | UploadSingleChunk { .. }
-            | FileNotExist { .. }
-            | TempDir { .. }
-            | AlreadyExists { .. }
-            | NoAvailableServers { .. }
-            | MissingConfigVal { .. } => ErrorInternalServerError(display).into(),
-        }
-    }
-}
+    #[snafu(display("Upload was interrupted, the uploaded part was removed"))]
+    UploadCanceled,
+
+    #[snafu(display("Unable to upload input: {}", source))]
+    ReadInput { source: std::io::Error },
 
-impl From<Error> for CCFSResponseError {
-    fn from(error: Error) -> CCFSResponseError {
-        CCFSResponseError {
-            inner: Box::new(error),
-        }
-    }
+    #[snafu(display("Unable to write output: {}", source))]
+    WriteOutput { source: std::io::Error },
 }
@@ -1,15 +1,20 @@
 mod errors;
-mod file_ops;
 
-use actix_web::client::Client;
-use ccfs_commons::errors::{CCFSResponseError, Error as BaseError};
+use ccfs_client::CcfsClient;
+use ccfs_commons::errors::Error as BaseError;
+use ccfs_commons::CURR_DIR;
 use errors::*;
-use file_ops::{download, list, tree, upload};
 use snafu::ResultExt;
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use structopt::StructOpt;
 use tokio::fs::read_to_string;
+use tokio::io::{copy, stdin, stdout, AsyncWriteExt};
+use tokio::select;
+use tokio::signal::ctrl_c;
+
+/// Path which stands for the stdin or the stdout
+const STDIO: &str = "-";
 
 #[derive(Debug, StructOpt)]
 /// Chop-Chop File System
@@ -26,20 +31,62 @@ struct CliOpts {
 
 #[derive(Debug, StructOpt)]
 enum Command {
-    /// Upload files to the CCFS
+    /// Upload files to the CCFS, an upload interrupted with Ctrl-C is canceled
     Upload {
-        /// The local absolute or relative path to the file to be uploaded to CCFS
+        /// The local absolute or relative path to the file to be uploaded to CCFS, `-` reads the stdin
         file_path: String,
+        /// The path of the created file on CCFS, required when uploading the stdin
+        #[structopt(required_if("file-path", STDIO))]
+        target_path: Option<String>,
+        /// Number of replicas of each file chunk, the metadata server default is used if it's not set
+        #[structopt(short = "n", long)]
+        replicas: Option<usize>,
+        /// Resume the interrupted upload of the same path, skipping the already uploaded files and chunks
+        #[structopt(short, long)]
+        resume: bool,
     },
-    /// Download file from the CCFS
+    /// Download file from the CCFS, an interrupted download continues from its verified chunks
+    /// when it's repeated
     Download {
         /// The path of the file on CCFS
         file_path: String,
+        /// The local directory where the file is downloaded, `-` writes the file content to the stdout
+        #[structopt(default_value = CURR_DIR)]
+        target_dir: String,
+        /// Download the version of the file, which is either its current version or one of the kept ones
+        #[structopt(long)]
+        version: Option<usize>,
+    },
+    /// Print a byte range of a file on the CCFS, downloading only the needed parts of its chunks
+    Read {
+        /// The path of the file on CCFS
+        file_path: String,
+        /// Position of the first byte to read
+        offset: u64,
+        /// Number of bytes to read, the range is shortened at the end of the file
+        length: u64,
     },
     /// Remove a file from the CCFS
     Remove {
         /// The path of the file on CCFS
         file_path: String,
+        /// Remove directories and their content recursively
+        #[structopt(short, long)]
+        recursive: bool,
+    },
+    /// Change the number of replicas of a file on the CCFS
+    SetReplicas {
+        /// The path of the file on CCFS
+        file_path: String,
+        /// Number of replicas of each file chunk, the metadata server default is used if it's not set
+        replicas: Option<usize>,
+    },
+    /// Change the number of the previous versions of a file on the CCFS which are kept when it's overwritten
+    SetVersions {
+        /// The path of the file on CCFS
+        file_path: String,
+        /// Number of the kept previous versions, the ones over it are removed
+        versions: usize,
     },
     /// List directory content
     List,
@@ -65,17 +112,133 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let key = "metadata-server-url";
     let meta_url = config_map
         .get(key)
-        .ok_or_else(|| CCFSResponseError::from(MissingConfigVal { key }.build()))?;
+        .ok_or_else(|| MissingConfigVal { key }.build())?;
+
+    // the progress of the uploads is recorded there, so that the interrupted ones can be resumed
+    let journal_dir = match config_map.get("upload-journal-dir") {
+        Some(dir) => PathBuf::from(dir),
+        None => dirs::data_local_dir()
+            .unwrap_or_else(|| PathBuf::from(CURR_DIR))
+            .join("ccfs")
+            .join("uploads"),
+    };
 
-    let client = Client::new();
+    let client = CcfsClient::new(meta_url.as_str());
     match opts.cmd {
-        Command::Upload { file_path } => upload(&client, &meta_url, &file_path).await?,
-        Command::Download { file_path } => {
-            download(&client, &meta_url, &file_path, None, false).await?
+        Command::Upload {
+            file_path,
+            target_path,
+            replicas,
+            resume,
+        } => match (file_path.as_str(), target_path) {
+            (STDIO, _) if resume => return Err(StdinResume.build().into()),
+            (STDIO, Some(target_path)) => {
+                let mut writer = client.create(&target_path, replicas).await?;
+                let file_id = writer.file_id();
+                let upload = async {
+                    copy(&mut stdin(), &mut writer).await?;
+                    writer.shutdown().await
+                };
+                // the interrupted upload is canceled, so its chunks don't linger on the servers
+                select! {
+                    result = upload => result.context(ReadInput)?,
+                    Ok(()) = ctrl_c() => {
+                        client.cancel_upload(file_id).await?;
+                        return Err(UploadCanceled.build().into());
+                    }
+                };
+                println!("Completed file upload");
+            }
+            (_, Some(_)) => return Err(UnexpectedTargetPath.build().into()),
+            (_, None) => {
+                let upload = client.upload_resumable(&file_path, replicas, &journal_dir, resume);
+                select! {
+                    result = upload => result?,
+                    Ok(()) = ctrl_c() => {
+                        client.cancel_resumable(&file_path, &journal_dir).await?;
+                        return Err(UploadCanceled.build().into());
+                    }
+                };
+                match Path::new(&file_path).is_dir() {
+                    true => println!("Completed directory upload"),
+                    false => println!("Completed file upload"),
+                }
+            }
+        },
+        Command::Download {
+            file_path,
+            target_dir,
+            version,
+        } if target_dir == STDIO => {
+            let mut reader = match version {
+                Some(version) => client.open_version(&file_path, version).await?,
+                None => client.open(&file_path).await?,
+            };
+            let mut stdout = stdout();
+            copy(&mut reader, &mut stdout).await.context(WriteOutput)?;
+            stdout.flush().await.context(WriteOutput)?;
+        }
+        Command::Download {
+            file_path,
+            target_dir,
+            version,
+        } => {
+            let download = match version {
+                Some(version) => {
+                    client
+                        .download_version(&file_path, version, target_dir, false)
+                        .await?
+                }
+                None => client.download(&file_path, target_dir, false).await?,
+            };
+            for chunk in download.corrupted_replicas {
+                eprintln!(
+                    "Chunk {} on server {} is corrupted",
+                    chunk.chunk_name(),
+                    chunk.server_id
+                );
+            }
+            println!("Finished downloading `{}`", download.file.name);
+        }
+        Command::Read {
+            file_path,
+            offset,
+            length,
+        } => {
+            let mut stdout = stdout();
+            client
+                .read_range(&file_path, offset, length, &mut stdout)
+                .await?;
+            stdout.flush().await.context(WriteOutput)?;
+        }
+        Command::Remove {
+            file_path,
+            recursive,
+        } => {
+            let file = client.remove(&file_path, recursive).await?;
+            println!("Removed `{}`", file.name);
+        }
+        Command::SetReplicas {
+            file_path,
+            replicas,
+        } => {
+            let file = client.set_replicas(&file_path, replicas).await?;
+            match replicas {
+                Some(replicas) => {
+                    println!("Set the number of `{}` replicas to {}", file.name, replicas)
+                }
+                None => println!("Reset the number of `{}` replicas to default", file.name),
+            }
+        }
+        Command::SetVersions {
+            file_path,
+            versions,
+        } => {
+            let file = client.set_kept_versions(&file_path, versions).await?;
+            println!("Keeping {} previous versions of `{}`", versions, file.name);
         }
-        Command::Remove { file_path: _path } => unimplemented!(),
-        Command::List => list(&client, &meta_url).await?,
-        Command::Tree => tree(&client, &meta_url).await?,
+        Command::List => println!("{}", client.stat("").await?.print_current_dir()?),
+        Command::Tree => println!("{}", client.stat("").await?.print_subtree()),
     };
     Ok(())
 }
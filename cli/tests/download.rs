@@ -126,12 +126,13 @@ async fn test_download_not_existing() -> Result<(), Box<dyn std::error::Error>>
         .arg(&config_file_path)
         .arg("download")
         .arg(TEST_FILE)
+        .arg(temp_dir.path())
         .assert()
         .failure()
         .stderr(predicate::str::contains(
             "Error: Request failed: File doesn't exist",
         ));
-    assert!(!downloaded.exists());
+    assert!(!temp_dir.path().join(TEST_FILE).exists());
     Ok(())
 }
 
@@ -172,6 +173,7 @@ async fn test_download_file_no_chunks_for_file() -> Result<(), Box<dyn std::erro
         .arg(&config_file_path)
         .arg("download")
         .arg(TEST_FILE)
+        .arg(temp_dir.path())
         .assert()
         .failure()
         .stderr(predicate::str::contains(
@@ -233,6 +235,7 @@ async fn test_download_file_chunk_download_failure() -> Result<(), Box<dyn std::
         .arg(&config_file_path)
         .arg("download")
         .arg(TEST_FILE)
+        .arg(temp_dir.path())
         .assert()
         .failure()
         .stderr(predicate::str::contains(
@@ -284,12 +287,13 @@ async fn test_download_file_get_server_failure() -> Result<(), Box<dyn std::erro
         .arg(&config_file_path)
         .arg("download")
         .arg(TEST_FILE)
+        .arg(temp_dir.path())
         .assert()
         .failure()
         .stderr(predicate::str::contains(
             "Error: Failed to download some chunks",
         ));
-    assert!(!downloaded.exists());
+    assert!(!temp_dir.path().join(TEST_FILE).exists());
     Ok(())
 }
 
@@ -523,11 +527,158 @@ async fn test_download_file_with_multiple_parts_one_chunk_failure(
         .arg(&config_file_path)
         .arg("download")
         .arg(TEST_FILE)
+        .arg(temp_dir.path())
         .assert()
         .failure()
         .stderr(predicate::str::contains(
             "Error: Failed to download some chunks",
         ));
+    assert!(!temp_dir.path().join(TEST_FILE).exists());
+    Ok(())
+}
+
+#[actix_rt::test]
+async fn test_download_file_corrupted_replica() -> Result<(), Box<dyn std::error::Error>> {
+    const TEST_FILE: &str = "test-corrupted.txt";
+    let downloaded = Path::new(TEST_FILE);
     assert!(!downloaded.exists());
+    let _cleanup = Cleanup::new(vec![downloaded.into()]);
+    let chunk_id = Uuid::new_v4();
+    let server1_id = Uuid::new_v4();
+    let server2_id = Uuid::new_v4();
+    let file_resp = FileMetadata::create_file(TEST_FILE.into(), 10, vec![chunk_id]);
+    let file_id = match &file_resp.file_info {
+        FileInfo::File { id, .. } => *id,
+        _ => unreachable!(),
+    };
+    // CRC32 checksum of "Test file content"
+    let chunk1 = Chunk::new(chunk_id, file_id, server1_id).with_checksum(0x4f7271d5);
+    let chunk2 = Chunk::new(chunk_id, file_id, server2_id).with_checksum(0x4f7271d5);
+    let temp_dir = tempdir_in("./")?;
+
+    let chunk_server1 = MockServer::start();
+    let chunk_server1_val = ChunkServer::new(server1_id, chunk_server1.base_url());
+    let chunk_server2 = MockServer::start();
+    let chunk_server2_val = ChunkServer::new(server2_id, chunk_server2.base_url());
+
+    let corrupted = chunk_server1.mock(|when, then| {
+        when.method(Method::GET)
+            .path(format!("/api/download/{}", chunk1.chunk_name()));
+        then.status(200).body("Test file c0ntent");
+    });
+    chunk_server2.mock(|when, then| {
+        when.method(Method::GET)
+            .path(format!("/api/download/{}", chunk2.chunk_name()));
+        then.status(200).body("Test file content");
+    });
+    let meta_server = MockServer::start();
+    meta_server.mock(|when, then| {
+        when.method(Method::GET)
+            .path("/api/files")
+            .query_param("path", TEST_FILE);
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body_obj(&file_resp);
+    });
+    meta_server.mock(|when, then| {
+        when.method(Method::GET)
+            .path(format!("/api/chunks/file/{}", file_id));
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body_obj(&vec![vec![chunk1, chunk2]]);
+    });
+    meta_server.mock(|when, then| {
+        when.method(Method::GET)
+            .path(format!("/api/servers/{}", server1_id));
+        then.status(200)
+            .header("Content-Type", "application/json")
+            .json_body_obj(&chunk_server1_val);
+    });
+    meta_server.mock(|when, then| {
+        when.method(Method::GET)
+            .path(format!("/api/servers/{}", server2_id));
+        then.status(200)
+            .header("Content-Type", "application/json")
+            .json_body_obj(&chunk_server2_val);
+    });
+
+    let config_file_path = create_config_file(&meta_server.base_url(), temp_dir.path()).await?;
+    Command::cargo_bin("cli")?
+        .arg("-c")
+        .arg(&config_file_path)
+        .arg("download")
+        .arg(TEST_FILE)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Finished downloading `test-corrupted.txt`",
+        ))
+        .stderr(predicate::str::contains("is corrupted"));
+
+    corrupted.assert();
+    assert_eq!(read_to_string(downloaded).await?, "Test file content");
+    Ok(())
+}
+
+#[actix_rt::test]
+async fn test_download_version() -> Result<(), Box<dyn std::error::Error>> {
+    let chunk_id = Uuid::new_v4();
+    let server_id = Uuid::new_v4();
+    let file_resp = FileMetadata::create_file("test.txt".into(), 11, vec![chunk_id]);
+    let file_id = match &file_resp.file_info {
+        FileInfo::File { id, .. } => *id,
+        _ => unreachable!(),
+    };
+    let chunk = Chunk::new(chunk_id, file_id, server_id);
+    let temp_dir = tempdir_in("./")?;
+
+    let chunk_server = MockServer::start();
+    chunk_server.mock(|when, then| {
+        when.method(Method::GET)
+            .path(format!("/api/download/{}", chunk.chunk_name()));
+        then.status(200).body("Old content");
+    });
+    let meta_server = MockServer::start();
+    let version_mock = meta_server.mock(|when, then| {
+        when.method(Method::GET)
+            .path("/api/files")
+            .query_param("path", "/dir/test.txt")
+            .query_param("version", "1");
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body_obj(&file_resp);
+    });
+    meta_server.mock(|when, then| {
+        when.method(Method::GET)
+            .path(format!("/api/chunks/file/{}", file_id));
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body_obj(&vec![vec![chunk]]);
+    });
+    let chunk_server_val = ChunkServer::new(server_id, chunk_server.base_url());
+    meta_server.mock(|when, then| {
+        when.method(Method::GET)
+            .path(format!("/api/servers/{}", server_id));
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body_obj(&chunk_server_val);
+    });
+
+    let config_file_path = create_config_file(&meta_server.base_url(), temp_dir.path()).await?;
+    Command::cargo_bin("cli")?
+        .arg("-c")
+        .arg(&config_file_path)
+        .arg("download")
+        .arg("/dir/test.txt")
+        .arg(temp_dir.path())
+        .arg("--version")
+        .arg("1")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Finished downloading `test.txt`"));
+
+    version_mock.assert();
+    let downloaded = temp_dir.path().join("test.txt");
+    assert_eq!(read_to_string(downloaded).await?, "Old content");
     Ok(())
 }
@@ -0,0 +1,114 @@
+mod utils;
+
+use assert_cmd::prelude::*;
+use ccfs_commons::{Chunk, ChunkServer, FileInfo, FileMetadata, CHUNK_SIZE};
+use httpmock::{Method, MockServer};
+use std::process::Command;
+use tempfile::tempdir_in;
+use utils::create_config_file;
+use uuid::Uuid;
+
+/// Mocks the metadata server returning the file, whose chunks are all stored on the chunk server
+fn mock_meta_server(file: &FileMetadata, chunk_server: &ChunkServer) -> MockServer {
+    let (file_id, chunk_ids) = match &file.file_info {
+        FileInfo::File { id, chunks, .. } => (*id, chunks.clone()),
+        _ => unreachable!(),
+    };
+    let chunks = chunk_ids
+        .iter()
+        .map(|id| vec![Chunk::new(*id, file_id, chunk_server.id)])
+        .collect::<Vec<_>>();
+    let meta_server = MockServer::start();
+    meta_server.mock(|when, then| {
+        when.method(Method::GET)
+            .path("/api/files")
+            .query_param("path", &file.name);
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body_obj(file);
+    });
+    meta_server.mock(|when, then| {
+        when.method(Method::GET)
+            .path(format!("/api/chunks/file/{}", file_id));
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body_obj(&chunks);
+    });
+    meta_server.mock(|when, then| {
+        when.method(Method::GET)
+            .path(format!("/api/servers/{}", chunk_server.id));
+        then.status(200)
+            .header("Content-Type", "application/json")
+            .json_body_obj(chunk_server);
+    });
+    meta_server
+}
+
+#[actix_rt::test]
+async fn test_read_range() -> Result<(), Box<dyn std::error::Error>> {
+    let file = FileMetadata::create_file("test.txt".into(), 17, vec![Uuid::new_v4()]);
+    let chunk_server = MockServer::start();
+    let server = ChunkServer::new(Uuid::new_v4(), chunk_server.base_url());
+    let range = chunk_server.mock(|when, then| {
+        when.method(Method::GET)
+            .path_contains("/api/download/")
+            .header("range", "bytes=5-8");
+        then.status(206).body("file");
+    });
+    let meta_server = mock_meta_server(&file, &server);
+
+    let temp_dir = tempdir_in("./")?;
+    let config_file_path = create_config_file(&meta_server.base_url(), temp_dir.path()).await?;
+    Command::cargo_bin("cli")?
+        .arg("-c")
+        .arg(&config_file_path)
+        .arg("read")
+        .arg("test.txt")
+        .arg("5")
+        .arg("4")
+        .assert()
+        .success()
+        .stdout("file");
+    range.assert();
+    Ok(())
+}
+
+#[actix_rt::test]
+async fn test_read_range_across_chunks() -> Result<(), Box<dyn std::error::Error>> {
+    let chunk_ids = vec![Uuid::new_v4(), Uuid::new_v4()];
+    let file = FileMetadata::create_file("test.txt".into(), CHUNK_SIZE + 10, chunk_ids.clone());
+    let chunk_server = MockServer::start();
+    let server = ChunkServer::new(Uuid::new_v4(), chunk_server.base_url());
+    let first_range = format!("bytes={}-{}", CHUNK_SIZE - 2, CHUNK_SIZE - 1);
+    let first = chunk_server.mock(|when, then| {
+        when.method(Method::GET)
+            .path_contains(chunk_ids[0].to_string())
+            .header("range", &first_range);
+        then.status(206).body("ab");
+    });
+    // the servers which don't support ranges send the whole chunk
+    let second = chunk_server.mock(|when, then| {
+        when.method(Method::GET)
+            .path_contains(chunk_ids[1].to_string())
+            .header("range", "bytes=0-3");
+        then.status(200).body("cdefghijkl");
+    });
+    let meta_server = mock_meta_server(&file, &server);
+
+    let temp_dir = tempdir_in("./")?;
+    let config_file_path = create_config_file(&meta_server.base_url(), temp_dir.path()).await?;
+    let offset = (CHUNK_SIZE - 2).to_string();
+    Command::cargo_bin("cli")?
+        .arg("-c")
+        .arg(&config_file_path)
+        .arg("read")
+        .arg("test.txt")
+        .arg(&offset)
+        .arg("6")
+        .assert()
+        .success()
+        .stdout("abcdef");
+    first.assert();
+    second.assert();
+    Ok(())
+}
@@ -0,0 +1,91 @@
+mod utils;
+
+use assert_cmd::prelude::*;
+use ccfs_commons::FileMetadata;
+use httpmock::{Method, MockServer};
+use predicates::prelude::*;
+use std::process::Command;
+use tempfile::tempdir_in;
+use utils::create_config_file;
+use uuid::Uuid;
+
+#[actix_rt::test]
+async fn test_remove_file() -> Result<(), Box<dyn std::error::Error>> {
+    let file_resp = FileMetadata::create_file("test.txt".into(), 10, vec![Uuid::new_v4()]);
+    let meta_server = MockServer::start();
+    let remove_mock = meta_server.mock(|when, then| {
+        when.method(Method::DELETE)
+            .path("/api/files")
+            .query_param("path", "/dir/test.txt")
+            .query_param("recursive", "false");
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body_obj(&file_resp);
+    });
+
+    let temp_dir = tempdir_in("./")?;
+    let config_file_path = create_config_file(&meta_server.base_url(), temp_dir.path()).await?;
+    Command::cargo_bin("cli")?
+        .arg("-c")
+        .arg(&config_file_path)
+        .arg("remove")
+        .arg("/dir/test.txt")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Removed `test.txt`"));
+    remove_mock.assert();
+    Ok(())
+}
+
+#[actix_rt::test]
+async fn test_remove_dir_recursive() -> Result<(), Box<dyn std::error::Error>> {
+    let dir_resp = FileMetadata::create_dir("dir".into());
+    let meta_server = MockServer::start();
+    let remove_mock = meta_server.mock(|when, then| {
+        when.method(Method::DELETE)
+            .path("/api/files")
+            .query_param("path", "/dir")
+            .query_param("recursive", "true");
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body_obj(&dir_resp);
+    });
+
+    let temp_dir = tempdir_in("./")?;
+    let config_file_path = create_config_file(&meta_server.base_url(), temp_dir.path()).await?;
+    Command::cargo_bin("cli")?
+        .arg("-c")
+        .arg(&config_file_path)
+        .arg("remove")
+        .arg("-r")
+        .arg("/dir")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Removed `dir`"));
+    remove_mock.assert();
+    Ok(())
+}
+
+#[actix_rt::test]
+async fn test_remove_dir_not_recursive() -> Result<(), Box<dyn std::error::Error>> {
+    let meta_server = MockServer::start();
+    meta_server.mock(|when, then| {
+        when.method(Method::DELETE).path("/api/files");
+        then.status(400)
+            .body("'/dir' is a directory, it can only be removed recursively");
+    });
+
+    let temp_dir = tempdir_in("./")?;
+    let config_file_path = create_config_file(&meta_server.base_url(), temp_dir.path()).await?;
+    Command::cargo_bin("cli")?
+        .arg("-c")
+        .arg(&config_file_path)
+        .arg("remove")
+        .arg("/dir")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Error: Request failed: '/dir' is a directory, it can only be removed recursively",
+        ));
+    Ok(())
+}
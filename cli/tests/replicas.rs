@@ -0,0 +1,102 @@
+mod utils;
+
+use assert_cmd::prelude::*;
+use ccfs_commons::FileMetadata;
+use httpmock::{Method, MockServer};
+use predicates::prelude::*;
+use std::process::Command;
+use tempfile::tempdir_in;
+use utils::create_config_file;
+use uuid::Uuid;
+
+#[actix_rt::test]
+async fn test_set_replicas() -> Result<(), Box<dyn std::error::Error>> {
+    let file_resp = FileMetadata::create_file("test.txt".into(), 10, vec![Uuid::new_v4()])
+        .with_replicas(Some(5));
+    let meta_server = MockServer::start();
+    let replicas_mock = meta_server.mock(|when, then| {
+        when.method(Method::PUT)
+            .path("/api/files/replicas")
+            .query_param("path", "/dir/test.txt")
+            .query_param("replicas", "5");
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body_obj(&file_resp);
+    });
+
+    let temp_dir = tempdir_in("./")?;
+    let config_file_path = create_config_file(&meta_server.base_url(), temp_dir.path()).await?;
+    Command::cargo_bin("cli")?
+        .arg("-c")
+        .arg(&config_file_path)
+        .arg("set-replicas")
+        .arg("/dir/test.txt")
+        .arg("5")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Set the number of `test.txt` replicas to 5",
+        ));
+    replicas_mock.assert();
+    Ok(())
+}
+
+#[actix_rt::test]
+async fn test_reset_replicas() -> Result<(), Box<dyn std::error::Error>> {
+    let file_resp = FileMetadata::create_file("test.txt".into(), 10, vec![Uuid::new_v4()]);
+    let meta_server = MockServer::start();
+    let replicas_mock = meta_server.mock(|when, then| {
+        when.method(Method::PUT)
+            .path("/api/files/replicas")
+            .query_param("path", "/dir/test.txt");
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body_obj(&file_resp);
+    });
+
+    let temp_dir = tempdir_in("./")?;
+    let config_file_path = create_config_file(&meta_server.base_url(), temp_dir.path()).await?;
+    Command::cargo_bin("cli")?
+        .arg("-c")
+        .arg(&config_file_path)
+        .arg("set-replicas")
+        .arg("/dir/test.txt")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Reset the number of `test.txt` replicas to default",
+        ));
+    replicas_mock.assert();
+    Ok(())
+}
+
+#[actix_rt::test]
+async fn test_set_versions() -> Result<(), Box<dyn std::error::Error>> {
+    let file_resp = FileMetadata::create_file("test.txt".into(), 10, vec![Uuid::new_v4()]);
+    let meta_server = MockServer::start();
+    let versions_mock = meta_server.mock(|when, then| {
+        when.method(Method::PUT)
+            .path("/api/files/versions")
+            .query_param("path", "/dir/test.txt")
+            .query_param("versions", "3");
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body_obj(&file_resp);
+    });
+
+    let temp_dir = tempdir_in("./")?;
+    let config_file_path = create_config_file(&meta_server.base_url(), temp_dir.path()).await?;
+    Command::cargo_bin("cli")?
+        .arg("-c")
+        .arg(&config_file_path)
+        .arg("set-versions")
+        .arg("/dir/test.txt")
+        .arg("3")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Keeping 3 previous versions of `test.txt`",
+        ));
+    versions_mock.assert();
+    Ok(())
+}
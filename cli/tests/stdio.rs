@@ -0,0 +1,211 @@
+mod utils;
+
+use assert_cmd::prelude::*;
+use ccfs_commons::FileStatus;
+use ccfs_commons::{Chunk, ChunkPlacement, ChunkServer, FileInfo, FileMetadata, FileUpload};
+use httpmock::{Method, MockServer};
+use predicates::prelude::*;
+use std::process::{Command, Stdio};
+use std::thread::sleep;
+use std::time::Duration;
+use tempfile::tempdir_in;
+use utils::create_config_file;
+use uuid::Uuid;
+
+#[actix_rt::test]
+async fn test_upload_stdin() -> Result<(), Box<dyn std::error::Error>> {
+    let file = FileMetadata::create_file("x.tar".into(), 0, Vec::new());
+    let file_id = match &file.file_info {
+        FileInfo::File { id, .. } => *id,
+        _ => unreachable!(),
+    };
+    let chunk_server = MockServer::start();
+    let upload_mock = chunk_server.mock(|when, then| {
+        when.method(Method::POST)
+            .path("/api/upload")
+            .body_contains("Test file content");
+        then.status(200);
+    });
+    let meta_server = MockServer::start();
+    let create_mock = meta_server.mock(|when, then| {
+        when.method(Method::POST)
+            .path("/api/files/upload")
+            .query_param("path", "/backups");
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body_obj(&FileUpload {
+                file: file.clone(),
+                path: "/backups".into(),
+                placement: Vec::new(),
+            });
+    });
+    let placement = vec![ChunkPlacement {
+        chunk_id: Uuid::new_v4(),
+        servers: vec![ChunkServer::new(Uuid::new_v4(), chunk_server.base_url())],
+    }];
+    meta_server.mock(|when, then| {
+        when.method(Method::POST)
+            .path(format!("/api/files/{}/chunks", file_id));
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body_obj(&placement);
+    });
+    let commit_mock = meta_server.mock(|when, then| {
+        when.method(Method::POST)
+            .path(format!("/api/files/{}/commit", file_id))
+            .query_param("size", "17");
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body_obj(&file);
+    });
+
+    let temp_dir = tempdir_in("./")?;
+    let config_file_path = create_config_file(&meta_server.base_url(), temp_dir.path()).await?;
+    assert_cmd::Command::cargo_bin("cli")?
+        .arg("-c")
+        .arg(&config_file_path)
+        .arg("upload")
+        .arg("-")
+        .arg("/backups/x.tar")
+        .write_stdin("Test file content")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Completed file upload"));
+    create_mock.assert();
+    upload_mock.assert();
+    commit_mock.assert();
+    Ok(())
+}
+
+#[actix_rt::test]
+async fn test_upload_target_path_without_stdin() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = tempdir_in("./")?;
+    let config_file_path = create_config_file("http://localhost", temp_dir.path()).await?;
+    Command::cargo_bin("cli")?
+        .arg("-c")
+        .arg(&config_file_path)
+        .arg("upload")
+        .arg("./test.txt")
+        .arg("/backups/x.tar")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Error: Target path can only be set when uploading the stdin",
+        ));
+    Ok(())
+}
+
+#[actix_rt::test]
+async fn test_download_stdout() -> Result<(), Box<dyn std::error::Error>> {
+    let chunk_id = Uuid::new_v4();
+    let server_id = Uuid::new_v4();
+    let file = FileMetadata::create_file("a.log".into(), 17, vec![chunk_id]);
+    let file_id = match &file.file_info {
+        FileInfo::File { id, .. } => *id,
+        _ => unreachable!(),
+    };
+    let chunk = Chunk::new(chunk_id, file_id, server_id);
+    let chunk_server = MockServer::start();
+    chunk_server.mock(|when, then| {
+        when.method(Method::GET)
+            .path(format!("/api/download/{}", chunk.chunk_name()));
+        then.status(200).body("Test file content");
+    });
+    let meta_server = MockServer::start();
+    meta_server.mock(|when, then| {
+        when.method(Method::GET)
+            .path("/api/files")
+            .query_param("path", "/logs/a.log");
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body_obj(&file);
+    });
+    meta_server.mock(|when, then| {
+        when.method(Method::GET)
+            .path(format!("/api/chunks/file/{}", file_id));
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body_obj(&vec![vec![chunk]]);
+    });
+    let chunk_server_val = ChunkServer::new(server_id, chunk_server.base_url());
+    meta_server.mock(|when, then| {
+        when.method(Method::GET)
+            .path(format!("/api/servers/{}", server_id));
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body_obj(&chunk_server_val);
+    });
+
+    let temp_dir = tempdir_in("./")?;
+    let config_file_path = create_config_file(&meta_server.base_url(), temp_dir.path()).await?;
+    Command::cargo_bin("cli")?
+        .arg("-c")
+        .arg(&config_file_path)
+        .arg("download")
+        .arg("/logs/a.log")
+        .arg("-")
+        .assert()
+        .success()
+        .stdout("Test file content");
+    Ok(())
+}
+
+#[actix_rt::test]
+async fn test_interrupted_upload_is_canceled() -> Result<(), Box<dyn std::error::Error>> {
+    let mut file = FileMetadata::create_file("x.tar".into(), 0, Vec::new());
+    let file_id = match &file.file_info {
+        FileInfo::File { id, .. } => *id,
+        _ => unreachable!(),
+    };
+    let meta_server = MockServer::start();
+    let create_mock = meta_server.mock(|when, then| {
+        when.method(Method::POST).path("/api/files/upload");
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body_obj(&FileUpload {
+                file: file.clone(),
+                path: "/backups".into(),
+                placement: Vec::new(),
+            });
+    });
+    if let FileInfo::File { status, .. } = &mut file.file_info {
+        *status = FileStatus::Canceled;
+    }
+    let cancel_mock = meta_server.mock(|when, then| {
+        when.method(Method::DELETE)
+            .path(format!("/api/files/{}/upload", file_id));
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body_obj(&file);
+    });
+
+    let temp_dir = tempdir_in("./")?;
+    let config_file_path = create_config_file(&meta_server.base_url(), temp_dir.path()).await?;
+    // the stdin stays open, so the upload waits for more input until it's interrupted
+    let child = Command::cargo_bin("cli")?
+        .arg("-c")
+        .arg(&config_file_path)
+        .arg("upload")
+        .arg("-")
+        .arg("/backups/x.tar")
+        .stdin(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    for _ in 0..50 {
+        if create_mock.hits() > 0 {
+            break;
+        }
+        sleep(Duration::from_millis(100));
+    }
+    sleep(Duration::from_millis(200));
+    Command::new("kill")
+        .arg("-INT")
+        .arg(child.id().to_string())
+        .assert()
+        .success();
+    let output = child.wait_with_output()?;
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr)?.contains("Upload was interrupted"));
+    cancel_mock.assert();
+    Ok(())
+}
@@ -1,7 +1,7 @@
 mod utils;
 
 use assert_cmd::prelude::*;
-use ccfs_commons::ChunkServer;
+use ccfs_commons::{ChunkPlacement, ChunkServer, FileInfo, FileMetadata, FileUpload, UploadStatus};
 use httpmock::{Method, MockServer};
 use predicates::prelude::*;
 use std::process::Command;
@@ -11,6 +11,21 @@ use tokio::io::AsyncWriteExt;
 use utils::create_config_file;
 use uuid::Uuid;
 
+/// Returns the `tests/file_resp.json` file, with its single chunk placed on the servers
+fn file_upload(servers: Vec<ChunkServer>) -> FileUpload {
+    let content = std::fs::read_to_string("tests/file_resp.json").unwrap();
+    let file: FileMetadata = serde_json::from_str(&content).unwrap();
+    let placement = vec![ChunkPlacement {
+        chunk_id: file.chunks().unwrap()[0],
+        servers,
+    }];
+    FileUpload {
+        file,
+        path: "/dir".into(),
+        placement,
+    }
+}
+
 #[actix_rt::test]
 async fn test_upload_not_existing_file() -> Result<(), Box<dyn std::error::Error>> {
     let mut cmd = Command::cargo_bin("cli")?;
@@ -49,22 +64,32 @@ async fn test_upload_file_meta_servers_error() -> Result<(), Box<dyn std::error:
 }
 
 #[actix_rt::test]
-async fn test_upload_file_fetch_servers_error() -> Result<(), Box<dyn std::error::Error>> {
+async fn test_upload_file_fallback_to_next_server() -> Result<(), Box<dyn std::error::Error>> {
     let temp_dir = tempdir_in("./")?;
     let file_path = temp_dir.path().join("test.txt");
     let mut file = File::create(&file_path).await?;
     file.write_all(b"Test file content").await?;
 
+    let failing_server = MockServer::start();
+    let chunk_server = MockServer::start();
+    let servers = vec![
+        ChunkServer::new(Uuid::new_v4(), failing_server.base_url()),
+        ChunkServer::new(Uuid::new_v4(), chunk_server.base_url()),
+    ];
     let meta_server = MockServer::start();
     meta_server.mock(|when, then| {
         when.method(Method::POST).path("/api/files/upload");
         then.status(200)
             .header("content-type", "application/json")
-            .body_from_file("tests/file_resp.json");
+            .json_body_obj(&file_upload(servers));
     });
-    meta_server.mock(|when, then| {
-        when.method(Method::GET).path("/api/servers");
-        then.status(500).body("Couldn't fetch servers");
+    let failed_upload = failing_server.mock(|when, then| {
+        when.method(Method::POST).path("/api/upload");
+        then.status(500);
+    });
+    let upload = chunk_server.mock(|when, then| {
+        when.method(Method::POST).path("/api/upload");
+        then.status(200);
     });
 
     let config_file_path = create_config_file(&meta_server.base_url(), temp_dir.path()).await?;
@@ -74,10 +99,10 @@ async fn test_upload_file_fetch_servers_error() -> Result<(), Box<dyn std::error
         .arg("upload")
         .arg(&file_path)
         .assert()
-        .failure()
-        .stderr(predicate::str::contains(
-            "Error: Request failed: Couldn't fetch servers",
-        ));
+        .success()
+        .stdout(predicate::str::contains("Completed file upload"));
+    failed_upload.assert();
+    upload.assert();
     Ok(())
 }
 
@@ -93,14 +118,7 @@ async fn test_upload_file_no_available_servers() -> Result<(), Box<dyn std::erro
         when.method(Method::POST).path("/api/files/upload");
         then.status(200)
             .header("content-type", "application/json")
-            .body_from_file("tests/file_resp.json");
-    });
-    let data: Vec<ChunkServer> = Vec::new();
-    meta_server.mock(|when, then| {
-        when.method(Method::GET).path("/api/servers");
-        then.status(200)
-            .header("Content-Type", "application/json")
-            .json_body_obj(&data);
+            .json_body_obj(&file_upload(Vec::new()));
     });
 
     let config_file_path = create_config_file(&meta_server.base_url(), temp_dir.path()).await?;
@@ -132,13 +150,7 @@ async fn test_upload_file_failed_chunk_upload() -> Result<(), Box<dyn std::error
         when.method(Method::POST).path("/api/files/upload");
         then.status(200)
             .header("content-type", "application/json")
-            .body_from_file("tests/file_resp.json");
-    });
-    meta_server.mock(|when, then| {
-        when.method(Method::GET).path("/api/servers");
-        then.status(200)
-            .header("Content-Type", "application/json")
-            .json_body_obj(&vec![chunk_server_val]);
+            .json_body_obj(&file_upload(vec![chunk_server_val]));
     });
     chunk_server.mock(|when, then| {
         when.method(Method::POST).path("/api/upload");
@@ -167,19 +179,65 @@ async fn test_upload_file() -> Result<(), Box<dyn std::error::Error>> {
     file.write_all(b"Test file content").await?;
 
     let chunk_server = MockServer::start();
+    let replica_server = MockServer::start();
+    let servers = vec![
+        ChunkServer::new(Uuid::new_v4(), chunk_server.base_url()),
+        ChunkServer::new(Uuid::new_v4(), replica_server.base_url()),
+    ];
     let meta_server = MockServer::start();
     meta_server.mock(|when, then| {
         when.method(Method::POST).path("/api/files/upload");
         then.status(200)
             .header("content-type", "application/json")
-            .body_from_file("tests/file_resp.json");
+            .json_body_obj(&file_upload(servers));
     });
-    let chunk_server_val = ChunkServer::new(Uuid::new_v4(), chunk_server.base_url());
-    meta_server.mock(|when, then| {
-        when.method(Method::GET).path("/api/servers");
+    let upload = chunk_server.mock(|when, then| {
+        // CRC32 checksum of the file content, and the metadata recorded next to the chunk
+        when.method(Method::POST)
+            .path("/api/upload")
+            .body_contains("4f7271d5")
+            .body_contains("\"path\":\"/dir\"");
+        then.status(200);
+    });
+    let replicate = chunk_server.mock(|when, then| {
+        when.method(Method::POST)
+            .path("/api/replicate")
+            .header("x-ccfs-chunk-id", "9413f6e4-4e8d-4e14-ac9c-be83644a7eb4")
+            .header("x-ccfs-server-url", &replica_server.base_url());
+        then.status(200);
+    });
+
+    let config_file_path = create_config_file(&meta_server.base_url(), temp_dir.path()).await?;
+    Command::cargo_bin("cli")?
+        .arg("-c")
+        .arg(&config_file_path)
+        .arg("upload")
+        .arg(&file_path)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Completed file upload"));
+    upload.assert();
+    replicate.assert();
+    Ok(())
+}
+
+#[actix_rt::test]
+async fn test_upload_file_with_replicas() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = tempdir_in("./")?;
+    let file_path = temp_dir.path().join("test.txt");
+    let mut file = File::create(&file_path).await?;
+    file.write_all(b"Test file content").await?;
+
+    let chunk_server = MockServer::start();
+    let servers = vec![ChunkServer::new(Uuid::new_v4(), chunk_server.base_url())];
+    let meta_server = MockServer::start();
+    let create_file = meta_server.mock(|when, then| {
+        when.method(Method::POST)
+            .path("/api/files/upload")
+            .body_contains("\"replicas\":2");
         then.status(200)
-            .header("Content-Type", "application/json")
-            .json_body_obj(&vec![chunk_server_val]);
+            .header("content-type", "application/json")
+            .json_body_obj(&file_upload(servers));
     });
     chunk_server.mock(|when, then| {
         when.method(Method::POST).path("/api/upload");
@@ -192,9 +250,12 @@ async fn test_upload_file() -> Result<(), Box<dyn std::error::Error>> {
         .arg(&config_file_path)
         .arg("upload")
         .arg(&file_path)
+        .arg("--replicas")
+        .arg("2")
         .assert()
         .success()
         .stdout(predicate::str::contains("Completed file upload"));
+    create_file.assert();
     Ok(())
 }
 
@@ -223,3 +284,78 @@ async fn test_upload_dir() -> Result<(), Box<dyn std::error::Error>> {
         .stdout(predicate::str::contains("Completed directory upload"));
     Ok(())
 }
+
+#[actix_rt::test]
+async fn test_upload_resume() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = tempdir_in("./")?;
+    let file_path = temp_dir.path().join("test.txt");
+    let mut file = File::create(&file_path).await?;
+    file.write_all(b"Test file content").await?;
+
+    let failing_server = MockServer::start();
+    failing_server.mock(|when, then| {
+        when.method(Method::POST).path("/api/upload");
+        then.status(500);
+    });
+    let chunk_server = MockServer::start();
+    let upload = chunk_server.mock(|when, then| {
+        when.method(Method::POST).path("/api/upload");
+        then.status(200);
+    });
+    let failing_upload = file_upload(vec![ChunkServer::new(
+        Uuid::new_v4(),
+        failing_server.base_url(),
+    )]);
+    let file_id = match &failing_upload.file.file_info {
+        FileInfo::File { id, .. } => *id,
+        _ => unreachable!(),
+    };
+    let meta_server = MockServer::start();
+    let create = meta_server.mock(|when, then| {
+        when.method(Method::POST).path("/api/files/upload");
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body_obj(&failing_upload);
+    });
+    let status = UploadStatus {
+        upload: file_upload(vec![ChunkServer::new(
+            Uuid::new_v4(),
+            chunk_server.base_url(),
+        )]),
+        completed: Vec::new(),
+    };
+    let get_status = meta_server.mock(|when, then| {
+        when.method(Method::GET)
+            .path(format!("/api/files/{}/upload", file_id));
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body_obj(&status);
+    });
+
+    let config_file_path = create_config_file(&meta_server.base_url(), temp_dir.path()).await?;
+    Command::cargo_bin("cli")?
+        .arg("-c")
+        .arg(&config_file_path)
+        .arg("upload")
+        .arg(&file_path)
+        .assert()
+        .failure();
+    let journal_dir = temp_dir.path().join("journal");
+    assert_eq!(journal_dir.read_dir()?.count(), 1);
+
+    Command::cargo_bin("cli")?
+        .arg("-c")
+        .arg(&config_file_path)
+        .arg("upload")
+        .arg("--resume")
+        .arg(&file_path)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Completed file upload"));
+    // the file created by the interrupted upload is reused
+    create.assert();
+    get_status.assert();
+    upload.assert();
+    assert_eq!(journal_dir.read_dir()?.count(), 0);
+    Ok(())
+}
@@ -9,9 +9,13 @@ pub async fn create_config_file(
 ) -> Result<PathBuf, Box<dyn std::error::Error>> {
     let config_file_path = temp_dir.join("config.yml");
     let mut config_file = File::create(&config_file_path).await?;
-    config_file
-        .write_all(format!("metadata-server-url: {}", meta_url).as_bytes())
-        .await?;
+    let journal_dir = temp_dir.join("journal");
+    let config = format!(
+        "metadata-server-url: {}\nupload-journal-dir: {}",
+        meta_url,
+        journal_dir.display()
+    );
+    config_file.write_all(config.as_bytes()).await?;
     Ok(config_file_path)
 }
 
@@ -1,4 +1,6 @@
-use actix_web::error::{ErrorBadRequest, ErrorInternalServerError};
+use actix_web::error::ErrorServiceUnavailable;
+use actix_web::error::{ErrorBadRequest, ErrorConflict, ErrorInternalServerError, ErrorNotFound};
+use actix_web::http::header::LOCATION;
 use actix_web::{HttpResponse, ResponseError};
 use ccfs_commons::errors::CCFSResponseError;
 use snafu::Snafu;
@@ -14,11 +16,64 @@ pub enum Error {
         source: std::boxed::Box<bincode::ErrorKind>,
     },
 
+    #[snafu(display("Unable to deserialize raft state: {}", source))]
+    DeserializeRaftState {
+        source: std::boxed::Box<bincode::ErrorKind>,
+    },
+
     #[snafu(display("Not found"))]
     NotFound,
 
     #[snafu(display("Missing required query param"))]
     MissingParam,
+
+    #[snafu(display("'{}' is a directory, it can only be removed recursively", path))]
+    IsDirectory { path: String },
+
+    #[snafu(display("'{}' already exists, and it isn't a {}", name, kind))]
+    KindConflict { name: String, kind: &'static str },
+
+    #[snafu(display("Number of replicas must be greater than 0"))]
+    InvalidReplicas,
+
+    #[snafu(display("This node is not the cluster leader"))]
+    NotLeader { location: Option<String> },
+
+    #[snafu(display("Operation wasn't replicated to the majority of the cluster in time"))]
+    CommitTimeout,
+
+    #[snafu(display("Unable to parse to json: {}", source))]
+    ParseJson {
+        source: actix_web::client::JsonPayloadError,
+    },
+
+    #[snafu(display("File {} isn't open for writing", id))]
+    NotOpen { id: uuid::Uuid },
+
+    #[snafu(display("File {} isn't being uploaded", id))]
+    NotUploading { id: uuid::Uuid },
+
+    #[snafu(display("Size {} doesn't match the {} chunks of the file", size, num_of_chunks))]
+    SizeMismatch { size: u64, num_of_chunks: usize },
+
+    #[snafu(display("Version '{}' of the file doesn't exist", version))]
+    InvalidVersion { version: String },
+
+    #[snafu(display("Invalid If-Match header '{}', it should be a file version", value))]
+    InvalidPrecondition { value: String },
+
+    #[snafu(display(
+        "Expected version {} of the file, but it's at version {}",
+        expected,
+        actual
+    ))]
+    VersionConflict { expected: usize, actual: usize },
+
+    #[snafu(display("The metadata of this node isn't complete yet"))]
+    MetadataNotReady,
+
+    #[snafu(display("Snapshot '{}' already exists", path.display()))]
+    SnapshotExists { path: std::path::PathBuf },
 }
 
 impl<'a> ResponseError for Error {
@@ -27,8 +82,28 @@ impl<'a> ResponseError for Error {
         let display = format!("{}", self);
         match self {
             Base { source } => source.error_response(),
-            Deserialize { .. } | MissingParam { .. } => ErrorBadRequest(display).into(),
-            NotFound { .. } => ErrorInternalServerError(display).into(),
+            Deserialize { .. }
+            | MissingParam { .. }
+            | IsDirectory { .. }
+            | InvalidReplicas
+            | NotOpen { .. }
+            | NotUploading { .. }
+            | InvalidPrecondition { .. }
+            | SizeMismatch { .. } => ErrorBadRequest(display).into(),
+            InvalidVersion { .. } => ErrorNotFound(display).into(),
+            VersionConflict { .. } | KindConflict { .. } => ErrorConflict(display).into(),
+            NotFound { .. }
+            | DeserializeRaftState { .. }
+            | ParseJson { .. }
+            | SnapshotExists { .. } => ErrorInternalServerError(display).into(),
+            NotLeader {
+                location: Some(location),
+            } => HttpResponse::TemporaryRedirect()
+                .insert_header((LOCATION, location.as_str()))
+                .finish(),
+            NotLeader { location: None } | CommitTimeout | MetadataNotReady => {
+                ErrorServiceUnavailable(display).into()
+            }
         }
     }
 }
@@ -0,0 +1,67 @@
+//! Reconciliation of the chunks map against the chunk inventories reported by the chunk servers
+
+use crate::{Chunks, Files};
+use ccfs_commons::{Chunk, ChunkReport, ChunkReportResult};
+use std::collections::HashSet;
+
+/// Finds the registered replicas which are missing on the reporting server, and
+/// the stored ones which aren't registered. The latter are registered only when
+/// they belong to a known file and match the checksum of its other replicas
+pub fn reconcile_chunks(report: &ChunkReport, files: &Files, chunks: &Chunks) -> ChunkReportResult {
+    let registered_replica = |chunk_id| {
+        chunks
+            .get(chunk_id)?
+            .iter()
+            .find(|c| c.server_id == report.server_id)
+    };
+    let unregistered = match report.full {
+        true => {
+            let stored = report.stored.iter().map(|c| c.id).collect::<HashSet<_>>();
+            chunks
+                .values()
+                .flatten()
+                .filter(|c| c.server_id == report.server_id && !stored.contains(&c.id))
+                .cloned()
+                .collect()
+        }
+        false => report
+            .removed
+            .iter()
+            .filter_map(|c| registered_replica(&c.id))
+            .cloned()
+            .collect(),
+    };
+
+    let mut result = ChunkReportResult {
+        unregistered,
+        ..Default::default()
+    };
+    let stored = report
+        .stored
+        .iter()
+        .filter(|c| c.server_id == report.server_id);
+    for chunk in stored {
+        if registered_replica(&chunk.id).is_some() {
+            continue;
+        }
+        match belongs_to_file(chunk, files) && matches_replicas(chunk, chunks) {
+            true => result.registered.push(*chunk),
+            false => result.unknown.push(*chunk),
+        }
+    }
+    result
+}
+
+fn belongs_to_file(chunk: &Chunk, files: &Files) -> bool {
+    files.get(&chunk.file_id).map_or(false, |(_, file)| {
+        file.chunks().map_or(false, |ids| ids.contains(&chunk.id))
+    })
+}
+
+fn matches_replicas(chunk: &Chunk, chunks: &Chunks) -> bool {
+    chunks.get(&chunk.id).map_or(true, |replicas| {
+        replicas.iter().all(|c| {
+            c.checksum.is_none() || chunk.checksum.is_none() || c.checksum == chunk.checksum
+        })
+    })
+}
@@ -0,0 +1,56 @@
+use crate::{DeletionQueue, ServersMap};
+use actix_web::client::Client;
+use actix_web::http::StatusCode;
+use ccfs_commons::result::CCFSResult;
+use futures::future::{join_all, FutureExt, LocalBoxFuture};
+use tokio::time::{sleep, Duration};
+
+pub async fn start_deletion_job(sleep_interval: u64, queue: DeletionQueue, servers: ServersMap) {
+    loop {
+        sleep(Duration::from_secs(sleep_interval)).await;
+        match delete_chunks(queue.clone(), servers.clone()).await {
+            Ok(0) => {}
+            Ok(count) => println!("Successfully deleted {} chunks", count),
+            // TODO: replace with logger
+            Err(err) => println!("Error while deleting chunks: {:?}", err),
+        }
+    }
+}
+
+/// Sends the delete requests for the queued chunks to the chunk servers.
+///
+/// Chunks which are stored on currently inactive servers, or whose delete
+/// request failed, stay in the queue and are retried on the next run
+fn delete_chunks(
+    queue: DeletionQueue,
+    servers_map: ServersMap,
+) -> LocalBoxFuture<'static, CCFSResult<usize>> {
+    let c = Client::new();
+    async move {
+        let pending = queue.read().await.clone();
+        let servers = servers_map.read().await.clone();
+
+        let requests = pending.iter().filter_map(|chunk| {
+            let server = servers.get(&chunk.server_id).filter(|s| s.is_active())?;
+            let url = format!("{}/api/chunks/{}", server.address, chunk.chunk_name());
+            Some(c.delete(url).send().map(move |resp| (chunk, resp)))
+        });
+        let deleted = join_all(requests)
+            .await
+            .into_iter()
+            .filter_map(|(chunk, resp)| match resp {
+                Ok(r) if r.status().is_success() || r.status() == StatusCode::NOT_FOUND => {
+                    Some(chunk)
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        let mut queue = queue.write().await;
+        for chunk in deleted.iter() {
+            queue.remove(chunk);
+        }
+        Ok(deleted.len())
+    }
+    .boxed_local()
+}
@@ -1,2 +1,5 @@
+pub mod deletion;
+pub mod raft;
 pub mod replication;
 pub mod snapshot;
+pub mod uploads;
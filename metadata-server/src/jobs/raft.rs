@@ -0,0 +1,35 @@
+use crate::raft::lock_and_apply;
+use crate::{ChunksMap, DeletionQueue, FileMetadataTree, FilesMap, Raft};
+use tokio::time::{sleep, Duration};
+
+/// Drives the election and heartbeat timers of the node
+pub async fn start_raft_job(tick_interval: Duration, raft: Raft) {
+    loop {
+        sleep(tick_interval).await;
+        if let Err(err) = raft.lock().await.tick().await {
+            // TODO: replace with logger
+            println!("Error while running raft timers: {:?}", err);
+        }
+    }
+}
+
+/// Applies the entries to the metadata as soon as they are committed
+pub async fn start_apply_job(
+    raft: Raft,
+    tree: FileMetadataTree,
+    files: FilesMap,
+    chunks: ChunksMap,
+    deletion_queue: DeletionQueue,
+) {
+    let mut commits = raft.lock().await.subscribe();
+    while commits.changed().await.is_ok() {
+        {
+            let raft = raft.lock().await;
+            if raft.last_applied() >= raft.commit_index() && !raft.has_pending_snapshot() {
+                continue;
+            }
+        }
+        let removed_chunks = lock_and_apply(&raft, &tree, &files, &chunks).await;
+        deletion_queue.write().await.extend(removed_chunks);
+    }
+}
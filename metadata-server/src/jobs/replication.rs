@@ -1,35 +1,106 @@
-use crate::FileMetadataTree;
-use crate::{ChunksMap, ServersMap};
+use crate::operations::Operation;
+use crate::placement::{score, spread_across_zones};
+use crate::raft::{commit_and_apply, lock_writes};
+use crate::server_config::ServerConfig;
+use crate::{ChunksMap, DeletionQueue, FileMetadataTree, FilesMap, Raft};
+use crate::{ReplicationStatus, ServersMap};
 use actix_web::client::Client;
 use ccfs_commons::result::CCFSResult;
 use ccfs_commons::{Chunk, ChunkServer, FileInfo, FileMetadata};
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
 use futures::future::{join_all, FutureExt, LocalBoxFuture};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
 use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
 use tokio::time::{sleep, Duration};
 use uuid::Uuid;
 
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ChunkReplicationStatus {
+    pub chunk_id: Uuid,
+    pub file_id: Uuid,
+    /// Path of the file which the chunk belongs to
+    pub path: String,
+    pub live_replicas: usize,
+    pub required_replicas: usize,
+}
+
+/// Chunks which don't have the required number of replicas on the active servers
+#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ReplicationReport {
+    /// Chunks with some live replicas, ordered from the most endangered one
+    pub under_replicated: Vec<ChunkReplicationStatus>,
+    /// Chunks without live replicas, which are stored on the servers that may still come back
+    pub unavailable: Vec<ChunkReplicationStatus>,
+    /// Chunks whose replicas were all stored on dead servers (or which don't have any replicas)
+    pub lost: Vec<ChunkReplicationStatus>,
+    /// Chunks with more live replicas than required, e.g. when a server came back
+    /// after its chunks were already replicated to the other servers
+    pub over_replicated: Vec<ChunkReplicationStatus>,
+}
+
+#[allow(clippy::too_many_arguments)]
 pub async fn start_replication_job(
-    sleep_interval: u64,
+    config: Arc<ServerConfig>,
     tree: FileMetadataTree,
+    files: FilesMap,
     chunks: ChunksMap,
     servers: ServersMap,
+    raft: Raft,
+    deletion_queue: DeletionQueue,
+    status: ReplicationStatus,
 ) {
+    let started = Utc::now();
     loop {
-        sleep(Duration::from_secs(sleep_interval)).await;
-        if let Err(err) = replicate_files(tree.clone(), chunks.clone(), servers.clone(), 3).await {
+        sleep(Duration::from_secs(config.replication_interval)).await;
+        if let Err(err) = replicate_files(
+            config.clone(),
+            tree.clone(),
+            chunks.clone(),
+            servers.clone(),
+            raft.clone(),
+            status.clone(),
+            started,
+        )
+        .await
+        {
             // TODO: replace with logger
             println!("Error while creating replicas: {:?}", err);
         } else {
             println!("Successfully created replicas");
         }
+        match remove_excess_replicas(
+            tree.clone(),
+            files.clone(),
+            chunks.clone(),
+            servers.clone(),
+            raft.clone(),
+            deletion_queue.clone(),
+            status.clone(),
+        )
+        .await
+        {
+            Ok(0) => {}
+            Ok(count) => println!("Scheduled deletion of {} excess replicas", count),
+            // TODO: replace with logger
+            Err(err) => println!("Error while removing excess replicas: {:?}", err),
+        }
     }
 }
 
-fn replicate_files(
+/// Checks the replication of the chunks and updates the report. Only the cluster leader
+/// replicates the under-replicated chunks, so that they're copied once.
+///
+/// `started` is the time when the servers map started to be filled by the pings
+pub fn replicate_files(
+    config: Arc<ServerConfig>,
     tree: FileMetadataTree,
     chunks_map: ChunksMap,
     servers_map: ServersMap,
-    required_replicas: usize,
+    raft: Raft,
+    status: ReplicationStatus,
+    started: DateTime<Utc>,
 ) -> LocalBoxFuture<'static, CCFSResult<()>> {
     let c = Client::new();
     async move {
@@ -37,6 +108,22 @@ fn replicate_files(
         let chunks = chunks_map.read().await.clone();
         let servers = servers_map.read().await.clone();
 
+        let report = check_replication(
+            &files_tree,
+            &chunks,
+            &servers,
+            config.replication_factor,
+            ChronoDuration::seconds(config.dead_server_timeout as i64),
+            started,
+        );
+        if !report.lost.is_empty() {
+            // TODO: replace with logger
+            println!("{} chunks are lost", report.lost.len());
+        }
+        if !raft.lock().await.is_leader() {
+            *status.write().await = report;
+            return Ok(());
+        }
         let active_servers = servers
             .iter()
             .filter_map(|(id, s)| match s.is_active() {
@@ -44,56 +131,197 @@ fn replicate_files(
                 false => None,
             })
             .collect::<HashSet<_>>();
-        let files = files_tree
-            .dfs_iter()
-            .filter(|f| matches!(f.file_info, FileInfo::File { .. }));
-        let futures = files
-            .map(|f| replicate_file(&c, f, &chunks, &active_servers, &servers, required_replicas));
+        // the requests are sent in the report order, so the most endangered chunks go first
+        let futures = report
+            .under_replicated
+            .iter()
+            .map(|chunk| replicate_chunk(&c, chunk, &chunks, &active_servers, &servers));
         join_all(futures).await;
+        *status.write().await = report;
         Ok(())
     }
     .boxed_local()
 }
 
-async fn replicate_file(
-    c: &Client,
-    file: &FileMetadata,
+/// Finds the chunks of the completed files which don't have enough live replicas.
+///
+/// A server is considered dead when it didn't ping for longer than `dead_server_timeout`.
+/// The servers which didn't ping at all since `started` (e.g. after a restart of the
+/// metadata server) are considered dead once the timeout passes from then
+pub fn check_replication(
+    tree: &FileMetadata,
     chunks: &HashMap<Uuid, HashSet<Chunk>>,
-    active_servers: &HashSet<&Uuid>,
     servers: &HashMap<Uuid, ChunkServer>,
-    required_replicas: usize,
-) -> CCFSResult<()> {
-    if let FileInfo::File {
-        chunks: ref file_chunks,
-        id,
-        ..
-    } = &file.file_info
-    {
-        for chunk in file_chunks.iter() {
-            if let Some(replicas) = chunks.get(chunk) {
-                let replica_servers = replicas
-                    .iter()
-                    .filter(|c| active_servers.contains(&c.server_id))
-                    .map(|c| &c.server_id)
-                    .collect::<HashSet<_>>();
-                if !replica_servers.is_empty() && replica_servers.len() < required_replicas {
-                    let target_server_candidates = active_servers - &replica_servers;
-                    if !target_server_candidates.is_empty() {
-                        send_replication_requests(
-                            c,
-                            servers,
-                            &replica_servers,
-                            &target_server_candidates,
-                            &id,
-                            chunk,
-                            required_replicas,
-                        )
-                        .await?;
+    replication_factor: usize,
+    dead_server_timeout: ChronoDuration,
+    started: DateTime<Utc>,
+) -> ReplicationReport {
+    let now = Utc::now();
+    let is_dead = |server_id: &Uuid| match servers.get(server_id) {
+        Some(s) => now.signed_duration_since(s.latest_ping_time) > dead_server_timeout,
+        None => now.signed_duration_since(started) > dead_server_timeout,
+    };
+    let mut report = ReplicationReport::default();
+    for (node, parent_dir) in tree.bfs_iter().zip(tree.bfs_paths_iter()) {
+        // the kept previous versions of the files are replicated as well
+        let versions = match &node.file_info {
+            FileInfo::File { versions, .. } => versions.as_slice(),
+            FileInfo::Directory { .. } => &[],
+        };
+        for file in std::iter::once(node).chain(versions) {
+            if let FileInfo::File {
+                id,
+                chunks: file_chunks,
+                replicas,
+                ..
+            } = &file.file_info
+            {
+                let required_replicas = replicas.unwrap_or(replication_factor);
+                for chunk_id in file_chunks.iter() {
+                    let chunk_replicas = chunks.get(chunk_id).cloned().unwrap_or_default();
+                    let live_replicas = chunk_replicas
+                        .iter()
+                        .filter(|c| servers.get(&c.server_id).map_or(false, |s| s.is_active()))
+                        .count();
+                    let status = ChunkReplicationStatus {
+                        chunk_id: *chunk_id,
+                        file_id: *id,
+                        path: parent_dir.join(&file.name).display().to_string(),
+                        live_replicas,
+                        required_replicas,
+                    };
+                    if live_replicas == 0 {
+                        match chunk_replicas.iter().all(|c| is_dead(&c.server_id)) {
+                            true => report.lost.push(status),
+                            false => report.unavailable.push(status),
+                        }
+                    } else if live_replicas < required_replicas {
+                        report.under_replicated.push(status);
+                    } else if live_replicas > required_replicas {
+                        report.over_replicated.push(status);
                     }
                 }
             }
         }
     }
+    report.under_replicated.sort_by_key(|s| {
+        (
+            s.live_replicas,
+            std::cmp::Reverse(s.required_replicas - s.live_replicas),
+        )
+    });
+    report
+}
+
+/// Unregisters the excess replicas of the over-replicated chunks from the latest report,
+/// and schedules their deletion from the chunk servers. Only the cluster leader removes them
+fn remove_excess_replicas(
+    tree: FileMetadataTree,
+    files: FilesMap,
+    chunks: ChunksMap,
+    servers: ServersMap,
+    raft: Raft,
+    deletion_queue: DeletionQueue,
+    status: ReplicationStatus,
+) -> LocalBoxFuture<'static, CCFSResult<usize>> {
+    async move {
+        if !raft.lock().await.is_leader() {
+            return Ok(0);
+        }
+        let report = status.read().await.clone();
+        if report.over_replicated.is_empty() {
+            return Ok(0);
+        }
+        let writes = lock_writes(&raft).await;
+        let replicas = find_excess_replicas(&report, &*chunks.read().await, &*servers.read().await);
+        if replicas.is_empty() {
+            return Ok(0);
+        }
+        let operation = Operation::RemoveReplicas { replicas };
+        let removed_chunks = commit_and_apply(&raft, operation, &tree, &files, &chunks).await?;
+        drop(writes);
+        let count = removed_chunks.len();
+        deletion_queue.write().await.extend(removed_chunks);
+        Ok(count)
+    }
+    .boxed_local()
+}
+
+/// Picks the live replicas of the over-replicated chunks which should be removed.
+///
+/// The kept replicas are picked the same way as the new ones are placed, the servers
+/// from distinct zones first, and then by their free space and load
+pub fn find_excess_replicas(
+    report: &ReplicationReport,
+    chunks: &HashMap<Uuid, HashSet<Chunk>>,
+    servers: &HashMap<Uuid, ChunkServer>,
+) -> Vec<Chunk> {
+    let mut excess = Vec::new();
+    for status in report.over_replicated.iter() {
+        let mut live = match chunks.get(&status.chunk_id) {
+            Some(replicas) => replicas
+                .iter()
+                .filter_map(|c| Some((c, servers.get(&c.server_id).filter(|s| s.is_active())?)))
+                .collect::<Vec<_>>(),
+            None => continue,
+        };
+        live.sort_by(|(_, a), (_, b)| {
+            let a = score(a.available_space, a.pending_requests);
+            let b = score(b.available_space, b.pending_requests);
+            b.partial_cmp(&a).unwrap_or(Ordering::Equal)
+        });
+        let ranked = live.iter().map(|(_, s)| *s).collect();
+        let kept = spread_across_zones(ranked, &HashSet::new())
+            .into_iter()
+            .take(status.required_replicas)
+            .map(|s| s.id)
+            .collect::<HashSet<_>>();
+        excess.extend(
+            live.iter()
+                .filter(|(c, _)| !kept.contains(&c.server_id))
+                .map(|(c, _)| **c),
+        );
+    }
+    excess
+}
+
+async fn replicate_chunk(
+    c: &Client,
+    status: &ChunkReplicationStatus,
+    chunks: &HashMap<Uuid, HashSet<Chunk>>,
+    active_servers: &HashSet<&Uuid>,
+    servers: &HashMap<Uuid, ChunkServer>,
+) -> CCFSResult<()> {
+    let replica_servers = match chunks.get(&status.chunk_id) {
+        Some(replicas) => replicas
+            .iter()
+            .filter(|c| active_servers.contains(&c.server_id))
+            .map(|c| &c.server_id)
+            .collect::<HashSet<_>>(),
+        None => return Ok(()),
+    };
+    let target_server_candidates = (active_servers - &replica_servers)
+        .iter()
+        .filter_map(|id| servers.get(id))
+        .collect::<Vec<_>>();
+    if !replica_servers.is_empty() && !target_server_candidates.is_empty() {
+        // the servers from the zones which don't hold a replica yet are tried first
+        let used_zones = replica_servers
+            .iter()
+            .filter_map(|id| servers.get(id)?.zone.as_deref())
+            .collect();
+        let target_servers = spread_across_zones(target_server_candidates, &used_zones);
+        send_replication_requests(
+            c,
+            servers,
+            &replica_servers,
+            &target_servers,
+            &status.file_id,
+            &status.chunk_id,
+            status.required_replicas,
+        )
+        .await?;
+    }
     Ok(())
 }
 
@@ -101,7 +329,7 @@ async fn send_replication_requests(
     c: &Client,
     servers_map: &HashMap<Uuid, ChunkServer>,
     replica_servers: &HashSet<&Uuid>,
-    target_servers: &HashSet<&Uuid>,
+    target_servers: &[&ChunkServer],
     file_id: &Uuid,
     chunk_id: &Uuid,
     required_replicas: usize,
@@ -111,9 +339,8 @@ async fn send_replication_requests(
     let mut target_iter = target_servers.iter().peekable();
     while remaining > 0 && target_iter.peek().is_some() {
         let requests = (0..remaining).filter_map(|_| {
-            let s_id = target_iter.next()?;
+            let target_server = &target_iter.next()?.address;
             let from_server = &servers_map.get(active_iter.next()?)?.address;
-            let target_server = &servers_map.get(s_id)?.address;
             Some(
                 c.post(format!("{}/api/replicate", &from_server))
                     .insert_header(("x-ccfs-chunk-id", chunk_id.to_string()))
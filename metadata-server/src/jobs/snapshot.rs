@@ -1,14 +1,63 @@
+use crate::errors::Deserialize as DeserializeSnapshot;
 use crate::server_config::ServerConfig;
-use crate::FileMetadataTree;
+use crate::{Chunks, ChunksMap, FileMetadataTree, Files, FilesMap, Raft};
 use ccfs_commons::{errors::Error as BaseError, result::CCFSResult};
+use ccfs_commons::{FileInfo, FileMetadata};
 use futures::future::{FutureExt, LocalBoxFuture};
-use std::path::PathBuf;
+use serde::{Deserialize, Serialize};
+use snafu::ResultExt;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tempfile::tempdir_in;
-use tokio::fs::{rename, write};
+use tokio::fs::{read, rename, File};
+use tokio::io::AsyncWriteExt;
 use tokio::time::{sleep, Duration};
 
-pub async fn start_snapshot_job(config: Arc<ServerConfig>, metadata_tree: FileMetadataTree) {
+/// A consistent copy of the metadata server state which is persisted to disk
+#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Snapshot {
+    pub tree: FileMetadata,
+    pub files: Files,
+    pub chunks: Chunks,
+    /// Sequence number and term of the last operation log entry included in the snapshot
+    pub last_op_seq: u64,
+    pub last_op_term: u64,
+}
+
+impl Snapshot {
+    /// Creates a snapshot from a tree only, the files map is rebuilt
+    /// from the completed files in the tree and their previous versions
+    pub fn from_tree(tree: FileMetadata) -> Self {
+        let mut files = HashMap::new();
+        for (file, parent_dir) in tree.bfs_iter().zip(tree.bfs_paths_iter()) {
+            if let FileInfo::File { id, versions, .. } = &file.file_info {
+                let path = parent_dir.display().to_string();
+                for version in versions {
+                    if let FileInfo::File { id, .. } = &version.file_info {
+                        files.insert(*id, (path.clone(), version.clone()));
+                    }
+                }
+                files.insert(*id, (path, file.clone()));
+            }
+        }
+        Self {
+            tree,
+            files,
+            chunks: HashMap::new(),
+            last_op_seq: 0,
+            last_op_term: 0,
+        }
+    }
+}
+
+pub async fn start_snapshot_job(
+    config: Arc<ServerConfig>,
+    metadata_tree: FileMetadataTree,
+    files: FilesMap,
+    chunks: ChunksMap,
+    raft: Raft,
+) {
     let temp_dir = tempdir_in(&config.snapshot_dir_path).expect("Couldn't create temp dir");
     let temp_path = Arc::new(temp_dir.path().join("tmp_snapshot"));
     let snapshot_path = Arc::new(config.snapshot_path());
@@ -18,6 +67,9 @@ pub async fn start_snapshot_job(config: Arc<ServerConfig>, metadata_tree: FileMe
             snapshot_path.clone(),
             temp_path.clone(),
             metadata_tree.clone(),
+            files.clone(),
+            chunks.clone(),
+            raft.clone(),
         )
         .await
         {
@@ -27,29 +79,102 @@ pub async fn start_snapshot_job(config: Arc<ServerConfig>, metadata_tree: FileMe
     }
 }
 
-fn create_snapshot(
+pub fn create_snapshot(
     snapshot_path: Arc<PathBuf>,
     temp_path: Arc<PathBuf>,
     tree: FileMetadataTree,
+    files: FilesMap,
+    chunks: ChunksMap,
+    raft: Raft,
 ) -> LocalBoxFuture<'static, CCFSResult<()>> {
     async move {
-        write(
-            &*temp_path,
-            &bincode::serialize(&*tree.read().await).unwrap(),
-        )
+        let snapshot = {
+            // the committed entries are applied while the tree is write locked,
+            // so the state and the last applied entry are consistent while it's read locked
+            let tree = tree.read().await;
+            let files = files.read().await;
+            let chunks = chunks.read().await;
+            let raft = raft.lock().await;
+            if raft.has_pending_snapshot() {
+                // the snapshot received from the leader is already persisted
+                return Ok(());
+            }
+            let last_op_seq = raft.last_applied();
+            Snapshot {
+                tree: tree.clone(),
+                files: files.clone(),
+                chunks: chunks.clone(),
+                last_op_seq,
+                last_op_term: raft.log().term_at(last_op_seq).unwrap_or_default(),
+            }
+        };
+        write_snapshot(&snapshot_path, &temp_path, &snapshot).await?;
+        raft.lock().await.compact_log(snapshot.last_op_seq).await?;
+        Ok(())
+    }
+    .boxed_local()
+}
+
+/// Atomically replaces the snapshot at the path, by writing it to the temp path first.
+/// Both the file and the directory entry are synced, since the log entries included
+/// in the snapshot are removed afterwards
+pub async fn write_snapshot(path: &Path, temp_path: &Path, snapshot: &Snapshot) -> CCFSResult<()> {
+    let mut temp = File::create(temp_path)
+        .await
+        .map_err(|source| BaseError::Create {
+            path: temp_path.into(),
+            source,
+        })?;
+    temp.write_all(&bincode::serialize(snapshot).unwrap())
         .await
         .map_err(|source| BaseError::Write {
-            path: temp_path.to_path_buf(),
+            path: temp_path.into(),
             source,
         })?;
-        rename(&*temp_path, &*snapshot_path)
-            .await
-            .map_err(|source| BaseError::Rename {
-                from: temp_path.to_path_buf(),
-                to: snapshot_path.to_path_buf(),
-                source,
-            })?;
-        Ok(())
+    temp.sync_data().await.map_err(|source| BaseError::Write {
+        path: temp_path.into(),
+        source,
+    })?;
+    rename(temp_path, path)
+        .await
+        .map_err(|source| BaseError::Rename {
+            from: temp_path.into(),
+            to: path.into(),
+            source,
+        })?;
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let dir_file = File::open(dir).await.map_err(|source| BaseError::Read {
+        path: dir.into(),
+        source,
+    })?;
+    dir_file
+        .sync_all()
+        .await
+        .map_err(|source| BaseError::Write {
+            path: dir.into(),
+            source,
+        })?;
+    Ok(())
+}
+
+/// Loads the snapshot from the path, or returns an empty one if it doesn't exist yet
+pub async fn load_snapshot(path: &Path) -> CCFSResult<Snapshot> {
+    if !path.exists() {
+        return Ok(Snapshot::default());
+    }
+    let content = read(path).await.map_err(|source| BaseError::Read {
+        path: path.into(),
+        source,
+    })?;
+    match bincode::deserialize(&content) {
+        Ok(snapshot) => Ok(snapshot),
+        Err(_) => {
+            // snapshots created by older versions contain only the metadata tree
+            let tree = bincode::deserialize(&content).context(DeserializeSnapshot)?;
+            Ok(Snapshot::from_tree(tree))
+        }
     }
-    .boxed_local()
 }
@@ -0,0 +1,131 @@
+use crate::operations::{get_upload, Operation};
+use crate::raft::{commit_and_apply, lock_writes};
+use crate::server_config::ServerConfig;
+use crate::{ChunksMap, DeletionQueue, FileMetadataTree, Files, FilesMap, Raft};
+use ccfs_commons::result::CCFSResult;
+use ccfs_commons::FileInfo;
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::time::{sleep, Duration};
+use uuid::Uuid;
+
+/// Lease of an upload in progress, which is renewed whenever the upload makes progress
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Lease {
+    /// Number of the file chunks and the number of the uploaded ones
+    progress: (usize, usize),
+    expires_at: DateTime<Utc>,
+}
+
+/// Leases of the uploads in progress, kept by the cluster leader
+#[derive(Debug, Default)]
+pub struct UploadLeases {
+    leases: HashMap<Uuid, Lease>,
+}
+
+impl UploadLeases {
+    /// Grants the leases to the new uploads, renews the leases of the uploads which made
+    /// progress since the last check, and returns the uploads whose leases expired
+    pub fn check(
+        &mut self,
+        files: &Files,
+        now: DateTime<Utc>,
+        duration: ChronoDuration,
+    ) -> Vec<Uuid> {
+        let mut expired = Vec::new();
+        let mut leases = HashMap::new();
+        for (id, (_, file)) in files.iter() {
+            let progress = match (&file.file_info, get_upload(files, id)) {
+                (
+                    FileInfo::File {
+                        chunks,
+                        num_of_completed_chunks,
+                        ..
+                    },
+                    Ok(_),
+                ) => (chunks.len(), *num_of_completed_chunks),
+                _ => continue,
+            };
+            let lease = match self.leases.get(id) {
+                Some(lease) if lease.progress == progress => *lease,
+                _ => Lease {
+                    progress,
+                    expires_at: now + duration,
+                },
+            };
+            if lease.expires_at <= now {
+                expired.push(*id);
+            }
+            leases.insert(*id, lease);
+        }
+        // the leases of the completed and removed uploads are dropped
+        self.leases = leases;
+        expired
+    }
+
+    /// Drops all the leases, e.g. when the node isn't the leader anymore
+    pub fn clear(&mut self) {
+        self.leases.clear();
+    }
+}
+
+pub async fn start_upload_expiry_job(
+    config: Arc<ServerConfig>,
+    tree: FileMetadataTree,
+    files: FilesMap,
+    chunks: ChunksMap,
+    raft: Raft,
+    deletion_queue: DeletionQueue,
+) {
+    let mut leases = UploadLeases::default();
+    let duration = ChronoDuration::seconds(config.upload_lease as i64);
+    loop {
+        sleep(Duration::from_secs(config.upload_expiry_interval)).await;
+        match cancel_expired_uploads(
+            &mut leases,
+            duration,
+            &tree,
+            &files,
+            &chunks,
+            &raft,
+            &deletion_queue,
+        )
+        .await
+        {
+            Ok(0) => {}
+            Ok(count) => println!("Canceled {} expired uploads", count),
+            // TODO: replace with logger
+            Err(err) => println!("Error while canceling expired uploads: {:?}", err),
+        }
+    }
+}
+
+/// Cancels the uploads whose leases expired, and schedules the deletion of their chunks.
+/// Only the cluster leader cancels them, the other nodes drop their leases so that
+/// the uploads get the whole lease when the node is elected
+pub async fn cancel_expired_uploads(
+    leases: &mut UploadLeases,
+    duration: ChronoDuration,
+    tree: &FileMetadataTree,
+    files: &FilesMap,
+    chunks: &ChunksMap,
+    raft: &Raft,
+    deletion_queue: &DeletionQueue,
+) -> CCFSResult<usize> {
+    if !raft.lock().await.is_leader() {
+        leases.clear();
+        return Ok(0);
+    }
+    let writes = lock_writes(raft).await;
+    let file_ids = leases.check(&*files.read().await, Utc::now(), duration);
+    if file_ids.is_empty() {
+        return Ok(0);
+    }
+    let count = file_ids.len();
+    let operation = Operation::CancelUploads { file_ids };
+    let removed_chunks = commit_and_apply(raft, operation, tree, files, chunks).await?;
+    drop(writes);
+    deletion_queue.write().await.extend(removed_chunks);
+    Ok(count)
+}
@@ -1,16 +1,36 @@
 pub mod errors;
+pub mod inventory;
 pub mod jobs;
+pub mod operations;
+pub mod oplog;
+pub mod placement;
+pub mod raft;
+pub mod recovery;
 pub mod routes;
 pub mod server_config;
 pub mod ws;
 
 use ccfs_commons::{Chunk, ChunkServer, FileMetadata};
+use jobs::replication::ReplicationReport;
+use raft::RaftNode;
 use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use tokio::sync::{Mutex, RwLock};
 use uuid::Uuid;
 
+/// Number of servers where each chunk is stored, unless it's overridden in the config or per file
+pub const DEFAULT_REPLICATION_FACTOR: usize = 3;
+
+pub type Chunks = HashMap<Uuid, HashSet<Chunk>>;
+pub type Files = HashMap<Uuid, (String, FileMetadata)>;
+
+// When multiple maps are locked at once, the locks are always acquired in the
+// FileMetadataTree -> FilesMap -> ChunksMap -> Raft order to avoid deadlocks.
+// The changes take the writes guard of the Raft node (`raft::lock_writes`) before any of them
 pub type ServersMap = Arc<RwLock<HashMap<Uuid, ChunkServer>>>;
-pub type ChunksMap = Arc<RwLock<HashMap<Uuid, HashSet<Chunk>>>>;
-pub type FilesMap = Arc<RwLock<HashMap<Uuid, (String, FileMetadata)>>>;
+pub type ChunksMap = Arc<RwLock<Chunks>>;
+pub type FilesMap = Arc<RwLock<Files>>;
 pub type FileMetadataTree = Arc<RwLock<FileMetadata>>;
+pub type DeletionQueue = Arc<RwLock<HashSet<Chunk>>>;
+pub type Raft = Arc<Mutex<RaftNode>>;
+pub type ReplicationStatus = Arc<RwLock<ReplicationReport>>;
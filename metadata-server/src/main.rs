@@ -1,34 +1,56 @@
+use actix::Actor;
 use actix_web::{web, App, HttpServer};
-use ccfs_commons::FileMetadata;
-use ccfs_commons::{errors::Error as BaseError, result::CCFSResult};
-use metadata_server::jobs::{replication, snapshot};
+use ccfs_commons::result::CCFSResult;
+use metadata_server::jobs::snapshot::{self, Snapshot};
+use metadata_server::jobs::{deletion, raft as raft_jobs, replication, uploads};
+use metadata_server::oplog::OperationLog;
+use metadata_server::placement::Placement;
+use metadata_server::raft::{apply_committed, RaftMessage, RaftNode};
+use metadata_server::recovery;
 use metadata_server::routes::api::{
-    chunk_server_ping, create_file, get_chunks, get_file, get_server, get_servers, join_cluster,
-    signal_chuck_upload_completed,
+    append_file_chunks, cancel_upload, chunk_server_ping, chunk_server_report, commit_file,
+    create_file, get_chunks, get_file, get_file_upload, get_raft_status, get_referenced_chunks,
+    get_replication_status, get_server, get_servers, join_cluster, remove_file,
+    report_corrupted_chunks, set_file_replicas, set_file_versions, signal_chuck_upload_completed,
 };
-use metadata_server::{errors::*, server_config::ServerConfig};
-use metadata_server::{ChunksMap, FileMetadataTree, FilesMap, ServersMap};
-use snafu::ResultExt;
-use std::collections::HashMap;
+use metadata_server::server_config::ServerConfig;
+use metadata_server::ws::cluster::{self, Cluster};
+use metadata_server::{
+    ChunksMap, DeletionQueue, FileMetadataTree, FilesMap, Raft, ReplicationStatus, ServersMap,
+};
+use std::collections::{HashMap, HashSet};
 use std::env;
-use std::fs::File;
-use std::path::Path;
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
+use tokio::sync::{Mutex, RwLock};
 use tokio::task;
+use tokio::time::Duration;
 
-async fn init_metadata_tree(path: &Path) -> CCFSResult<FileMetadataTree> {
-    let tree = match path.exists() {
-        true => {
-            let file = File::open(path).map_err(|source| BaseError::Read {
-                path: path.into(),
-                source,
-            })?;
-            bincode::deserialize_from(&file).context(Deserialize)?
-        }
-        false => FileMetadata::create_root(),
-    };
-    Ok(Arc::new(RwLock::new(tree)))
+const RAFT_TICK_INTERVAL: Duration = Duration::from_millis(10);
+
+async fn init_metadata_tree(
+    config: &ServerConfig,
+    outbox: UnboundedSender<(u64, RaftMessage)>,
+) -> CCFSResult<(FileMetadataTree, FilesMap, ChunksMap, Raft)> {
+    let Snapshot {
+        mut tree,
+        mut files,
+        mut chunks,
+        last_op_seq,
+        last_op_term,
+    } = snapshot::load_snapshot(&config.snapshot_path()).await?;
+    let oplog = OperationLog::open(&config.oplog_path(), last_op_seq, last_op_term).await?;
+    let raft = Arc::new(Mutex::new(
+        RaftNode::new(config.raft_config(), oplog, outbox).await?,
+    ));
+    // a single node cluster commits its whole log right away
+    apply_committed(&raft, &mut tree, &mut files, &mut chunks).await;
+    Ok((
+        Arc::new(RwLock::new(tree)),
+        Arc::new(RwLock::new(files)),
+        Arc::new(RwLock::new(chunks)),
+        raft,
+    ))
 }
 
 #[actix_web::main]
@@ -36,19 +58,77 @@ async fn main() -> std::io::Result<()> {
     let config_file_path = env::var("CONFIG_PATH").unwrap_or_else(|_| "./ms_config.yml".into());
     let config = Arc::new(ServerConfig::load_config(&config_file_path)?);
 
+    // `--recover <chunk server address>...` rebuilds the lost snapshot from the chunk servers
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    if let Some((flag, chunk_servers)) = args.split_first() {
+        if flag == "--recover" {
+            let snapshot = recovery::recover(&config, chunk_servers)
+                .await
+                .unwrap_or_else(|err| panic!("Couldn't recover metadata: {:?}", err));
+            println!(
+                "Recovered {} files and {} chunks",
+                snapshot.files.len(),
+                snapshot.chunks.len()
+            );
+            return Ok(());
+        }
+    }
+
     let chunk_servers: ServersMap = Arc::new(RwLock::new(HashMap::new()));
-    let chunks: ChunksMap = Arc::new(RwLock::new(HashMap::new()));
-    let files: FilesMap = Arc::new(RwLock::new(HashMap::new()));
-    let tree = init_metadata_tree(&config.snapshot_path())
+    let deletion_queue: DeletionQueue = Arc::new(RwLock::new(HashSet::new()));
+    let replication_status = ReplicationStatus::default();
+    let placement = Placement {
+        policy: config.placement_policy.policy(),
+        replication_factor: config.replication_factor,
+    };
+    let (outbox, outbox_receiver) = unbounded_channel();
+    let (tree, files, chunks, raft) = init_metadata_tree(&config, outbox)
         .await
         .unwrap_or_else(|err| panic!("Couldn't init metadata tree: {:?}", err));
 
-    task::spawn_local(snapshot::start_snapshot_job(config.clone(), tree.clone()));
+    let cluster = Cluster::new(config.node_id, config.peers.clone()).start();
+    task::spawn_local(cluster::forward_messages(
+        config.node_id,
+        outbox_receiver,
+        cluster,
+    ));
+    task::spawn_local(raft_jobs::start_raft_job(RAFT_TICK_INTERVAL, raft.clone()));
+    task::spawn_local(raft_jobs::start_apply_job(
+        raft.clone(),
+        tree.clone(),
+        files.clone(),
+        chunks.clone(),
+        deletion_queue.clone(),
+    ));
+    task::spawn_local(snapshot::start_snapshot_job(
+        config.clone(),
+        tree.clone(),
+        files.clone(),
+        chunks.clone(),
+        raft.clone(),
+    ));
     task::spawn_local(replication::start_replication_job(
-        config.replication_interval,
+        config.clone(),
         tree.clone(),
+        files.clone(),
         chunks.clone(),
         chunk_servers.clone(),
+        raft.clone(),
+        deletion_queue.clone(),
+        replication_status.clone(),
+    ));
+    task::spawn_local(uploads::start_upload_expiry_job(
+        config.clone(),
+        tree.clone(),
+        files.clone(),
+        chunks.clone(),
+        raft.clone(),
+        deletion_queue.clone(),
+    ));
+    task::spawn_local(deletion::start_deletion_job(
+        config.deletion_interval,
+        deletion_queue.clone(),
+        chunk_servers.clone(),
     ));
 
     let address = config.address();
@@ -58,18 +138,34 @@ async fn main() -> std::io::Result<()> {
             .data(chunks.clone())
             .data(files.clone())
             .data(tree.clone())
+            .data(deletion_queue.clone())
+            .data(raft.clone())
+            .data(placement.clone())
+            .data(replication_status.clone())
             .service(
                 web::scope("/api")
                     .service(get_servers)
                     .service(get_server)
                     .service(chunk_server_ping)
                     .service(create_file)
+                    .service(get_file_upload)
+                    .service(cancel_upload)
+                    .service(append_file_chunks)
+                    .service(commit_file)
                     .service(signal_chuck_upload_completed)
                     .service(get_file)
-                    .service(get_chunks),
+                    .service(remove_file)
+                    .service(set_file_replicas)
+                    .service(set_file_versions)
+                    .service(get_chunks)
+                    .service(get_referenced_chunks)
+                    .service(report_corrupted_chunks)
+                    .service(chunk_server_report)
+                    .service(get_replication_status),
             )
             .service(
                 web::scope("/raft")
+                    .service(get_raft_status)
                     .service(web::resource("/ws/").route(web::get().to(join_cluster))),
             )
     })
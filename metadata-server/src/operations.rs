@@ -0,0 +1,525 @@
+//! Metadata mutations, recorded in the operation log before they are applied
+//! so that they can be replayed on top of the latest snapshot after a restart
+
+use crate::errors::*;
+use crate::{Chunks, Files};
+use ccfs_commons::{errors::Error as BaseError, result::CCFSResult};
+use ccfs_commons::{Chunk, FileInfo, FileMetadata, FileStatus, CHUNK_SIZE};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::Path;
+use uuid::Uuid;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum Operation {
+    CreateFile {
+        path: String,
+        file: FileMetadata,
+    },
+    CompleteChunk {
+        chunk: Chunk,
+    },
+    /// Appends the chunks to the open file
+    AppendChunks {
+        file_id: Uuid,
+        chunks: Vec<Uuid>,
+    },
+    /// Closes the open file, its chunks aren't appended anymore
+    CommitFile {
+        file_id: Uuid,
+        size: u64,
+    },
+    RemoveFile {
+        path: String,
+    },
+    /// Drops the uploads which are still in progress, with their uploaded chunks
+    CancelUploads {
+        file_ids: Vec<Uuid>,
+    },
+    SetReplicas {
+        path: String,
+        replicas: Option<usize>,
+    },
+    /// Changes the number of the previous versions kept when the file is overwritten
+    SetKeptVersions {
+        path: String,
+        kept_versions: usize,
+    },
+    /// Unregisters the excess replicas of over-replicated chunks
+    RemoveReplicas {
+        replicas: Vec<Chunk>,
+    },
+    /// Unregisters the replicas which are corrupted or missing on their chunk servers
+    UnregisterReplicas {
+        replicas: Vec<Chunk>,
+    },
+    /// Registers the replicas of the known files, which were reported by the chunk servers
+    RegisterReplicas {
+        replicas: Vec<Chunk>,
+    },
+    /// Appended by a newly elected leader, to commit the entries from the previous terms
+    Noop,
+}
+
+impl Operation {
+    /// Applies the operation, and returns the chunk replicas which should be deleted
+    pub fn apply(
+        self,
+        tree: &mut FileMetadata,
+        files: &mut Files,
+        chunks: &mut Chunks,
+    ) -> CCFSResult<Vec<Chunk>> {
+        match self {
+            Operation::CreateFile { path, file } => {
+                create_file(tree, files, path, file).map(|_| Vec::new())
+            }
+            Operation::CompleteChunk { chunk } => complete_chunk(tree, files, chunks, chunk),
+            Operation::AppendChunks { file_id, chunks } => {
+                append_chunks(files, &file_id, chunks).map(|_| Vec::new())
+            }
+            Operation::CommitFile { file_id, size } => {
+                commit_file(tree, files, chunks, &file_id, size)
+            }
+            Operation::RemoveFile { path } => {
+                remove_file(tree, files, chunks, &path).map(|(_, removed)| removed)
+            }
+            Operation::CancelUploads { file_ids } => Ok(cancel_uploads(files, chunks, &file_ids)),
+            Operation::SetReplicas { path, replicas } => {
+                set_replicas(tree, files, &path, replicas).map(|_| Vec::new())
+            }
+            Operation::SetKeptVersions {
+                path,
+                kept_versions,
+            } => set_kept_versions(tree, files, chunks, &path, kept_versions),
+            Operation::RemoveReplicas { replicas } => Ok(remove_replicas(chunks, replicas)),
+            Operation::UnregisterReplicas { replicas } => {
+                unregister_replicas(chunks, &replicas);
+                // the chunk servers don't store them anymore
+                Ok(Vec::new())
+            }
+            Operation::RegisterReplicas { replicas } => {
+                let mut removed = Vec::new();
+                for replica in replicas {
+                    removed.extend(complete_chunk(tree, files, chunks, replica)?);
+                }
+                Ok(removed)
+            }
+            Operation::Noop => Ok(Vec::new()),
+        }
+    }
+}
+
+/// Adds the directory to the tree, or registers the file upload
+/// (files are added to the tree once all of their chunks are uploaded).
+/// The file which overwrites an existing one gets the next version
+pub fn create_file(
+    tree: &mut FileMetadata,
+    files: &mut Files,
+    path: String,
+    mut file: FileMetadata,
+) -> CCFSResult<()> {
+    let target = tree.traverse_mut(&path)?;
+    check_kind(target, &file)?;
+    let existing = target.children()?.get(&file.name);
+    match &file.file_info {
+        // the existing directory is kept with its content
+        FileInfo::Directory { .. } if existing.is_some() => {}
+        FileInfo::Directory { .. } => {
+            target.children_mut()?.insert(file.name.clone(), file);
+        }
+        FileInfo::File { id, .. } => {
+            let id = *id;
+            if let Some(existing) = existing {
+                file.version = existing.version + 1;
+            }
+            files.insert(id, (path, file));
+        }
+    }
+    Ok(())
+}
+
+/// Checks that the node with the file name in the directory, if there is one, is of the same
+/// kind as the file, so that a file doesn't replace a directory and the other way around
+pub fn check_kind(dir: &FileMetadata, file: &FileMetadata) -> CCFSResult<()> {
+    let is_dir = |node: &FileMetadata| matches!(node.file_info, FileInfo::Directory { .. });
+    match dir.children()?.get(&file.name) {
+        Some(existing) if is_dir(existing) != is_dir(file) => Err(KindConflict {
+            name: file.name.clone(),
+            kind: if is_dir(file) { "directory" } else { "file" },
+        }
+        .build()
+        .into()),
+        _ => Ok(()),
+    }
+}
+
+/// Registers the chunk replica, and adds the file to the tree when all of its chunks
+/// are uploaded. Returns the chunk replicas of the dropped versions of the file
+pub fn complete_chunk(
+    tree: &mut FileMetadata,
+    files: &mut Files,
+    chunks: &mut Chunks,
+    chunk: Chunk,
+) -> CCFSResult<Vec<Chunk>> {
+    let file_id = chunk.file_id;
+    let (_, file) = files.get_mut(&file_id).ok_or_else(|| NotFound.build())?;
+    let chunk_set = chunks.entry(chunk.id).or_insert_with(HashSet::new);
+    let mut completed = false;
+    if chunk_set.is_empty() {
+        if let FileInfo::File {
+            num_of_completed_chunks,
+            chunks: file_chunks,
+            status,
+            ..
+        } = &mut file.file_info
+        {
+            *num_of_completed_chunks += 1;
+            // the open files are completed once they're committed
+            if *num_of_completed_chunks == file_chunks.len() && *status == FileStatus::Started {
+                *status = FileStatus::Completed;
+                completed = true;
+            }
+        }
+    }
+    chunk_set.insert(chunk);
+    match completed {
+        true => insert_completed(tree, files, chunks, &file_id),
+        false => Ok(Vec::new()),
+    }
+}
+
+/// Adds the completed file to the tree, in place of the file it overwrites. The overwritten file
+/// becomes its previous version, and the versions over the `kept_versions` of the overwritten file
+/// are dropped. Returns the chunk replicas of the dropped versions, which should be deleted
+fn insert_completed(
+    tree: &mut FileMetadata,
+    files: &mut Files,
+    chunks: &mut Chunks,
+    file_id: &Uuid,
+) -> CCFSResult<Vec<Chunk>> {
+    let (path, mut file) = files
+        .get(file_id)
+        .cloned()
+        .ok_or_else(|| NotFound.build())?;
+    let target_dir = tree.traverse_mut(&path).map_err(|_| NotFound.build())?;
+    check_kind(target_dir, &file)?;
+    let children = target_dir.children_mut()?;
+    let mut dropped = Vec::new();
+    if let Some(mut previous) = children.remove(&file.name) {
+        if let FileInfo::File {
+            kept_versions,
+            versions,
+            ..
+        } = &mut previous.file_info
+        {
+            let kept_versions = *kept_versions;
+            let older = std::mem::take(versions);
+            file.version = file.version.max(previous.version + 1);
+            let mut history = vec![previous];
+            history.extend(older);
+            dropped = history.split_off(kept_versions.min(history.len()));
+            if let FileInfo::File {
+                kept_versions: kept,
+                versions,
+                ..
+            } = &mut file.file_info
+            {
+                *kept = kept_versions;
+                *versions = history;
+            }
+        }
+    }
+    children.insert(file.name.clone(), file.clone());
+    files.insert(*file_id, (path, file));
+    Ok(drop_versions(files, chunks, &dropped))
+}
+
+/// Drops the file versions, and returns their chunk replicas which should be deleted
+fn drop_versions(files: &mut Files, chunks: &mut Chunks, versions: &[FileMetadata]) -> Vec<Chunk> {
+    let mut removed_chunks = Vec::new();
+    for version in versions {
+        if let FileInfo::File {
+            id, chunks: ids, ..
+        } = &version.file_info
+        {
+            files.remove(id);
+            removed_chunks.extend(ids.iter().filter_map(|id| chunks.remove(id)).flatten());
+        }
+    }
+    removed_chunks
+}
+
+/// Returns the file which is still being written, and the path of its directory
+pub fn get_open_file<'a>(
+    files: &'a Files,
+    file_id: &Uuid,
+) -> CCFSResult<&'a (String, FileMetadata)> {
+    let entry = files.get(file_id).ok_or_else(|| NotFound.build())?;
+    match &entry.1.file_info {
+        FileInfo::File {
+            status: FileStatus::Open,
+            ..
+        } => Ok(entry),
+        _ => Err(NotOpen { id: *file_id }.build().into()),
+    }
+}
+
+/// Checks that the open file can be committed with the size,
+/// which has to match the number of its chunks
+pub fn check_commit(files: &Files, file_id: &Uuid, size: u64) -> CCFSResult<()> {
+    let (_, file) = get_open_file(files, file_id)?;
+    let num_of_chunks = file.chunks()?.len();
+    if num_of_chunks as u64 != size / CHUNK_SIZE + 1 {
+        return Err(SizeMismatch {
+            size,
+            num_of_chunks,
+        }
+        .build()
+        .into());
+    }
+    Ok(())
+}
+
+/// Checks that the node is at the version which the request expects, if there is one
+pub fn check_version(expected: Option<usize>, actual: usize) -> CCFSResult<()> {
+    match expected {
+        Some(expected) if expected != actual => {
+            Err(VersionConflict { expected, actual }.build().into())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Checks that the file which the upload in progress overwrites is still a file,
+/// at the version the upload expects
+pub fn check_overwrite(tree: &FileMetadata, files: &Files, file_id: &Uuid) -> CCFSResult<()> {
+    let (path, file) = match get_upload(files, file_id) {
+        Ok(upload) => upload,
+        Err(_) => return Ok(()),
+    };
+    check_kind(tree.traverse(path)?, file)?;
+    if let FileInfo::File {
+        expected_version: Some(expected),
+        ..
+    } = &file.file_info
+    {
+        let children = tree.traverse(path)?.children()?;
+        let actual = children.get(&file.name).map_or(0, |node| node.version);
+        check_version(Some(*expected), actual)?;
+    }
+    Ok(())
+}
+
+/// Appends the chunks to the file which is still being written
+pub fn append_chunks(files: &mut Files, file_id: &Uuid, new_chunks: Vec<Uuid>) -> CCFSResult<()> {
+    get_open_file(files, file_id)?;
+    if let Some((_, file)) = files.get_mut(file_id) {
+        if let FileInfo::File { chunks, .. } = &mut file.file_info {
+            chunks.extend(new_chunks);
+        }
+    }
+    Ok(())
+}
+
+/// Sets the final size of the open file, and adds it to the tree if all of its chunks
+/// are already uploaded. Returns the chunk replicas of the dropped versions of the file
+pub fn commit_file(
+    tree: &mut FileMetadata,
+    files: &mut Files,
+    chunks: &mut Chunks,
+    file_id: &Uuid,
+    final_size: u64,
+) -> CCFSResult<Vec<Chunk>> {
+    check_commit(files, file_id, final_size)?;
+    let mut completed = false;
+    if let Some((_, file)) = files.get_mut(file_id) {
+        if let FileInfo::File {
+            size,
+            chunks: file_chunks,
+            num_of_completed_chunks,
+            status,
+            ..
+        } = &mut file.file_info
+        {
+            *size = final_size;
+            *status = FileStatus::Started;
+            if *num_of_completed_chunks == file_chunks.len() {
+                *status = FileStatus::Completed;
+                completed = true;
+            }
+        }
+    }
+    match completed {
+        true => insert_completed(tree, files, chunks, file_id),
+        false => Ok(Vec::new()),
+    }
+}
+
+/// Returns the file which is still being uploaded (or written), and the path of its directory
+pub fn get_upload<'a>(files: &'a Files, file_id: &Uuid) -> CCFSResult<&'a (String, FileMetadata)> {
+    let entry = files.get(file_id).ok_or_else(|| NotFound.build())?;
+    match &entry.1.file_info {
+        FileInfo::File {
+            status: FileStatus::Started,
+            ..
+        }
+        | FileInfo::File {
+            status: FileStatus::Open,
+            ..
+        } => Ok(entry),
+        _ => Err(NotUploading { id: *file_id }.build().into()),
+    }
+}
+
+/// Drops the uploads which are still in progress, the completed files are left unchanged.
+/// Returns the chunk replicas which should be deleted from the chunk servers
+pub fn cancel_uploads(files: &mut Files, chunks: &mut Chunks, file_ids: &[Uuid]) -> Vec<Chunk> {
+    let mut removed_chunks = Vec::new();
+    for file_id in file_ids {
+        if get_upload(files, file_id).is_err() {
+            continue;
+        }
+        if let Some((_, file)) = files.remove(file_id) {
+            if let FileInfo::File { chunks: ids, .. } = &file.file_info {
+                removed_chunks.extend(ids.iter().filter_map(|id| chunks.remove(id)).flatten());
+            }
+        }
+    }
+    removed_chunks
+}
+
+/// Detaches the node at the (evaluated) path from the tree, and drops its files and chunks.
+///
+/// Returns the removed node and the chunk replicas which should be deleted from the chunk servers
+pub fn remove_file(
+    tree: &mut FileMetadata,
+    files: &mut Files,
+    chunks: &mut Chunks,
+    path: &str,
+) -> CCFSResult<(FileMetadata, Vec<Chunk>)> {
+    let target = Path::new(path);
+    let (parent_path, name) = match (target.parent(), target.file_name()) {
+        (Some(parent), Some(name)) => (parent.display().to_string(), name.to_string_lossy()),
+        _ => {
+            let msg = "Cannot remove the root directory".into();
+            return Err(BaseError::InvalidPath { msg }.into());
+        }
+    };
+    let removed = tree.traverse_mut(&parent_path)?.remove_child(&name)?;
+
+    let mut file_ids = HashSet::new();
+    let mut chunk_ids = Vec::new();
+    for file in removed.dfs_iter() {
+        if let FileInfo::File { versions, .. } = &file.file_info {
+            // the previous versions of the file are removed with it
+            for version in std::iter::once(file).chain(versions) {
+                if let FileInfo::File { id, chunks, .. } = &version.file_info {
+                    file_ids.insert(*id);
+                    chunk_ids.extend(chunks.iter().cloned());
+                }
+            }
+        }
+    }
+    // files which are still being uploaded to the removed path are dropped as well
+    let removed_ids = files
+        .iter()
+        .filter(|(id, (file_path, _))| {
+            file_ids.contains(id) || Path::new(file_path).starts_with(path)
+        })
+        .map(|(id, _)| *id)
+        .collect::<Vec<_>>();
+    for id in removed_ids {
+        if let Some((_, file)) = files.remove(&id) {
+            chunk_ids.extend(file.chunks()?.iter().cloned());
+        }
+    }
+    let removed_chunks = chunk_ids
+        .iter()
+        .filter_map(|chunk_id| chunks.remove(chunk_id))
+        .flatten()
+        .collect();
+    Ok((removed, removed_chunks))
+}
+
+/// Changes the number of replicas of the file at the (evaluated) path,
+/// the replication job creates or removes the replicas afterwards
+pub fn set_replicas(
+    tree: &mut FileMetadata,
+    files: &mut Files,
+    path: &str,
+    num_of_replicas: Option<usize>,
+) -> CCFSResult<()> {
+    let file = tree.traverse_mut(path)?;
+    file.chunks()?;
+    if let FileInfo::File { id, replicas, .. } = &mut file.file_info {
+        *replicas = num_of_replicas;
+        // the files map holds a copy of the completed files as well
+        if let Some((_, file)) = files.get_mut(id) {
+            if let FileInfo::File { replicas, .. } = &mut file.file_info {
+                *replicas = num_of_replicas;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Changes the number of the previous versions of the file at the (evaluated) path which are
+/// kept when it's overwritten. Returns the chunk replicas of the versions over the new number
+pub fn set_kept_versions(
+    tree: &mut FileMetadata,
+    files: &mut Files,
+    chunks: &mut Chunks,
+    path: &str,
+    num_of_versions: usize,
+) -> CCFSResult<Vec<Chunk>> {
+    let file = tree.traverse_mut(path)?;
+    file.chunks()?;
+    let mut dropped = Vec::new();
+    if let FileInfo::File {
+        id,
+        kept_versions,
+        versions,
+        ..
+    } = &mut file.file_info
+    {
+        *kept_versions = num_of_versions;
+        dropped = versions.split_off(num_of_versions.min(versions.len()));
+        let versions = versions.clone();
+        // the files map holds a copy of the completed files as well
+        if let Some((_, file)) = files.get_mut(id) {
+            if let FileInfo::File {
+                kept_versions,
+                versions: copied,
+                ..
+            } = &mut file.file_info
+            {
+                *kept_versions = num_of_versions;
+                *copied = versions;
+            }
+        }
+    }
+    Ok(drop_versions(files, chunks, &dropped))
+}
+
+/// Unregisters the chunk replicas, and returns the ones which were removed.
+/// The last replica of a chunk is never removed
+pub fn remove_replicas(chunks: &mut Chunks, replicas: Vec<Chunk>) -> Vec<Chunk> {
+    replicas
+        .into_iter()
+        .filter(|replica| match chunks.get_mut(&replica.id) {
+            Some(chunk_set) if chunk_set.len() > 1 => chunk_set.remove(replica),
+            _ => false,
+        })
+        .collect()
+}
+
+/// Unregisters the replicas stored on the reporting servers, even the last ones,
+/// so the replication job restores them from the healthy copies
+pub fn unregister_replicas(chunks: &mut Chunks, replicas: &[Chunk]) {
+    for replica in replicas {
+        if let Some(chunk_set) = chunks.get_mut(&replica.id) {
+            // the recorded checksums may differ, so the replicas are matched by their server
+            chunk_set.retain(|c| c.server_id != replica.server_id);
+        }
+    }
+}
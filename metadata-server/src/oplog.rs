@@ -0,0 +1,245 @@
+use crate::operations::Operation;
+use ccfs_commons::{errors::Error as BaseError, result::CCFSResult};
+use serde::{Deserialize, Serialize};
+use std::convert::TryInto;
+use std::path::{Path, PathBuf};
+use tokio::fs::{rename, File, OpenOptions};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LogEntry {
+    pub seq: u64,
+    /// Raft term of the leader which created the entry
+    pub term: u64,
+    pub operation: Operation,
+}
+
+/// Append-only log of the metadata mutations.
+///
+/// Every entry is written as a length prefixed (u32 LE) bincode record, and
+/// flushed to disk before the operation is applied. A torn record at the end
+/// of the file (crash in the middle of a write) is ignored when reading.
+///
+/// The entries which are not included in the snapshot are kept in memory as well,
+/// so they can be replicated to the other nodes of the cluster.
+pub struct OperationLog {
+    path: PathBuf,
+    file: File,
+    entries: Vec<LogEntry>,
+    /// Sequence number and term of the last entry included in the snapshot
+    snapshot_seq: u64,
+    snapshot_term: u64,
+}
+
+impl OperationLog {
+    /// Opens (or creates) the log, skipping the entries which are already
+    /// included in the snapshot with the `snapshot_seq` sequence number
+    pub async fn open(path: &Path, snapshot_seq: u64, snapshot_term: u64) -> CCFSResult<Self> {
+        let (entries, is_torn) = read_log(path).await?;
+        if is_torn {
+            // drop the partially written record, so the new ones are appended after a valid one
+            write_entries(path, &entries).await?;
+        }
+        let entries = entries
+            .into_iter()
+            .filter(|e| e.seq > snapshot_seq)
+            .collect::<Vec<_>>();
+        let file = open_append(path).await?;
+        Ok(Self {
+            path: path.into(),
+            file,
+            entries,
+            snapshot_seq,
+            snapshot_term,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn snapshot_seq(&self) -> u64 {
+        self.snapshot_seq
+    }
+
+    pub fn last_seq(&self) -> u64 {
+        self.entries.last().map_or(self.snapshot_seq, |e| e.seq)
+    }
+
+    pub fn last_term(&self) -> u64 {
+        self.entries.last().map_or(self.snapshot_term, |e| e.term)
+    }
+
+    /// Returns the term of the entry with the `seq` sequence number, if it's still known
+    pub fn term_at(&self, seq: u64) -> Option<u64> {
+        match seq {
+            seq if seq == self.snapshot_seq => Some(self.snapshot_term),
+            seq => self.entry(seq).map(|e| e.term),
+        }
+    }
+
+    pub fn entry(&self, seq: u64) -> Option<&LogEntry> {
+        if seq <= self.snapshot_seq {
+            return None;
+        }
+        self.entries.get((seq - self.snapshot_seq - 1) as usize)
+    }
+
+    /// Returns the entries starting from the `seq` sequence number
+    pub fn entries_from(&self, seq: u64) -> &[LogEntry] {
+        let start = seq.saturating_sub(self.snapshot_seq + 1) as usize;
+        &self.entries[start.min(self.entries.len())..]
+    }
+
+    /// Durably appends the operation to the log, and returns its sequence number
+    pub async fn append(&mut self, term: u64, operation: Operation) -> CCFSResult<u64> {
+        let entry = LogEntry {
+            seq: self.last_seq() + 1,
+            term,
+            operation,
+        };
+        let seq = entry.seq;
+        self.append_entries(vec![entry]).await?;
+        Ok(seq)
+    }
+
+    /// Durably appends the entries (received from the leader) to the log
+    pub async fn append_entries(&mut self, entries: Vec<LogEntry>) -> CCFSResult<()> {
+        let records = entries.iter().flat_map(encode_entry).collect::<Vec<u8>>();
+        let path = &self.path;
+        self.file
+            .write_all(&records)
+            .await
+            .map_err(|source| BaseError::Write {
+                path: path.into(),
+                source,
+            })?;
+        self.file
+            .sync_data()
+            .await
+            .map_err(|source| BaseError::Write {
+                path: path.into(),
+                source,
+            })?;
+        self.entries.extend(entries);
+        Ok(())
+    }
+
+    /// Removes the entries up to (including) `seq`, which are covered by a snapshot
+    pub async fn truncate(&mut self, seq: u64) -> CCFSResult<()> {
+        let term = match self.term_at(seq) {
+            Some(term) if seq > self.snapshot_seq => term,
+            _ => return Ok(()),
+        };
+        self.entries.retain(|e| e.seq > seq);
+        self.snapshot_seq = seq;
+        self.snapshot_term = term;
+        self.rewrite().await
+    }
+
+    /// Removes the entries starting from `seq`, which conflict with the leader's log
+    pub async fn truncate_from(&mut self, seq: u64) -> CCFSResult<()> {
+        self.entries.retain(|e| e.seq < seq);
+        self.rewrite().await
+    }
+
+    /// Drops the whole log, when it's replaced by a snapshot received from the leader
+    pub async fn reset(&mut self, snapshot_seq: u64, snapshot_term: u64) -> CCFSResult<()> {
+        self.entries.clear();
+        self.snapshot_seq = snapshot_seq;
+        self.snapshot_term = snapshot_term;
+        self.rewrite().await
+    }
+
+    async fn rewrite(&mut self) -> CCFSResult<()> {
+        write_entries(&self.path, &self.entries).await?;
+        self.file = open_append(&self.path).await?;
+        Ok(())
+    }
+}
+fn encode_entry(entry: &LogEntry) -> Vec<u8> {
+    let data = bincode::serialize(entry).unwrap();
+    let mut record = (data.len() as u32).to_le_bytes().to_vec();
+    record.extend(data);
+    record
+}
+
+async fn open_append(path: &Path) -> CCFSResult<File> {
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .await
+        .map_err(|source| BaseError::Open {
+            path: path.into(),
+            source,
+        })?;
+    Ok(file)
+}
+
+pub async fn read_entries(path: &Path) -> CCFSResult<Vec<LogEntry>> {
+    Ok(read_log(path).await?.0)
+}
+
+/// Reads the valid entries, and whether the log ends with a partially written record
+async fn read_log(path: &Path) -> CCFSResult<(Vec<LogEntry>, bool)> {
+    if !path.exists() {
+        return Ok((Vec::new(), false));
+    }
+    let mut content = Vec::new();
+    let mut file = File::open(path).await.map_err(|source| BaseError::Open {
+        path: path.into(),
+        source,
+    })?;
+    file.read_to_end(&mut content)
+        .await
+        .map_err(|source| BaseError::Read {
+            path: path.into(),
+            source,
+        })?;
+
+    let mut entries = Vec::new();
+    let mut rest = content.as_slice();
+    while rest.len() >= 4 {
+        let len = u32::from_le_bytes(rest[..4].try_into().unwrap()) as usize;
+        if rest.len() < 4 + len {
+            break;
+        }
+        match bincode::deserialize(&rest[4..4 + len]) {
+            Ok(entry) => entries.push(entry),
+            Err(_) => break,
+        }
+        rest = &rest[4 + len..];
+    }
+    Ok((entries, !rest.is_empty()))
+}
+
+/// Atomically replaces the log content with the entries
+async fn write_entries(path: &Path, entries: &[LogEntry]) -> CCFSResult<()> {
+    let content = entries.iter().flat_map(encode_entry).collect::<Vec<u8>>();
+    let temp_path = path.with_extension("tmp");
+    let mut temp = File::create(&temp_path)
+        .await
+        .map_err(|source| BaseError::Create {
+            path: temp_path.clone(),
+            source,
+        })?;
+    temp.write_all(&content)
+        .await
+        .map_err(|source| BaseError::Write {
+            path: temp_path.clone(),
+            source,
+        })?;
+    temp.sync_data().await.map_err(|source| BaseError::Write {
+        path: temp_path.clone(),
+        source,
+    })?;
+    rename(&temp_path, path)
+        .await
+        .map_err(|source| BaseError::Rename {
+            from: temp_path,
+            to: path.into(),
+            source,
+        })?;
+    Ok(())
+}
@@ -0,0 +1,162 @@
+//! Selection of the chunk servers where the chunks of the newly created files are stored
+
+use ccfs_commons::{ChunkPlacement, ChunkServer, CHUNK_SIZE};
+use rand::{seq::SliceRandom, thread_rng};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::sync::Arc;
+use uuid::Uuid;
+
+pub trait PlacementPolicy: Send + Sync {
+    /// Picks up to `replicas` distinct servers for each of the chunks, the first one being the primary
+    fn place(
+        &self,
+        servers: &[ChunkServer],
+        chunks: &[Uuid],
+        replicas: usize,
+    ) -> Vec<ChunkPlacement>;
+}
+
+/// Placement policy used for the new files, along with the default number of replicas of their chunks
+#[derive(Clone)]
+pub struct Placement {
+    pub policy: Arc<dyn PlacementPolicy>,
+    pub replication_factor: usize,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PlacementPolicyKind {
+    Balanced,
+    Random,
+}
+impl Default for PlacementPolicyKind {
+    fn default() -> Self {
+        Self::Balanced
+    }
+}
+impl PlacementPolicyKind {
+    pub fn policy(self) -> Arc<dyn PlacementPolicy> {
+        match self {
+            Self::Balanced => Arc::new(BalancedPlacement),
+            Self::Random => Arc::new(RandomPlacement),
+        }
+    }
+}
+
+/// Returns the servers which can receive new chunks: the active ones,
+/// which have enough free space (or didn't report their capacity yet)
+pub fn candidates<'a, I: IntoIterator<Item = &'a ChunkServer>>(servers: I) -> Vec<ChunkServer> {
+    servers
+        .into_iter()
+        .filter(|s| s.is_active())
+        .filter(|s| s.total_space == 0 || s.available_space >= CHUNK_SIZE)
+        .cloned()
+        .collect()
+}
+
+/// Reorders the servers, so the ones from the zones which aren't in `used_zones` come first,
+/// one per zone. The preference order is kept within both groups, and the servers
+/// without a zone label are considered to be in a zone of their own
+pub fn spread_across_zones<'a>(
+    servers: Vec<&'a ChunkServer>,
+    used_zones: &HashSet<&str>,
+) -> Vec<&'a ChunkServer> {
+    let mut picked_zones = HashSet::new();
+    let (mut spread, mut rest) = (Vec::new(), Vec::new());
+    for server in servers {
+        let is_new_zone = match server.zone.as_deref() {
+            Some(zone) => !used_zones.contains(zone) && picked_zones.insert(zone),
+            None => true,
+        };
+        match is_new_zone {
+            true => spread.push(server),
+            false => rest.push(server),
+        }
+    }
+    spread.extend(rest);
+    spread
+}
+
+/// Prefers the servers with the most free space and the least pending requests.
+///
+/// Every placed chunk is accounted to its servers, so the chunks
+/// of a single file are spread across the cluster
+pub struct BalancedPlacement;
+
+impl PlacementPolicy for BalancedPlacement {
+    fn place(
+        &self,
+        servers: &[ChunkServer],
+        chunks: &[Uuid],
+        replicas: usize,
+    ) -> Vec<ChunkPlacement> {
+        let mut load = servers
+            .iter()
+            .map(|s| (s, s.available_space, s.pending_requests))
+            .collect::<Vec<_>>();
+        chunks
+            .iter()
+            .map(|chunk_id| {
+                // the sort is stable, so the ties keep their previous order
+                load.sort_by(|(_, a_space, a_pending), (_, b_space, b_pending)| {
+                    let a = score(*a_space, *a_pending);
+                    let b = score(*b_space, *b_pending);
+                    b.partial_cmp(&a).unwrap_or(Ordering::Equal)
+                });
+                let ranked = load.iter().map(|(server, ..)| *server).collect();
+                let servers = spread_across_zones(ranked, &HashSet::new())
+                    .into_iter()
+                    .take(replicas)
+                    .cloned()
+                    .collect::<Vec<_>>();
+                for (server, space, pending) in load.iter_mut() {
+                    if servers.iter().any(|s| s.id == server.id) {
+                        *space = space.saturating_sub(CHUNK_SIZE);
+                        *pending += 1;
+                    }
+                }
+                ChunkPlacement {
+                    chunk_id: *chunk_id,
+                    servers,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Ranks the servers by their free space and load, the higher the better.
+/// The servers which didn't report their capacity are still ranked by their load
+pub fn score(available_space: u64, pending_requests: u64) -> f64 {
+    (available_space + CHUNK_SIZE) as f64 / (pending_requests + 1) as f64
+}
+
+/// Picks random servers (from distinct zones), regardless of their capacity and load
+pub struct RandomPlacement;
+
+impl PlacementPolicy for RandomPlacement {
+    fn place(
+        &self,
+        servers: &[ChunkServer],
+        chunks: &[Uuid],
+        replicas: usize,
+    ) -> Vec<ChunkPlacement> {
+        let mut rng = thread_rng();
+        chunks
+            .iter()
+            .map(|chunk_id| {
+                let mut shuffled = servers.iter().collect::<Vec<_>>();
+                shuffled.shuffle(&mut rng);
+                ChunkPlacement {
+                    chunk_id: *chunk_id,
+                    servers: spread_across_zones(shuffled, &HashSet::new())
+                        .into_iter()
+                        .take(replicas)
+                        .cloned()
+                        .collect(),
+                }
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,758 @@
+//! Raft consensus between the metadata servers.
+//!
+//! Every metadata mutation is appended to the leader's operation log, replicated
+//! to the followers, and applied once it's stored on the majority of the nodes.
+//! Followers don't accept writes, the clients are redirected to the leader instead.
+
+use crate::errors::*;
+use crate::jobs::snapshot::{load_snapshot, write_snapshot, Snapshot};
+use crate::operations::Operation;
+use crate::oplog::{LogEntry, OperationLog};
+use crate::{Chunks, ChunksMap, FileMetadataTree, Files, FilesMap, Raft};
+use ccfs_commons::{errors::Error as BaseError, result::CCFSResult};
+use ccfs_commons::{Chunk, FileMetadata};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use snafu::ResultExt;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::fs::{read, rename, write};
+use tokio::sync::{mpsc::UnboundedSender, watch, Mutex, OwnedMutexGuard};
+use tokio::time::{timeout, Duration, Instant};
+
+/// Max number of log entries sent to a follower in a single message
+pub const MAX_ENTRIES_PER_MESSAGE: usize = 100;
+/// How long the leader waits for an operation to be replicated to the majority of the nodes
+pub const COMMIT_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Peer {
+    pub id: u64,
+    pub address: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct RaftConfig {
+    pub node_id: u64,
+    pub peers: Vec<Peer>,
+    pub heartbeat_interval: Duration,
+    /// Followers start an election after a random timeout between 1x and 2x of this value
+    pub election_timeout: Duration,
+    pub snapshot_path: PathBuf,
+    /// Path of the file where the current term and vote are persisted
+    pub state_path: PathBuf,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Role {
+    Follower,
+    Candidate,
+    Leader,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum RaftMessage {
+    RequestVote {
+        term: u64,
+        last_log_seq: u64,
+        last_log_term: u64,
+    },
+    Vote {
+        term: u64,
+        granted: bool,
+    },
+    AppendEntries {
+        term: u64,
+        prev_log_seq: u64,
+        prev_log_term: u64,
+        entries: Vec<LogEntry>,
+        leader_commit: u64,
+    },
+    /// `last_seq` is the last replicated entry on success, or the last entry
+    /// which may match the leader's log on failure
+    AppendResponse {
+        term: u64,
+        success: bool,
+        last_seq: u64,
+    },
+    /// Sent instead of the entries which are already compacted into the leader's snapshot
+    InstallSnapshot {
+        term: u64,
+        snapshot: Snapshot,
+    },
+}
+
+impl RaftMessage {
+    pub fn term(&self) -> u64 {
+        match self {
+            RaftMessage::RequestVote { term, .. }
+            | RaftMessage::Vote { term, .. }
+            | RaftMessage::AppendEntries { term, .. }
+            | RaftMessage::AppendResponse { term, .. }
+            | RaftMessage::InstallSnapshot { term, .. } => *term,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RaftStatus {
+    pub node_id: u64,
+    pub role: Role,
+    pub term: u64,
+    pub leader_id: Option<u64>,
+    pub commit_index: u64,
+    pub last_applied: u64,
+    pub last_seq: u64,
+}
+
+/// State which has to survive restarts, so a node doesn't vote twice in the same term
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct HardState {
+    term: u64,
+    voted_for: Option<u64>,
+}
+
+pub struct RaftNode {
+    id: u64,
+    peers: HashMap<u64, String>,
+    heartbeat_interval: Duration,
+    election_timeout: Duration,
+    snapshot_path: PathBuf,
+    state_path: PathBuf,
+
+    term: u64,
+    voted_for: Option<u64>,
+    role: Role,
+    leader_id: Option<u64>,
+    log: OperationLog,
+    commit_index: u64,
+    last_applied: u64,
+    /// Snapshot received from the leader, which is not applied yet
+    pending_snapshot: Option<Snapshot>,
+    votes: HashSet<u64>,
+    next_seq: HashMap<u64, u64>,
+    match_seq: HashMap<u64, u64>,
+    election_deadline: Instant,
+    heartbeat_deadline: Instant,
+
+    outbox: UnboundedSender<(u64, RaftMessage)>,
+    commit_sender: watch::Sender<u64>,
+    commit_receiver: watch::Receiver<u64>,
+    /// Held by the leader from checking a change until it's applied, so the changes
+    /// don't need to hold the metadata locks while they're being committed
+    writes: Arc<Mutex<()>>,
+}
+
+impl RaftNode {
+    /// Creates the node with the log whose entries up to the `log.snapshot_seq()`
+    /// are already applied. The messages for the other nodes are sent to the `outbox`.
+    ///
+    /// A node without peers becomes the leader right away
+    pub async fn new(
+        config: RaftConfig,
+        log: OperationLog,
+        outbox: UnboundedSender<(u64, RaftMessage)>,
+    ) -> CCFSResult<Self> {
+        let state = load_hard_state(&config.state_path).await?;
+        let applied = log.snapshot_seq();
+        let (commit_sender, commit_receiver) = watch::channel(applied);
+        let now = Instant::now();
+        let mut node = Self {
+            id: config.node_id,
+            peers: config
+                .peers
+                .into_iter()
+                .map(|peer| (peer.id, peer.address))
+                .collect(),
+            heartbeat_interval: config.heartbeat_interval,
+            election_timeout: config.election_timeout,
+            snapshot_path: config.snapshot_path,
+            state_path: config.state_path,
+            term: state.term,
+            voted_for: state.voted_for,
+            role: Role::Follower,
+            leader_id: None,
+            log,
+            commit_index: applied,
+            last_applied: applied,
+            pending_snapshot: None,
+            votes: HashSet::new(),
+            next_seq: HashMap::new(),
+            match_seq: HashMap::new(),
+            election_deadline: now,
+            heartbeat_deadline: now,
+            outbox,
+            commit_sender,
+            commit_receiver,
+            writes: Arc::new(Mutex::new(())),
+        };
+        node.reset_election_deadline();
+        if node.peers.is_empty() {
+            node.start_election().await?;
+        }
+        Ok(node)
+    }
+
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn term(&self) -> u64 {
+        self.term
+    }
+
+    pub fn role(&self) -> Role {
+        self.role
+    }
+
+    pub fn is_leader(&self) -> bool {
+        self.role == Role::Leader
+    }
+
+    pub fn leader_id(&self) -> Option<u64> {
+        self.leader_id
+    }
+
+    /// Returns the address of the leader, if it's known and it's not this node
+    pub fn leader_address(&self) -> Option<&str> {
+        self.leader_id
+            .and_then(|id| self.peers.get(&id))
+            .map(String::as_str)
+    }
+
+    pub fn commit_index(&self) -> u64 {
+        self.commit_index
+    }
+
+    pub fn last_applied(&self) -> u64 {
+        self.last_applied
+    }
+
+    pub fn log(&self) -> &OperationLog {
+        &self.log
+    }
+
+    /// Returns whether there is a snapshot received from the leader which isn't applied yet
+    pub fn has_pending_snapshot(&self) -> bool {
+        self.pending_snapshot.is_some()
+    }
+
+    /// Returns whether the leader committed an entry of its term, and applied all
+    /// the committed entries, so its metadata includes all the changes of the cluster
+    pub fn is_caught_up(&self) -> bool {
+        self.is_leader()
+            && self.log.term_at(self.commit_index) == Some(self.term)
+            && self.last_applied >= self.commit_index
+            && self.pending_snapshot.is_none()
+    }
+
+    /// Returns a receiver which is notified whenever the commit index changes
+    pub fn subscribe(&self) -> watch::Receiver<u64> {
+        self.commit_receiver.clone()
+    }
+
+    pub fn status(&self) -> RaftStatus {
+        RaftStatus {
+            node_id: self.id,
+            role: self.role,
+            term: self.term,
+            leader_id: self.leader_id,
+            commit_index: self.commit_index,
+            last_applied: self.last_applied,
+            last_seq: self.log.last_seq(),
+        }
+    }
+
+    /// Sends the heartbeats when the node is the leader, or starts
+    /// an election when the leader didn't respond in time
+    pub async fn tick(&mut self) -> CCFSResult<()> {
+        let now = Instant::now();
+        match self.role {
+            Role::Leader if now >= self.heartbeat_deadline => self.broadcast_append().await,
+            Role::Leader => Ok(()),
+            _ if now >= self.election_deadline => self.start_election().await,
+            _ => Ok(()),
+        }
+    }
+
+    /// Appends the operation to the log and starts replicating it,
+    /// returns the sequence number of the new entry
+    pub async fn propose(&mut self, operation: Operation) -> CCFSResult<u64> {
+        if !self.is_leader() {
+            return Err(NotLeader { location: None }.build().into());
+        }
+        let seq = self.log.append(self.term, operation).await?;
+        self.update_commit_index();
+        self.broadcast_append().await?;
+        Ok(seq)
+    }
+
+    /// Returns the snapshot received from the leader and the committed entries
+    /// which should be applied next, and marks them as applied
+    pub fn take_committed(&mut self) -> (Option<Snapshot>, Vec<LogEntry>) {
+        let snapshot = self.pending_snapshot.take();
+        if let Some(snapshot) = &snapshot {
+            self.last_applied = snapshot.last_op_seq;
+        }
+        let commit_index = self.commit_index;
+        let entries = self
+            .log
+            .entries_from(self.last_applied + 1)
+            .iter()
+            .take_while(|e| e.seq <= commit_index)
+            .cloned()
+            .collect::<Vec<_>>();
+        if let Some(entry) = entries.last() {
+            self.last_applied = entry.seq;
+        }
+        (snapshot, entries)
+    }
+
+    /// Removes the log entries which are included in a snapshot
+    pub async fn compact_log(&mut self, seq: u64) -> CCFSResult<()> {
+        self.log.truncate(seq.min(self.last_applied)).await
+    }
+
+    pub async fn handle(&mut self, from: u64, message: RaftMessage) -> CCFSResult<()> {
+        if !self.peers.contains_key(&from) {
+            return Ok(());
+        }
+        if message.term() > self.term {
+            self.become_follower(message.term()).await?;
+        }
+        match message {
+            RaftMessage::RequestVote {
+                term,
+                last_log_seq,
+                last_log_term,
+            } => {
+                self.handle_vote_request(from, term, last_log_seq, last_log_term)
+                    .await
+            }
+            RaftMessage::Vote { term, granted } => self.handle_vote(from, term, granted).await,
+            RaftMessage::AppendEntries {
+                term,
+                prev_log_seq,
+                prev_log_term,
+                entries,
+                leader_commit,
+            } => {
+                self.handle_append_entries(
+                    from,
+                    term,
+                    prev_log_seq,
+                    prev_log_term,
+                    entries,
+                    leader_commit,
+                )
+                .await
+            }
+            RaftMessage::AppendResponse {
+                term,
+                success,
+                last_seq,
+            } => {
+                self.handle_append_response(from, term, success, last_seq)
+                    .await
+            }
+            RaftMessage::InstallSnapshot { term, snapshot } => {
+                self.handle_install_snapshot(from, term, snapshot).await
+            }
+        }
+    }
+
+    async fn handle_vote_request(
+        &mut self,
+        from: u64,
+        term: u64,
+        last_log_seq: u64,
+        last_log_term: u64,
+    ) -> CCFSResult<()> {
+        let is_up_to_date =
+            (last_log_term, last_log_seq) >= (self.log.last_term(), self.log.last_seq());
+        let granted =
+            term == self.term && is_up_to_date && self.voted_for.map_or(true, |id| id == from);
+        if granted {
+            self.voted_for = Some(from);
+            self.persist_hard_state().await?;
+            self.reset_election_deadline();
+        }
+        let term = self.term;
+        self.send(from, RaftMessage::Vote { term, granted });
+        Ok(())
+    }
+
+    async fn handle_vote(&mut self, from: u64, term: u64, granted: bool) -> CCFSResult<()> {
+        if self.role != Role::Candidate || term != self.term || !granted {
+            return Ok(());
+        }
+        self.votes.insert(from);
+        if self.has_majority(self.votes.len()) {
+            self.become_leader().await?;
+        }
+        Ok(())
+    }
+
+    async fn handle_append_entries(
+        &mut self,
+        from: u64,
+        term: u64,
+        prev_log_seq: u64,
+        prev_log_term: u64,
+        entries: Vec<LogEntry>,
+        leader_commit: u64,
+    ) -> CCFSResult<()> {
+        if term < self.term {
+            self.respond_append(from, false, self.log.last_seq());
+            return Ok(());
+        }
+        self.follow(from);
+
+        let snapshot_seq = self.log.snapshot_seq();
+        if prev_log_seq > self.log.last_seq() {
+            self.respond_append(from, false, self.log.last_seq());
+            return Ok(());
+        }
+        // entries included in the snapshot are committed, so they match the leader's log
+        if prev_log_seq >= snapshot_seq && self.log.term_at(prev_log_seq) != Some(prev_log_term) {
+            // the logs are the same at least up to the commit index
+            let last_seq = self.commit_index.min(prev_log_seq.saturating_sub(1));
+            self.respond_append(from, false, last_seq);
+            return Ok(());
+        }
+
+        let last_new_seq = entries.last().map_or(prev_log_seq, |e| e.seq);
+        let mut new_entries = Vec::new();
+        for entry in entries.into_iter().filter(|e| e.seq > snapshot_seq) {
+            if new_entries.is_empty() {
+                match self.log.term_at(entry.seq) {
+                    Some(term) if term == entry.term => continue,
+                    Some(_) => self.log.truncate_from(entry.seq).await?,
+                    None => {}
+                }
+            }
+            new_entries.push(entry);
+        }
+        if !new_entries.is_empty() {
+            self.log.append_entries(new_entries).await?;
+        }
+        let commit_index = leader_commit.min(last_new_seq);
+        if commit_index > self.commit_index {
+            self.set_commit_index(commit_index);
+        }
+        self.respond_append(from, true, last_new_seq);
+        Ok(())
+    }
+
+    async fn handle_append_response(
+        &mut self,
+        from: u64,
+        term: u64,
+        success: bool,
+        last_seq: u64,
+    ) -> CCFSResult<()> {
+        if self.role != Role::Leader || term != self.term {
+            return Ok(());
+        }
+        let next_seq = self.next_seq.get(&from).copied().unwrap_or(1);
+        if success {
+            let match_seq = self.match_seq.entry(from).or_default();
+            *match_seq = (*match_seq).max(last_seq);
+            let match_seq = *match_seq;
+            self.next_seq.insert(from, next_seq.max(match_seq + 1));
+            self.update_commit_index();
+            if match_seq < self.log.last_seq() && next_seq <= match_seq + 1 {
+                self.send_append(from).await?;
+            }
+        } else {
+            let next_seq = (last_seq + 1).min(next_seq.saturating_sub(1)).max(1);
+            self.next_seq.insert(from, next_seq);
+            self.send_append(from).await?;
+        }
+        Ok(())
+    }
+
+    async fn handle_install_snapshot(
+        &mut self,
+        from: u64,
+        term: u64,
+        snapshot: Snapshot,
+    ) -> CCFSResult<()> {
+        if term < self.term {
+            self.respond_append(from, false, self.log.last_seq());
+            return Ok(());
+        }
+        self.follow(from);
+
+        let (seq, snapshot_term) = (snapshot.last_op_seq, snapshot.last_op_term);
+        if seq > self.commit_index {
+            // the snapshot is persisted before the log is compacted, so there
+            // is no gap between them if the node crashes in the meantime
+            let temp_path = self.snapshot_path.with_extension("received");
+            write_snapshot(&self.snapshot_path, &temp_path, &snapshot).await?;
+            if self.log.term_at(seq) == Some(snapshot_term) {
+                self.log.truncate(seq).await?;
+            } else {
+                self.log.reset(seq, snapshot_term).await?;
+            }
+            self.set_commit_index(seq);
+            self.pending_snapshot = Some(snapshot);
+        }
+        self.respond_append(from, true, seq);
+        Ok(())
+    }
+
+    async fn start_election(&mut self) -> CCFSResult<()> {
+        self.term += 1;
+        self.role = Role::Candidate;
+        self.voted_for = Some(self.id);
+        self.leader_id = None;
+        self.votes = vec![self.id].into_iter().collect();
+        self.persist_hard_state().await?;
+        self.reset_election_deadline();
+        if self.has_majority(self.votes.len()) {
+            return self.become_leader().await;
+        }
+        let message = RaftMessage::RequestVote {
+            term: self.term,
+            last_log_seq: self.log.last_seq(),
+            last_log_term: self.log.last_term(),
+        };
+        for peer in self.peers.keys() {
+            self.send(*peer, message.clone());
+        }
+        Ok(())
+    }
+
+    async fn become_leader(&mut self) -> CCFSResult<()> {
+        self.role = Role::Leader;
+        self.leader_id = Some(self.id);
+        let next_seq = self.log.last_seq() + 1;
+        self.next_seq = self.peers.keys().map(|id| (*id, next_seq)).collect();
+        self.match_seq = self.peers.keys().map(|id| (*id, 0)).collect();
+        // entries from the previous terms are committed together with the first entry of this term
+        self.log.append(self.term, Operation::Noop).await?;
+        self.update_commit_index();
+        println!("Node {} became the leader for term {}", self.id, self.term); // TODO: replace with logger
+        self.broadcast_append().await
+    }
+
+    async fn become_follower(&mut self, term: u64) -> CCFSResult<()> {
+        self.term = term;
+        self.voted_for = None;
+        self.role = Role::Follower;
+        self.leader_id = None;
+        self.persist_hard_state().await
+    }
+
+    /// Accepts the sender as the leader of the current term
+    fn follow(&mut self, leader_id: u64) {
+        self.role = Role::Follower;
+        self.leader_id = Some(leader_id);
+        self.reset_election_deadline();
+    }
+
+    async fn broadcast_append(&mut self) -> CCFSResult<()> {
+        let peers = self.peers.keys().copied().collect::<Vec<_>>();
+        for peer in peers {
+            self.send_append(peer).await?;
+        }
+        self.heartbeat_deadline = Instant::now() + self.heartbeat_interval;
+        Ok(())
+    }
+
+    async fn send_append(&mut self, peer: u64) -> CCFSResult<()> {
+        let next_seq = self.next_seq.get(&peer).copied().unwrap_or(1);
+        let prev_log_seq = next_seq - 1;
+        match self.log.term_at(prev_log_seq) {
+            Some(prev_log_term) => {
+                let entries = self
+                    .log
+                    .entries_from(next_seq)
+                    .iter()
+                    .take(MAX_ENTRIES_PER_MESSAGE)
+                    .cloned()
+                    .collect();
+                let message = RaftMessage::AppendEntries {
+                    term: self.term,
+                    prev_log_seq,
+                    prev_log_term,
+                    entries,
+                    leader_commit: self.commit_index,
+                };
+                self.send(peer, message);
+            }
+            None => {
+                // the entries which the follower is missing are already compacted
+                let snapshot = load_snapshot(&self.snapshot_path).await?;
+                self.next_seq.insert(peer, snapshot.last_op_seq + 1);
+                let term = self.term;
+                self.send(peer, RaftMessage::InstallSnapshot { term, snapshot });
+            }
+        }
+        Ok(())
+    }
+
+    fn respond_append(&self, to: u64, success: bool, last_seq: u64) {
+        let term = self.term;
+        let message = RaftMessage::AppendResponse {
+            term,
+            success,
+            last_seq,
+        };
+        self.send(to, message);
+    }
+
+    /// Commits the latest entry from the current term which is stored on the majority of the nodes
+    fn update_commit_index(&mut self) {
+        for seq in (self.commit_index + 1..=self.log.last_seq()).rev() {
+            if self.log.term_at(seq) != Some(self.term) {
+                break;
+            }
+            let replicas = 1 + self.match_seq.values().filter(|s| **s >= seq).count();
+            if self.has_majority(replicas) {
+                self.set_commit_index(seq);
+                break;
+            }
+        }
+    }
+
+    fn set_commit_index(&mut self, seq: u64) {
+        self.commit_index = seq;
+        let _ = self.commit_sender.send(seq);
+    }
+
+    fn has_majority(&self, count: usize) -> bool {
+        count * 2 > self.peers.len() + 1
+    }
+
+    fn reset_election_deadline(&mut self) {
+        let min = self.election_timeout.as_millis() as u64;
+        let timeout = rand::thread_rng().gen_range(min..=2 * min);
+        self.election_deadline = Instant::now() + Duration::from_millis(timeout);
+    }
+
+    fn send(&self, to: u64, message: RaftMessage) {
+        // the receiving end is dropped only when the server is shutting down
+        let _ = self.outbox.send((to, message));
+    }
+
+    async fn persist_hard_state(&self) -> CCFSResult<()> {
+        let state = HardState {
+            term: self.term,
+            voted_for: self.voted_for,
+        };
+        let temp_path = self.state_path.with_extension("tmp");
+        write(&temp_path, bincode::serialize(&state).unwrap())
+            .await
+            .map_err(|source| BaseError::Write {
+                path: temp_path.clone(),
+                source,
+            })?;
+        rename(&temp_path, &self.state_path)
+            .await
+            .map_err(|source| BaseError::Rename {
+                from: temp_path,
+                to: self.state_path.clone(),
+                source,
+            })?;
+        Ok(())
+    }
+}
+
+async fn load_hard_state(path: &Path) -> CCFSResult<HardState> {
+    if !path.exists() {
+        return Ok(HardState::default());
+    }
+    let content = read(path).await.map_err(|source| BaseError::Read {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    Ok(bincode::deserialize(&content).context(DeserializeRaftState)?)
+}
+
+/// Appends the operation to the leader's log, and waits until it's
+/// replicated to the majority of the nodes
+pub async fn commit(raft: &Raft, operation: Operation) -> CCFSResult<()> {
+    let (seq, term, mut commits) = {
+        let mut raft = raft.lock().await;
+        let seq = raft.propose(operation).await?;
+        (seq, raft.term(), raft.subscribe())
+    };
+    let committed = timeout(COMMIT_TIMEOUT, async {
+        while *commits.borrow() < seq {
+            if commits.changed().await.is_err() {
+                break;
+            }
+        }
+    })
+    .await;
+    if committed.is_err() {
+        return Err(CommitTimeout.build().into());
+    }
+    // a new leader could replace the entry if this node lost the leadership in the meantime,
+    // the term of the entry isn't known anymore once it's compacted into the snapshot
+    match raft.lock().await.log().term_at(seq) {
+        Some(entry_term) if entry_term != term => Err(NotLeader { location: None }.build().into()),
+        _ => Ok(()),
+    }
+}
+
+/// Serializes the changes of the metadata on the leader. The preconditions of a change
+/// are checked while the guard is held, and stay valid until the change is applied
+pub async fn lock_writes(raft: &Raft) -> OwnedMutexGuard<()> {
+    let writes = raft.lock().await.writes.clone();
+    writes.lock_owned().await
+}
+
+/// Commits the operation without holding the metadata locks, then applies the committed
+/// entries under fresh locks, and returns the chunk replicas which should be deleted
+pub async fn commit_and_apply(
+    raft: &Raft,
+    operation: Operation,
+    tree: &FileMetadataTree,
+    files: &FilesMap,
+    chunks: &ChunksMap,
+) -> CCFSResult<Vec<Chunk>> {
+    commit(raft, operation).await?;
+    Ok(lock_and_apply(raft, tree, files, chunks).await)
+}
+
+/// Applies the committed entries which weren't applied yet under the metadata locks
+pub async fn lock_and_apply(
+    raft: &Raft,
+    tree: &FileMetadataTree,
+    files: &FilesMap,
+    chunks: &ChunksMap,
+) -> Vec<Chunk> {
+    let mut tree = tree.write().await;
+    let mut files = files.write().await;
+    let mut chunks = chunks.write().await;
+    apply_committed(raft, &mut tree, &mut files, &mut chunks).await
+}
+
+/// Applies the committed entries which weren't applied yet, and returns
+/// the chunk replicas which should be deleted from the chunk servers
+pub async fn apply_committed(
+    raft: &Raft,
+    tree: &mut FileMetadata,
+    files: &mut Files,
+    chunks: &mut Chunks,
+) -> Vec<Chunk> {
+    let (snapshot, entries) = raft.lock().await.take_committed();
+    if let Some(snapshot) = snapshot {
+        *tree = snapshot.tree;
+        *files = snapshot.files;
+        *chunks = snapshot.chunks;
+    }
+    let mut removed_chunks = Vec::new();
+    for entry in entries {
+        match entry.operation.apply(tree, files, chunks) {
+            Ok(removed) => removed_chunks.extend(removed),
+            // the operations are deterministic, so they fail on all nodes in the same way
+            Err(err) => println!("Couldn't apply operation {}: {:?}", entry.seq, err), // TODO: replace with logger
+        }
+    }
+    removed_chunks
+}
@@ -0,0 +1,193 @@
+//! Disaster recovery of the metadata, from the chunk metadata recorded on the chunk servers
+
+use crate::errors::*;
+use crate::jobs::snapshot::{write_snapshot, Snapshot};
+use crate::oplog::read_entries;
+use crate::server_config::ServerConfig;
+use actix_web::client::Client;
+use ccfs_commons::http_utils::read_body;
+use ccfs_commons::CHUNK_SIZE;
+use ccfs_commons::{errors::Error as BaseError, result::CCFSResult};
+use ccfs_commons::{Chunk, ChunkMetadata, FileInfo, FileMetadata, FileStatus, StoredChunk};
+use snafu::ResultExt;
+use std::collections::{BTreeMap, HashSet};
+use tempfile::tempdir_in;
+use uuid::Uuid;
+
+/// Max size of the chunk metadata list returned by a single chunk server
+const METADATA_RESPONSE_LIMIT: usize = 256 * 1024 * 1024;
+
+/// Rebuilds the metadata from the chunks stored on the listed chunk servers, and
+/// writes it as the snapshot. It fails when the snapshot exists, or any of the
+/// servers is unreachable, since the chunks missing from the rebuilt metadata
+/// would be removed by the garbage collection afterwards
+pub async fn recover(config: &ServerConfig, chunk_servers: &[String]) -> CCFSResult<Snapshot> {
+    let snapshot_path = config.snapshot_path();
+    if snapshot_path.exists() {
+        return Err(SnapshotExists {
+            path: snapshot_path,
+        }
+        .build()
+        .into());
+    }
+    let client = Client::new();
+    let mut stored = Vec::new();
+    for address in chunk_servers {
+        stored.extend(fetch_stored_chunks(&client, address).await?);
+    }
+    let mut snapshot = rebuild_metadata(stored);
+    // the remaining log entries were made on top of the lost snapshot, they're replaced by it
+    if let Some(last) = read_entries(&config.oplog_path()).await?.last() {
+        snapshot.last_op_seq = last.seq;
+        snapshot.last_op_term = last.term;
+    }
+    let temp_dir = tempdir_in(&config.snapshot_dir_path).map_err(|source| BaseError::Create {
+        path: config.snapshot_dir_path.clone(),
+        source,
+    })?;
+    write_snapshot(
+        &snapshot_path,
+        &temp_dir.path().join("tmp_snapshot"),
+        &snapshot,
+    )
+    .await?;
+    Ok(snapshot)
+}
+
+async fn fetch_stored_chunks(client: &Client, address: &str) -> CCFSResult<Vec<StoredChunk>> {
+    let url = format!("{}/api/chunks/metadata", address);
+    let mut resp = client
+        .get(&url)
+        .send()
+        .await
+        .map_err(|source| BaseError::FailedRequest { url, source })?;
+    if !resp.status().is_success() {
+        let response = read_body(resp).await?;
+        return Err(BaseError::Unsuccessful { response }.into());
+    }
+    Ok(resp
+        .json()
+        .limit(METADATA_RESPONSE_LIMIT)
+        .await
+        .context(ParseJson)?)
+}
+
+/// A rebuilt file, with the replicas of its chunks
+type FileVersion = (FileMetadata, Vec<Chunk>);
+
+/// Rebuilds the tree, files and chunks maps from the stored chunks.
+///
+/// When multiple files were stored at the same path, the one with the highest version
+/// is added to the tree, and the other ones are kept as its previous versions. The chunks
+/// which aren't stored on any server get new ids, so they are reported as lost by the replication job
+pub fn rebuild_metadata(stored: Vec<StoredChunk>) -> Snapshot {
+    let mut stored_files: BTreeMap<Uuid, (ChunkMetadata, Vec<(usize, Chunk)>)> = BTreeMap::new();
+    for StoredChunk { chunk, metadata } in stored {
+        let index = metadata.index;
+        let (file_metadata, chunks) = stored_files
+            .entry(chunk.file_id)
+            .or_insert_with(|| (metadata.clone(), Vec::new()));
+        // the chunks of the streamed files record the size written so far
+        let is_newer =
+            (metadata.version, metadata.size) > (file_metadata.version, file_metadata.size);
+        if is_newer {
+            *file_metadata = metadata;
+        }
+        chunks.push((index, chunk));
+    }
+
+    let mut versions: BTreeMap<(String, String), Vec<FileVersion>> = BTreeMap::new();
+    for (file_id, (metadata, stored_chunks)) in stored_files {
+        let num_of_chunks = (metadata.size / CHUNK_SIZE + 1) as usize;
+        let mut chunk_ids = vec![None; num_of_chunks];
+        for (index, chunk) in stored_chunks.iter() {
+            if let Some(id) = chunk_ids.get_mut(*index) {
+                id.get_or_insert(chunk.id);
+            }
+        }
+        let chunk_ids = chunk_ids
+            .into_iter()
+            .map(|id| id.unwrap_or_else(Uuid::new_v4))
+            .collect::<Vec<_>>();
+        let replicas = stored_chunks
+            .into_iter()
+            .filter(|(index, chunk)| chunk_ids.get(*index) == Some(&chunk.id))
+            .map(|(_, chunk)| chunk)
+            .collect();
+        let file = create_file(file_id, &metadata, chunk_ids);
+        versions
+            .entry((metadata.path, metadata.name))
+            .or_insert_with(Vec::new)
+            .push((file, replicas));
+    }
+
+    let mut snapshot = Snapshot::default();
+    for ((path, name), mut files) in versions {
+        // the latest version first, like the previous versions are ordered
+        files.sort_by(|(a, _), (b, _)| b.version.cmp(&a.version));
+        let (mut latest, latest_replicas) = files.remove(0);
+        if let FileInfo::File {
+            kept_versions,
+            versions,
+            ..
+        } = &mut latest.file_info
+        {
+            *kept_versions = files.len();
+            *versions = files.iter().map(|(file, _)| file.clone()).collect();
+        }
+        if insert_file(&mut snapshot.tree, &path, latest.clone()).is_err() {
+            // TODO: replace with logger
+            println!("Couldn't recover file '{}' in '{}'", name, path);
+            continue;
+        }
+        files.insert(0, (latest, latest_replicas));
+        for (file, replicas) in files {
+            for chunk in replicas {
+                snapshot
+                    .chunks
+                    .entry(chunk.id)
+                    .or_insert_with(HashSet::new)
+                    .insert(chunk);
+            }
+            if let FileInfo::File { id, .. } = &file.file_info {
+                snapshot.files.insert(*id, (path.clone(), file));
+            }
+        }
+    }
+    snapshot
+}
+
+fn create_file(id: Uuid, metadata: &ChunkMetadata, chunks: Vec<Uuid>) -> FileMetadata {
+    let mut file = FileMetadata::create_file(metadata.name.clone(), metadata.size, Vec::new());
+    file.version = metadata.version;
+    file.file_info = FileInfo::File {
+        id,
+        size: metadata.size,
+        num_of_completed_chunks: chunks.len(),
+        chunks,
+        status: FileStatus::Completed,
+        replicas: metadata.replicas,
+        kept_versions: 0,
+        versions: Vec::new(),
+        expected_version: None,
+    };
+    file
+}
+
+/// Inserts the file into the directory at the path, creating the missing directories
+fn insert_file(tree: &mut FileMetadata, path: &str, file: FileMetadata) -> CCFSResult<()> {
+    let mut curr = tree;
+    for segment in path.split('/').filter(|s| !s.is_empty()) {
+        curr = curr
+            .children_mut()?
+            .entry(segment.into())
+            .or_insert_with(|| FileMetadata::create_dir(segment.into()));
+    }
+    let children = curr.children_mut()?;
+    if let Some(existing) = children.get(&file.name) {
+        // a directory with the same name was recovered from the other files' paths
+        existing.chunks()?;
+    }
+    children.insert(file.name.clone(), file);
+    Ok(())
+}
@@ -1,14 +1,19 @@
-use crate::ws::cluster::Cluster;
+use crate::inventory::reconcile_chunks;
+use crate::operations::{check_commit, check_kind, check_overwrite, check_version};
+use crate::operations::{get_open_file, get_upload, Operation};
+use crate::placement::{self, Placement};
+use crate::raft::{commit, commit_and_apply, lock_and_apply, lock_writes};
 use crate::ws::server::CCFSWebSocket;
-use crate::{errors::*, ChunksMap, FileMetadataTree, FilesMap, ServersMap};
-use actix::Addr;
+use crate::{errors::*, ChunksMap, DeletionQueue, FileMetadataTree, FilesMap, Raft};
+use crate::{ReplicationStatus, ServersMap};
+use actix_web::http::header::{ETAG, IF_MATCH};
 use actix_web::web::{Data, Path, Payload};
-use actix_web::{get, post, web, HttpRequest, HttpResponse};
+use actix_web::{delete, get, post, put, web, HttpRequest, HttpResponse};
 use actix_web_actors::ws;
 use ccfs_commons::path::evaluate_path;
-use ccfs_commons::result::CCFSResult;
-use ccfs_commons::{Chunk, ChunkServer, FileInfo, FileMetadata, FileStatus, ROOT_DIR};
-use chrono::{DateTime, Utc};
+use ccfs_commons::{errors::Error as BaseError, result::CCFSResult};
+use ccfs_commons::{Chunk, ChunkReport, ChunkServer, FileInfo, FileMetadata, FileUpload};
+use ccfs_commons::{FileStatus, UploadStatus, ROOT_DIR};
 use std::collections::{HashMap, HashSet};
 use uuid::Uuid;
 use web::{Json, Query};
@@ -34,48 +39,250 @@ pub async fn get_server(id: Path<Uuid>, servers: Data<ServersMap>) -> CCFSResult
     Ok(HttpResponse::Ok().json(server))
 }
 
-/// Registers a new active chunk server or updates the latest_ping_time
+/// Registers a new active chunk server or updates its latest_ping_time and reported stats
 #[post("/ping")]
 pub async fn chunk_server_ping(
     payload: ChunkServer,
     servers: Data<ServersMap>,
 ) -> CCFSResult<HttpResponse> {
     let mut servers_map = servers.write().await;
-    let server = servers_map.entry(payload.id).or_insert_with(|| payload);
-    server.latest_ping_time = DateTime::from_utc(Utc::now().naive_utc(), Utc);
+    servers_map.insert(payload.id, payload);
     Ok(HttpResponse::Ok().finish())
 }
 
-/// Creates a file entity with basic file info
+/// Creates a file entity with basic file info, and returns the servers
+/// where its chunks should be uploaded
 #[post("/files/upload")]
+#[allow(clippy::too_many_arguments)]
 pub async fn create_file(
+    request: HttpRequest,
     file_info: Json<FileMetadata>,
     params: Query<HashMap<String, String>>,
-    files: Data<FilesMap>,
     file_metadata_tree: Data<FileMetadataTree>,
+    files: Data<FilesMap>,
+    chunks: Data<ChunksMap>,
+    servers: Data<ServersMap>,
+    raft: Data<Raft>,
+    placement: Data<Placement>,
+    deletion_queue: Data<DeletionQueue>,
 ) -> CCFSResult<HttpResponse> {
-    let file = file_info.into_inner();
-    let mut tree = file_metadata_tree.write().await;
-    let target_path = match params.get("path") {
-        Some(path) if !path.is_empty() => evaluate_path(ROOT_DIR, &tree, path)?,
-        _ => String::new(),
+    ensure_leader(&request, &raft).await?;
+    let mut file = file_info.into_inner();
+    if let FileInfo::File {
+        replicas: Some(0), ..
+    } = file.file_info
+    {
+        return Err(InvalidReplicas.build().into());
+    }
+    let expected = expected_version(&request)?;
+    // the file upload fails if the overwritten file is overwritten by another upload first
+    if let FileInfo::File {
+        expected_version, ..
+    } = &mut file.file_info
+    {
+        *expected_version = expected;
+    }
+    let writes = lock_writes(&raft).await;
+    let target_path = {
+        let tree = file_metadata_tree.read().await;
+        let target_path = match params.get("path") {
+            Some(path) if !path.is_empty() => evaluate_path(ROOT_DIR, &tree, path)?,
+            _ => String::new(),
+        };
+        let target = tree.traverse(&target_path)?;
+        check_kind(target, &file)?;
+        let existing = target
+            .children()?
+            .get(&file.name)
+            .map_or(0, |node| node.version);
+        check_version(expected, existing)?;
+        target_path
     };
-    let target = tree.traverse_mut(&target_path)?;
-    match &file.file_info {
-        FileInfo::Directory { .. } => {
-            target
-                .children_mut()?
-                .insert(file.name.clone(), file.clone());
-        }
-        FileInfo::File { id, .. } => {
-            let mut files_map = files.write().await;
-            files_map.insert(*id, (target_path, file.clone()));
+    let operation = Operation::CreateFile {
+        path: target_path.clone(),
+        file: file.clone(),
+    };
+    let removed_chunks =
+        commit_and_apply(&raft, operation, &file_metadata_tree, &files, &chunks).await?;
+    // the file which overwrites an existing one gets the next version
+    let created = match &file.file_info {
+        FileInfo::File { id, .. } => files.read().await.get(id).map(|(_, file)| file.clone()),
+        FileInfo::Directory { .. } => None,
+    };
+    drop(writes);
+    deletion_queue.write().await.extend(removed_chunks);
+    let file = created.unwrap_or(file);
+    let placement = match &file.file_info {
+        FileInfo::File {
+            chunks, replicas, ..
+        } => {
+            let candidates = placement::candidates(servers.read().await.values());
+            let replicas = replicas.unwrap_or(placement.replication_factor);
+            placement.policy.place(&candidates, chunks, replicas)
         }
+        FileInfo::Directory { .. } => Vec::new(),
+    };
+    Ok(HttpResponse::Ok().json(&FileUpload {
+        file,
+        path: target_path,
+        placement,
+    }))
+}
+
+/// Returns the upload of the created file, with its chunks which are already
+/// uploaded and the servers where the remaining ones should be uploaded
+#[get("/files/{file_id}/upload")]
+pub async fn get_file_upload(
+    file_id: Path<Uuid>,
+    files: Data<FilesMap>,
+    chunks: Data<ChunksMap>,
+    servers: Data<ServersMap>,
+    placement: Data<Placement>,
+) -> CCFSResult<HttpResponse> {
+    let (path, file, completed, missing) = {
+        let files_map = files.read().await;
+        let chunks_map = chunks.read().await;
+        let (path, file) = files_map.get(&file_id).ok_or_else(|| NotFound.build())?;
+        let (completed, missing): (Vec<Uuid>, Vec<Uuid>) = file
+            .chunks()?
+            .iter()
+            .partition(|id| chunks_map.get(id).map_or(false, |set| !set.is_empty()));
+        (path.clone(), file.clone(), completed, missing)
+    };
+    let replicas = match &file.file_info {
+        FileInfo::File { replicas, .. } => replicas.unwrap_or(placement.replication_factor),
+        FileInfo::Directory { .. } => placement.replication_factor,
+    };
+    let candidates = placement::candidates(servers.read().await.values());
+    let placement = placement.policy.place(&candidates, &missing, replicas);
+    Ok(HttpResponse::Ok().json(&UploadStatus {
+        upload: FileUpload {
+            file,
+            path,
+            placement,
+        },
+        completed,
+    }))
+}
+
+/// Appends the chunks to the file which is still being written,
+/// and returns the servers where they should be uploaded
+#[post("/files/{file_id}/chunks")]
+#[allow(clippy::too_many_arguments)]
+pub async fn append_file_chunks(
+    request: HttpRequest,
+    file_id: Path<Uuid>,
+    chunk_ids: Json<Vec<Uuid>>,
+    file_metadata_tree: Data<FileMetadataTree>,
+    files: Data<FilesMap>,
+    chunks: Data<ChunksMap>,
+    servers: Data<ServersMap>,
+    raft: Data<Raft>,
+    placement: Data<Placement>,
+    deletion_queue: Data<DeletionQueue>,
+) -> CCFSResult<HttpResponse> {
+    ensure_leader(&request, &raft).await?;
+    let chunk_ids = chunk_ids.into_inner();
+    let writes = lock_writes(&raft).await;
+    let replicas = match &get_open_file(&*files.read().await, &file_id)?.1.file_info {
+        FileInfo::File { replicas, .. } => *replicas,
+        FileInfo::Directory { .. } => None,
+    };
+    let operation = Operation::AppendChunks {
+        file_id: *file_id,
+        chunks: chunk_ids.clone(),
+    };
+    let removed_chunks =
+        commit_and_apply(&raft, operation, &file_metadata_tree, &files, &chunks).await?;
+    drop(writes);
+    deletion_queue.write().await.extend(removed_chunks);
+    let candidates = placement::candidates(servers.read().await.values());
+    let replicas = replicas.unwrap_or(placement.replication_factor);
+    Ok(HttpResponse::Ok().json(placement.policy.place(&candidates, &chunk_ids, replicas)))
+}
+
+/// Sets the final size of the file which was being written, it's added
+/// to the tree once all of its chunks are uploaded
+#[post("/files/{file_id}/commit")]
+#[allow(clippy::too_many_arguments)]
+pub async fn commit_file(
+    request: HttpRequest,
+    file_id: Path<Uuid>,
+    params: Query<HashMap<String, String>>,
+    file_metadata_tree: Data<FileMetadataTree>,
+    files: Data<FilesMap>,
+    chunks: Data<ChunksMap>,
+    raft: Data<Raft>,
+    deletion_queue: Data<DeletionQueue>,
+) -> CCFSResult<HttpResponse> {
+    ensure_leader(&request, &raft).await?;
+    let size = params
+        .get("size")
+        .and_then(|size| size.parse().ok())
+        .ok_or_else(|| MissingParam.build())?;
+    let writes = lock_writes(&raft).await;
+    let overwrite = {
+        let tree = file_metadata_tree.read().await;
+        let files_map = files.read().await;
+        check_commit(&files_map, &file_id, size)?;
+        check_overwrite(&tree, &files_map, &file_id)
+    };
+    if let Err(err) = overwrite {
+        let operation = Operation::CancelUploads {
+            file_ids: vec![*file_id],
+        };
+        let removed =
+            commit_and_apply(&raft, operation, &file_metadata_tree, &files, &chunks).await?;
+        drop(writes);
+        deletion_queue.write().await.extend(removed);
+        return Err(err);
     }
+    let operation = Operation::CommitFile {
+        file_id: *file_id,
+        size,
+    };
+    let removed_chunks =
+        commit_and_apply(&raft, operation, &file_metadata_tree, &files, &chunks).await?;
+    let file = files
+        .read()
+        .await
+        .get(&file_id)
+        .map(|(_, file)| file.clone());
+    drop(writes);
+    deletion_queue.write().await.extend(removed_chunks);
+    let file = file.ok_or_else(|| NotFound.build())?;
     Ok(HttpResponse::Ok().json(&file))
 }
 
-/// Returns the file info
+/// Cancels the upload of the file which is still in progress, and schedules
+/// the deletion of its uploaded chunks from the chunk servers
+#[delete("/files/{file_id}/upload")]
+pub async fn cancel_upload(
+    request: HttpRequest,
+    file_id: Path<Uuid>,
+    file_metadata_tree: Data<FileMetadataTree>,
+    files: Data<FilesMap>,
+    chunks: Data<ChunksMap>,
+    raft: Data<Raft>,
+    deletion_queue: Data<DeletionQueue>,
+) -> CCFSResult<HttpResponse> {
+    ensure_leader(&request, &raft).await?;
+    let writes = lock_writes(&raft).await;
+    let (_, mut file) = get_upload(&*files.read().await, &file_id)?.clone();
+    let operation = Operation::CancelUploads {
+        file_ids: vec![*file_id],
+    };
+    let removed_chunks =
+        commit_and_apply(&raft, operation, &file_metadata_tree, &files, &chunks).await?;
+    drop(writes);
+    deletion_queue.write().await.extend(removed_chunks);
+    if let FileInfo::File { status, .. } = &mut file.file_info {
+        *status = FileStatus::Canceled;
+    }
+    Ok(HttpResponse::Ok().json(&file))
+}
+
+/// Returns the file info, or the info of its previous version when `version` is set
 #[get("/files")]
 pub async fn get_file(
     params: Query<HashMap<String, String>>,
@@ -87,46 +294,261 @@ pub async fn get_file(
         _ => String::new(),
     };
     let files = files_tree.traverse(&path)?;
-    Ok(HttpResponse::Ok().json(files))
+    match params.get("version") {
+        Some(version) => {
+            let file = version
+                .parse()
+                .ok()
+                .and_then(|version| files.version(version))
+                .ok_or_else(|| InvalidVersion { version }.build())?;
+            Ok(file_response(file))
+        }
+        None => Ok(file_response(files)),
+    }
+}
+
+/// Returns the file info, with its version as the entity tag
+fn file_response(file: &FileMetadata) -> HttpResponse {
+    HttpResponse::Ok()
+        .insert_header((ETAG, format!("\"{}\"", file.version)))
+        .json(file)
+}
+
+/// Removes the file (or the whole directory when `recursive=true`) from the tree,
+/// and schedules the deletion of its chunks from the chunk servers
+#[delete("/files")]
+pub async fn remove_file(
+    request: HttpRequest,
+    params: Query<HashMap<String, String>>,
+    file_metadata_tree: Data<FileMetadataTree>,
+    files: Data<FilesMap>,
+    chunks: Data<ChunksMap>,
+    raft: Data<Raft>,
+    deletion_queue: Data<DeletionQueue>,
+) -> CCFSResult<HttpResponse> {
+    ensure_leader(&request, &raft).await?;
+    let recursive = matches!(params.get("recursive").map(String::as_str), Some("true"));
+    let writes = lock_writes(&raft).await;
+    let (path, removed) = {
+        let tree = file_metadata_tree.read().await;
+        let path = match params.get("path") {
+            Some(path) if !path.is_empty() => evaluate_path(ROOT_DIR, &tree, path)?,
+            _ => String::new(),
+        };
+        if path.is_empty() || path == ROOT_DIR {
+            let msg = "Cannot remove the root directory".into();
+            return Err(BaseError::InvalidPath { msg }.into());
+        }
+        let removed = tree.traverse(&path)?.clone();
+        check_version(expected_version(&request)?, removed.version)?;
+        if let FileInfo::Directory { .. } = removed.file_info {
+            if !recursive {
+                return Err(IsDirectory { path }.build().into());
+            }
+        }
+        (path, removed)
+    };
+    let operation = Operation::RemoveFile { path };
+    let removed_chunks =
+        commit_and_apply(&raft, operation, &file_metadata_tree, &files, &chunks).await?;
+    drop(writes);
+    deletion_queue.write().await.extend(removed_chunks);
+    Ok(HttpResponse::Ok().json(&removed))
+}
+
+/// Changes the number of replicas of the file chunks,
+/// the server default is used again when `replicas` isn't set
+#[put("/files/replicas")]
+pub async fn set_file_replicas(
+    request: HttpRequest,
+    params: Query<HashMap<String, String>>,
+    file_metadata_tree: Data<FileMetadataTree>,
+    files: Data<FilesMap>,
+    chunks: Data<ChunksMap>,
+    raft: Data<Raft>,
+    deletion_queue: Data<DeletionQueue>,
+) -> CCFSResult<HttpResponse> {
+    ensure_leader(&request, &raft).await?;
+    let expected = expected_version(&request)?;
+    let replicas = match params.get("replicas") {
+        Some(replicas) => match replicas.parse() {
+            Ok(0) | Err(_) => return Err(InvalidReplicas.build().into()),
+            Ok(replicas) => Some(replicas),
+        },
+        None => None,
+    };
+    let writes = lock_writes(&raft).await;
+    let path = {
+        let tree = file_metadata_tree.read().await;
+        let path = match params.get("path") {
+            Some(path) if !path.is_empty() => evaluate_path(ROOT_DIR, &tree, path)?,
+            _ => return Err(MissingParam.build().into()),
+        };
+        let file = tree.traverse(&path)?;
+        file.chunks()?;
+        check_version(expected, file.version)?;
+        path
+    };
+    let operation = Operation::SetReplicas {
+        path: path.clone(),
+        replicas,
+    };
+    let removed_chunks =
+        commit_and_apply(&raft, operation, &file_metadata_tree, &files, &chunks).await?;
+    let file = file_metadata_tree.read().await.traverse(&path)?.clone();
+    drop(writes);
+    deletion_queue.write().await.extend(removed_chunks);
+    Ok(HttpResponse::Ok().json(&file))
+}
+
+/// Changes the number of the previous versions which are kept when the file is overwritten,
+/// the kept versions over the new number are removed
+#[put("/files/versions")]
+pub async fn set_file_versions(
+    request: HttpRequest,
+    params: Query<HashMap<String, String>>,
+    file_metadata_tree: Data<FileMetadataTree>,
+    files: Data<FilesMap>,
+    chunks: Data<ChunksMap>,
+    raft: Data<Raft>,
+    deletion_queue: Data<DeletionQueue>,
+) -> CCFSResult<HttpResponse> {
+    ensure_leader(&request, &raft).await?;
+    let expected = expected_version(&request)?;
+    let kept_versions = params
+        .get("versions")
+        .and_then(|versions| versions.parse().ok())
+        .ok_or_else(|| MissingParam.build())?;
+    let writes = lock_writes(&raft).await;
+    let path = {
+        let tree = file_metadata_tree.read().await;
+        let path = match params.get("path") {
+            Some(path) if !path.is_empty() => evaluate_path(ROOT_DIR, &tree, path)?,
+            _ => return Err(MissingParam.build().into()),
+        };
+        let file = tree.traverse(&path)?;
+        file.chunks()?;
+        check_version(expected, file.version)?;
+        path
+    };
+    let operation = Operation::SetKeptVersions {
+        path: path.clone(),
+        kept_versions,
+    };
+    let removed_chunks =
+        commit_and_apply(&raft, operation, &file_metadata_tree, &files, &chunks).await?;
+    let file = file_metadata_tree.read().await.traverse(&path)?.clone();
+    drop(writes);
+    deletion_queue.write().await.extend(removed_chunks);
+    Ok(HttpResponse::Ok().json(&file))
 }
 
 /// Notifies the metadata server to mark the chunk as completed
 #[post("/chunk/completed")]
 pub async fn signal_chuck_upload_completed(
+    request: HttpRequest,
     chunk: Json<Chunk>,
     file_metadata_tree: Data<FileMetadataTree>,
     files: Data<FilesMap>,
     chunks: Data<ChunksMap>,
+    raft: Data<Raft>,
+    deletion_queue: Data<DeletionQueue>,
 ) -> CCFSResult<HttpResponse> {
-    let mut chunks = chunks.write().await;
-    let mut files = files.write().await;
-    let (path, file) = files
-        .get_mut(&chunk.file_id)
-        .ok_or_else(|| NotFound.build())?;
-    let chunk_set = chunks.entry(chunk.id).or_insert_with(HashSet::new);
-    if chunk_set.is_empty() {
-        if let FileInfo::File {
-            num_of_completed_chunks,
-            chunks: file_chunks,
-            status,
-            ..
-        } = &mut file.file_info
-        {
-            *num_of_completed_chunks += 1;
-            if *num_of_completed_chunks == file_chunks.len() {
-                *status = FileStatus::Completed;
-                let mut tree = file_metadata_tree.write().await;
-                let target_dir = tree.traverse_mut(path).map_err(|_| NotFound.build())?;
-                target_dir
-                    .children_mut()?
-                    .insert(file.name.clone(), file.clone());
-            }
+    ensure_leader(&request, &raft).await?;
+    let writes = lock_writes(&raft).await;
+    let overwrite = {
+        let tree = file_metadata_tree.read().await;
+        let files = files.read().await;
+        if !files.contains_key(&chunk.file_id) {
+            return Err(NotFound.build().into());
+        }
+        check_overwrite(&tree, &files, &chunk.file_id)
+    };
+    let (removed_chunks, conflict) = match overwrite {
+        Ok(()) => {
+            let operation = Operation::CompleteChunk { chunk: *chunk };
+            let removed =
+                commit_and_apply(&raft, operation, &file_metadata_tree, &files, &chunks).await?;
+            (removed, None)
         }
+        Err(err) => {
+            let operation = Operation::CancelUploads {
+                file_ids: vec![chunk.file_id],
+            };
+            let mut removed =
+                commit_and_apply(&raft, operation, &file_metadata_tree, &files, &chunks).await?;
+            // the rejected replica isn't registered, so it's deleted separately
+            removed.push(*chunk);
+            (removed, Some(err))
+        }
+    };
+    drop(writes);
+    deletion_queue.write().await.extend(removed_chunks);
+    match conflict {
+        Some(err) => Err(err),
+        None => Ok(HttpResponse::Ok().finish()),
     }
-    chunk_set.insert(*chunk);
+}
+
+/// Unregisters the chunk replicas which were found corrupted (and quarantined) by a chunk server
+#[post("/chunks/corrupted")]
+pub async fn report_corrupted_chunks(
+    request: HttpRequest,
+    replicas: Json<Vec<Chunk>>,
+    file_metadata_tree: Data<FileMetadataTree>,
+    files: Data<FilesMap>,
+    chunks: Data<ChunksMap>,
+    raft: Data<Raft>,
+    deletion_queue: Data<DeletionQueue>,
+) -> CCFSResult<HttpResponse> {
+    ensure_leader(&request, &raft).await?;
+    let operation = Operation::UnregisterReplicas {
+        replicas: replicas.into_inner(),
+    };
+    let writes = lock_writes(&raft).await;
+    let removed_chunks =
+        commit_and_apply(&raft, operation, &file_metadata_tree, &files, &chunks).await?;
+    drop(writes);
+    deletion_queue.write().await.extend(removed_chunks);
     Ok(HttpResponse::Ok().finish())
 }
 
+/// Reconciles the chunks map against the inventory of a chunk server, and returns the changes
+#[post("/chunks/report")]
+pub async fn chunk_server_report(
+    request: HttpRequest,
+    report: Json<ChunkReport>,
+    file_metadata_tree: Data<FileMetadataTree>,
+    files: Data<FilesMap>,
+    chunks: Data<ChunksMap>,
+    raft: Data<Raft>,
+    deletion_queue: Data<DeletionQueue>,
+) -> CCFSResult<HttpResponse> {
+    ensure_leader(&request, &raft).await?;
+    let writes = lock_writes(&raft).await;
+    let result = reconcile_chunks(&report, &*files.read().await, &*chunks.read().await);
+    if !result.unregistered.is_empty() {
+        let replicas = result.unregistered.clone();
+        commit(&raft, Operation::UnregisterReplicas { replicas }).await?;
+    }
+    if !result.registered.is_empty() {
+        let replicas = result.registered.clone();
+        commit(&raft, Operation::RegisterReplicas { replicas }).await?;
+    }
+    let removed_chunks = lock_and_apply(&raft, &file_metadata_tree, &files, &chunks).await;
+    drop(writes);
+    deletion_queue.write().await.extend(removed_chunks);
+    if !result.unknown.is_empty() {
+        // TODO: replace with logger
+        println!(
+            "Server {} stores {} chunks which don't belong to any file",
+            report.server_id,
+            result.unknown.len()
+        );
+    }
+    Ok(HttpResponse::Ok().json(&result))
+}
+
 /// Returns the list of servers which contains the
 /// uploaded chunks for a file
 #[get("/chunks/file/{file_id}")]
@@ -135,8 +557,8 @@ pub async fn get_chunks(
     chunks: Data<ChunksMap>,
     files: Data<FilesMap>,
 ) -> CCFSResult<HttpResponse> {
-    let chunks_map = chunks.read().await;
     let files_map = files.read().await;
+    let chunks_map = chunks.read().await;
     let (_, file) = files_map.get(&file_id).ok_or_else(|| NotFound.build())?;
     Ok(HttpResponse::Ok().json(
         file.chunks()?
@@ -147,10 +569,66 @@ pub async fn get_chunks(
     ))
 }
 
-/// Registers the node to the cluster by openning a ws connection to the leader
+/// Returns the subset of the given chunks which are still referenced by some file,
+/// either completed or still being uploaded.
+///
+/// The chunk servers remove the chunks which aren't listed, so only the leader answers,
+/// once its metadata is complete. Empty metadata is never considered complete
+#[post("/chunks/referenced")]
+pub async fn get_referenced_chunks(
+    request: HttpRequest,
+    stored_chunks: Json<Vec<Chunk>>,
+    file_metadata_tree: Data<FileMetadataTree>,
+    files: Data<FilesMap>,
+    raft: Data<Raft>,
+) -> CCFSResult<HttpResponse> {
+    ensure_leader(&request, &raft).await?;
+    if !raft.lock().await.is_caught_up() {
+        return Err(MetadataNotReady.build().into());
+    }
+    let mut referenced = HashSet::new();
+    {
+        let tree = file_metadata_tree.read().await;
+        if tree.children()?.is_empty() && files.read().await.is_empty() {
+            return Err(MetadataNotReady.build().into());
+        }
+        for file in tree.dfs_iter() {
+            if let FileInfo::File { id, chunks, .. } = &file.file_info {
+                referenced.extend(chunks.iter().map(|chunk_id| (*id, *chunk_id)));
+            }
+        }
+    }
+    {
+        let files_map = files.read().await;
+        for (id, (_, file)) in files_map.iter() {
+            referenced.extend(file.chunks()?.iter().map(|chunk_id| (*id, *chunk_id)));
+        }
+    }
+    Ok(HttpResponse::Ok().json(
+        stored_chunks
+            .iter()
+            .filter(|chunk| referenced.contains(&(chunk.file_id, chunk.id)))
+            .cloned()
+            .collect::<Vec<Chunk>>(),
+    ))
+}
+
+/// Returns the under-replicated, unavailable and lost chunks found by the latest replication job run
+#[get("/admin/chunks")]
+pub async fn get_replication_status(status: Data<ReplicationStatus>) -> CCFSResult<HttpResponse> {
+    Ok(HttpResponse::Ok().json(&*status.read().await))
+}
+
+/// Returns the raft state of the node
+#[get("/status")]
+pub async fn get_raft_status(raft: Data<Raft>) -> CCFSResult<HttpResponse> {
+    Ok(HttpResponse::Ok().json(&raft.lock().await.status()))
+}
+
+/// Accepts the ws connection through which the node with the `node_id` sends its raft messages
 pub async fn join_cluster(
     request: HttpRequest,
-    srv: Data<Addr<Cluster>>,
+    raft: Data<Raft>,
     stream: Payload,
 ) -> Result<HttpResponse, actix_web::Error> {
     let headers = request.headers();
@@ -162,8 +640,34 @@ pub async fn join_cluster(
         .parse()
         .unwrap();
     ws::start(
-        CCFSWebSocket::new(id, srv.get_ref().clone()),
+        CCFSWebSocket::new(id, raft.get_ref().clone()),
         &request,
         stream,
     )
 }
+
+/// Returns the version which the request expects the target file to be at, which is set with
+/// the `If-Match` header. The mutations of the files at the other versions are rejected
+fn expected_version(request: &HttpRequest) -> CCFSResult<Option<usize>> {
+    let value = match request.headers().get(IF_MATCH) {
+        Some(value) => value.to_str().unwrap_or_default(),
+        None => return Ok(None),
+    };
+    // the version can be sent as an entity tag, in quotes
+    match value.trim_matches('"').parse() {
+        Ok(version) => Ok(Some(version)),
+        Err(_) => Err(InvalidPrecondition { value }.build().into()),
+    }
+}
+
+/// Fails with a redirect to the leader when this node isn't the cluster leader
+async fn ensure_leader(request: &HttpRequest, raft: &Raft) -> CCFSResult<()> {
+    let raft = raft.lock().await;
+    if raft.is_leader() {
+        return Ok(());
+    }
+    let location = raft
+        .leader_address()
+        .map(|address| format!("{}{}", address, request.uri()));
+    Err(NotLeader { location }.build().into())
+}
@@ -1,7 +1,11 @@
+use crate::placement::PlacementPolicyKind;
+use crate::raft::{Peer, RaftConfig};
+use crate::DEFAULT_REPLICATION_FACTOR;
 use serde::{Deserialize, Serialize};
 use std::fs::{create_dir_all, File};
 use std::io::{Error, ErrorKind};
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ServerConfig {
@@ -11,6 +15,35 @@ pub struct ServerConfig {
     pub snapshot_dir_path: PathBuf,
     pub snapshot_file_name: String,
     pub replication_interval: u64,
+    /// Number of replicas of each chunk, for the files which don't override it
+    #[serde(default = "default_replication_factor")]
+    pub replication_factor: usize,
+    /// Time (in seconds) after which a server which stopped pinging is considered dead,
+    /// the chunks whose replicas were all stored on dead servers are reported as lost
+    #[serde(default = "default_dead_server_timeout")]
+    pub dead_server_timeout: u64,
+    #[serde(default = "default_deletion_interval")]
+    pub deletion_interval: u64,
+    /// Time (in seconds) after which an upload which stopped making progress is canceled
+    #[serde(default = "default_upload_lease")]
+    pub upload_lease: u64,
+    #[serde(default = "default_upload_expiry_interval")]
+    pub upload_expiry_interval: u64,
+    /// Id of this node in the cluster, a single node setup doesn't have to set it
+    #[serde(default = "default_node_id")]
+    pub node_id: u64,
+    /// The other metadata servers in the cluster
+    #[serde(default)]
+    pub peers: Vec<Peer>,
+    /// Interval (in milliseconds) between the leader's heartbeats
+    #[serde(default = "default_heartbeat_interval")]
+    pub heartbeat_interval: u64,
+    /// Time (in milliseconds) without heartbeats after which a follower starts an election
+    #[serde(default = "default_election_timeout")]
+    pub election_timeout: u64,
+    /// Policy used to pick the servers for the chunks of the new files
+    #[serde(default)]
+    pub placement_policy: PlacementPolicyKind,
 }
 impl ServerConfig {
     pub fn load_config<T: AsRef<Path>>(path: &T) -> std::io::Result<Self> {
@@ -33,6 +66,20 @@ impl ServerConfig {
             error_msg = "snapshot_interval must be greater than 0";
         } else if config.replication_interval == 0 {
             error_msg = "replication_interval must be greater than 0";
+        } else if config.replication_factor == 0 {
+            error_msg = "replication_factor must be greater than 0";
+        } else if config.deletion_interval == 0 {
+            error_msg = "deletion_interval must be greater than 0";
+        } else if config.upload_lease == 0 {
+            error_msg = "upload_lease must be greater than 0";
+        } else if config.upload_expiry_interval == 0 {
+            error_msg = "upload_expiry_interval must be greater than 0";
+        } else if config.heartbeat_interval == 0 {
+            error_msg = "heartbeat_interval must be greater than 0";
+        } else if config.election_timeout <= config.heartbeat_interval {
+            error_msg = "election_timeout must be greater than heartbeat_interval";
+        } else if config.peers.iter().any(|p| p.id == config.node_id) {
+            error_msg = "peers cannot contain the node_id";
         }
         if !error_msg.is_empty() {
             return Err(Error::new(ErrorKind::Other, error_msg));
@@ -58,4 +105,57 @@ impl ServerConfig {
     pub fn snapshot_path(&self) -> PathBuf {
         self.snapshot_dir_path.join(&self.snapshot_file_name)
     }
+
+    pub fn oplog_path(&self) -> PathBuf {
+        self.snapshot_dir_path
+            .join(format!("{}.oplog", self.snapshot_file_name))
+    }
+
+    pub fn raft_state_path(&self) -> PathBuf {
+        self.snapshot_dir_path
+            .join(format!("{}.raft", self.snapshot_file_name))
+    }
+
+    pub fn raft_config(&self) -> RaftConfig {
+        RaftConfig {
+            node_id: self.node_id,
+            peers: self.peers.clone(),
+            heartbeat_interval: Duration::from_millis(self.heartbeat_interval),
+            election_timeout: Duration::from_millis(self.election_timeout),
+            snapshot_path: self.snapshot_path(),
+            state_path: self.raft_state_path(),
+        }
+    }
+}
+
+fn default_replication_factor() -> usize {
+    DEFAULT_REPLICATION_FACTOR
+}
+
+fn default_dead_server_timeout() -> u64 {
+    600
+}
+
+fn default_deletion_interval() -> u64 {
+    5
+}
+
+fn default_upload_lease() -> u64 {
+    3600
+}
+
+fn default_upload_expiry_interval() -> u64 {
+    60
+}
+
+fn default_node_id() -> u64 {
+    1
+}
+
+fn default_heartbeat_interval() -> u64 {
+    50
+}
+
+fn default_election_timeout() -> u64 {
+    300
 }
@@ -1,47 +1,101 @@
-use super::Message as CCFSMessage;
+use super::cluster::Cluster;
+use super::{Connect, Disconnect, Message as CCFSMessage, MAX_FRAME_SIZE};
 use actix::io::SinkWrite;
 use actix::{prelude::*, Context, StreamHandler};
 use actix_codec::Framed;
+use actix_http::ws::Item;
 use awc::{
     error::WsProtocolError,
     ws::{Codec, Frame, Message},
     BoxedSocket,
 };
+use bytes::Bytes;
 use futures::stream::SplitSink;
 
+/// Outgoing connection to a peer, through which the raft messages are sent
 pub struct CCFSWsClient {
+    /// Id of the peer node
+    pub peer_id: u64,
+    pub cluster: Addr<Cluster>,
     pub conn: SinkWrite<Message, SplitSink<Framed<BoxedSocket, Codec>, Message>>,
 }
 
+impl CCFSWsClient {
+    pub fn new(
+        peer_id: u64,
+        cluster: Addr<Cluster>,
+        conn: SinkWrite<Message, SplitSink<Framed<BoxedSocket, Codec>, Message>>,
+    ) -> Self {
+        Self {
+            peer_id,
+            cluster,
+            conn,
+        }
+    }
+}
+
 impl Actor for CCFSWsClient {
     type Context = Context<Self>;
 
-    fn started(&mut self, _ctx: &mut Context<Self>) {
-        println!("Connected");
+    fn started(&mut self, ctx: &mut Context<Self>) {
+        self.cluster.do_send(Connect {
+            id: self.peer_id,
+            addr: ctx.address().recipient(),
+        });
     }
 
     fn stopped(&mut self, _: &mut Context<Self>) {
-        println!("Disconnected");
+        self.cluster.do_send(Disconnect { id: self.peer_id });
     }
 }
 
-/// Handle server websocket messages
-impl StreamHandler<Result<Frame, WsProtocolError>> for CCFSWsClient {
-    fn handle(&mut self, msg: Result<Frame, WsProtocolError>, _ctx: &mut Context<Self>) {
-        if let Ok(Frame::Binary(data)) = msg {
-            match bincode::deserialize::<CCFSMessage>(&data) {
-                Ok(req) => println!("Recieved {:?}", req),
-                Err(err) => println!("couldn't deser into AppendRequest: {}", err),
+/// Handle messages from cluster server, we simply send them to the peer
+impl Handler<CCFSMessage> for CCFSWsClient {
+    type Result = ();
+
+    fn handle(&mut self, msg: CCFSMessage, ctx: &mut Context<Self>) {
+        let data = Bytes::from(bincode::serialize(&msg).expect("failed to serialize ws msg"));
+        let frames = if data.len() <= MAX_FRAME_SIZE {
+            vec![Message::Binary(data)]
+        } else {
+            let parts = (0..data.len())
+                .step_by(MAX_FRAME_SIZE)
+                .map(|start| data.slice(start..data.len().min(start + MAX_FRAME_SIZE)))
+                .collect::<Vec<_>>();
+            let last = parts.len() - 1;
+            parts
+                .into_iter()
+                .enumerate()
+                .map(|(i, part)| match i {
+                    0 => Message::Continuation(Item::FirstBinary(part)),
+                    i if i == last => Message::Continuation(Item::Last(part)),
+                    _ => Message::Continuation(Item::Continue(part)),
+                })
+                .collect()
+        };
+        for frame in frames {
+            if self.conn.write(frame).is_some() {
+                // the connection is closing
+                ctx.stop();
+                return;
             }
         }
     }
+}
 
-    fn started(&mut self, _ctx: &mut Context<Self>) {
-        println!("Connected");
+/// Handle server websocket messages
+impl StreamHandler<Result<Frame, WsProtocolError>> for CCFSWsClient {
+    fn handle(&mut self, msg: Result<Frame, WsProtocolError>, ctx: &mut Context<Self>) {
+        match msg {
+            Ok(Frame::Ping(msg)) => {
+                self.conn.write(Message::Pong(msg));
+            }
+            Ok(Frame::Close(_)) | Err(_) => ctx.stop(),
+            _ => {}
+        }
     }
 
     fn finished(&mut self, ctx: &mut Context<Self>) {
-        println!("Server disconnected");
         ctx.stop()
     }
 }
@@ -1,25 +1,79 @@
 //! `Cluster` is an actor. It maintains the connection sessions.
-//! The leader sends messages to other nodes through `Cluster`.
+//! Nodes send messages to each other through `Cluster`.
 
-use super::{Connect, Disconnect, Message};
+use super::client::CCFSWsClient;
+use super::{Connect, Disconnect, Forward, Message, MAX_FRAME_SIZE};
+use crate::raft::{Peer, RaftMessage};
+use actix::io::SinkWrite;
 use actix::prelude::*;
-use std::collections::HashMap;
+use awc::Client;
+use futures::StreamExt;
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+use tokio::sync::mpsc::UnboundedReceiver;
 
-/// `Cluster` manages communication between leader and nodes
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// `Cluster` manages communication between the nodes.
+///
+/// It keeps a ws connection to every peer, which is reopened when it's closed
 pub struct Cluster {
+    pub node_id: u64,
+    pub peers: HashMap<u64, String>,
     pub sessions: HashMap<u64, Recipient<Message>>,
+    connecting: HashSet<u64>,
 }
 
-impl Default for Cluster {
-    fn default() -> Self {
+impl Cluster {
+    pub fn new(node_id: u64, peers: Vec<Peer>) -> Self {
         Self {
+            node_id,
+            peers: peers.into_iter().map(|p| (p.id, p.address)).collect(),
             sessions: HashMap::new(),
+            connecting: HashSet::new(),
+        }
+    }
+
+    fn connect_peers(&mut self, ctx: &mut Context<Self>) {
+        let disconnected = self
+            .peers
+            .iter()
+            .filter(|(id, _)| !self.sessions.contains_key(id) && !self.connecting.contains(id))
+            .map(|(id, address)| (*id, format!("{}/raft/ws/", address)))
+            .collect::<Vec<_>>();
+        for (peer_id, url) in disconnected {
+            self.connecting.insert(peer_id);
+            let request = Client::new()
+                .ws(url)
+                .max_frame_size(MAX_FRAME_SIZE)
+                .header("node_id", self.node_id.to_string());
+            let cluster = ctx.address();
+            async move { request.connect().await }
+                .into_actor(self)
+                .map(move |res, act, _ctx| match res {
+                    Ok((_, framed)) => {
+                        let (sink, stream) = framed.split();
+                        CCFSWsClient::create(|ctx| {
+                            CCFSWsClient::add_stream(stream, ctx);
+                            CCFSWsClient::new(peer_id, cluster, SinkWrite::new(sink, ctx))
+                        });
+                    }
+                    Err(_) => {
+                        act.connecting.remove(&peer_id);
+                    }
+                })
+                .spawn(ctx);
         }
     }
 }
 
 impl Actor for Cluster {
     type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Context<Self>) {
+        self.connect_peers(ctx);
+        ctx.run_interval(RECONNECT_INTERVAL, |act, ctx| act.connect_peers(ctx));
+    }
 }
 
 /// Handler for Connect message.
@@ -29,7 +83,8 @@ impl Handler<Connect> for Cluster {
     type Result = ();
 
     fn handle(&mut self, msg: Connect, _ctx: &mut Context<Self>) {
-        println!("Someone joined");
+        println!("Connected to node {}", msg.id);
+        self.connecting.remove(&msg.id);
         self.sessions.insert(msg.id, msg.addr);
     }
 }
@@ -39,8 +94,37 @@ impl Handler<Disconnect> for Cluster {
     type Result = ();
 
     fn handle(&mut self, msg: Disconnect, _: &mut Context<Self>) {
-        println!("Someone disconnected");
+        println!("Disconnected from node {}", msg.id);
         // remove address
+        self.connecting.remove(&msg.id);
         self.sessions.remove(&msg.id);
     }
 }
+
+/// Handler for Forward message.
+///
+/// Messages for the nodes which are not connected are dropped, raft resends them
+impl Handler<Forward> for Cluster {
+    type Result = ();
+
+    fn handle(&mut self, msg: Forward, _: &mut Context<Self>) {
+        if let Some(session) = self.sessions.get(&msg.to) {
+            let _ = session.do_send(msg.message);
+        }
+    }
+}
+
+/// Sends the outgoing raft messages of the node through the `Cluster`
+pub async fn forward_messages(
+    node_id: u64,
+    mut outbox: UnboundedReceiver<(u64, RaftMessage)>,
+    cluster: Addr<Cluster>,
+) {
+    while let Some((to, content)) = outbox.recv().await {
+        let message = Message {
+            from: node_id,
+            content,
+        };
+        cluster.do_send(Forward { to, message });
+    }
+}
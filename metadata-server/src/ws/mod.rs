@@ -2,13 +2,28 @@ pub mod client;
 pub mod cluster;
 pub mod server;
 
+use crate::raft::RaftMessage;
 use actix::prelude::*;
 use serde::{Deserialize, Serialize};
 
-/// Cluster server sends this message to session
+/// Max payload size of a single ws frame, larger messages are split into continuation frames
+pub const MAX_FRAME_SIZE: usize = 32 * 1024;
+
+/// Raft message sent from one node to another
 #[derive(Debug, Message, Serialize, Deserialize)]
 #[rtype(result = "()")]
-pub struct Message(pub String);
+pub struct Message {
+    pub from: u64,
+    pub content: RaftMessage,
+}
+
+/// Cluster sends the message to the node with the `to` id
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct Forward {
+    pub to: u64,
+    pub message: Message,
+}
 
 /// New session is created
 #[derive(Message)]
@@ -1,54 +1,24 @@
-use super::cluster::Cluster;
-use super::{Connect, Disconnect, Message};
+use super::Message;
+use crate::Raft;
 use actix::prelude::*;
-use actix::Addr;
+use actix_http::ws::Item;
 use actix_web_actors::ws;
 
+/// Incoming connection from a peer, through which its raft messages are received
 pub struct CCFSWebSocket {
     /// Node id
     node_id: u64,
-    /// Cluster server
-    addr: Addr<Cluster>,
+    /// Raft state of this node
+    raft: Raft,
+    /// Content of the message which is split into continuation frames
+    buffer: Vec<u8>,
 }
 
 impl Actor for CCFSWebSocket {
     type Context = ws::WebsocketContext<Self>;
 
-    /// Method is called on actor start.
-    /// We register ws session with Cluster
-    fn started(&mut self, ctx: &mut Self::Context) {
+    fn started(&mut self, _ctx: &mut Self::Context) {
         println!("new connection {}", self.node_id);
-        let addr = ctx.address();
-        self.addr
-            .send(Connect {
-                id: self.node_id,
-                addr: addr.recipient(),
-            })
-            .into_actor(self)
-            .then(|res, _act, ctx| {
-                match res {
-                    Ok(_res) => {}
-                    // something is wrong with cluster server
-                    _ => ctx.stop(),
-                }
-                fut::ready(())
-            })
-            .wait(ctx);
-    }
-
-    fn stopping(&mut self, _: &mut Self::Context) -> Running {
-        // notify cluster server
-        self.addr.do_send(Disconnect { id: self.node_id });
-        Running::Stop
-    }
-}
-
-/// Handle messages from cluster server, we simply send it to peer websocket
-impl Handler<Message> for CCFSWebSocket {
-    type Result = ();
-
-    fn handle(&mut self, msg: Message, ctx: &mut Self::Context) {
-        ctx.binary(bincode::serialize(&msg).expect("failed to serialize ws msg"));
     }
 }
 
@@ -69,23 +39,53 @@ impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for CCFSWebSocket {
             }
             ws::Message::Pong(_) => {}
             ws::Message::Text(_text) => {}
-            ws::Message::Binary(data) => {
-                println!("Received: {:?}", data);
-            }
+            ws::Message::Binary(data) => self.receive(&data),
             ws::Message::Close(reason) => {
                 ctx.close(reason);
                 ctx.stop();
             }
-            ws::Message::Continuation(_) => {
-                ctx.stop();
-            }
+            ws::Message::Continuation(item) => match item {
+                Item::FirstBinary(data) => self.buffer = data.to_vec(),
+                Item::Continue(data) => self.buffer.extend(data.as_ref()),
+                Item::Last(data) => {
+                    let mut content = std::mem::take(&mut self.buffer);
+                    content.extend(data.as_ref());
+                    self.receive(&content);
+                }
+                Item::FirstText(_) => ctx.stop(),
+            },
             ws::Message::Nop => (),
         }
     }
 }
 
 impl CCFSWebSocket {
-    pub fn new(node_id: u64, addr: Addr<Cluster>) -> Self {
-        Self { node_id, addr }
+    pub fn new(node_id: u64, raft: Raft) -> Self {
+        Self {
+            node_id,
+            raft,
+            buffer: Vec::new(),
+        }
+    }
+
+    /// Passes the received message to the raft node
+    fn receive(&self, data: &[u8]) {
+        let msg = match bincode::deserialize::<Message>(data) {
+            Ok(msg) => msg,
+            Err(err) => {
+                println!(
+                    "Couldn't deserialize message from {}: {}",
+                    self.node_id, err
+                );
+                return;
+            }
+        };
+        let raft = self.raft.clone();
+        actix::spawn(async move {
+            if let Err(err) = raft.lock().await.handle(msg.from, msg.content).await {
+                // TODO: replace with logger
+                println!("Error while handling message from {}: {:?}", msg.from, err);
+            }
+        });
     }
 }
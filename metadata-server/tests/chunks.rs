@@ -1,22 +1,30 @@
+mod utils;
+
 use actix_http::http::StatusCode;
 use actix_web::{test, web, App};
 use ccfs_commons::{Chunk, FileMetadata, FileStatus};
-use metadata_server::routes::api::{get_chunks, signal_chuck_upload_completed};
-use metadata_server::{ChunksMap, FilesMap};
+use metadata_server::routes::api::{
+    get_chunks, get_referenced_chunks, report_corrupted_chunks, signal_chuck_upload_completed,
+};
+use metadata_server::{ChunksMap, DeletionQueue, FilesMap};
 use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use test::{call_service, init_service, read_response_json, TestRequest};
 use tokio::sync::RwLock;
+use utils::test_raft;
 use uuid::Uuid;
 
 #[actix_rt::test]
 async fn test_upload_completed_non_existing_file() -> std::io::Result<()> {
+    let (_raft_dir, raft) = test_raft().await;
     let chunk = Chunk::new(Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
     let chunks: ChunksMap = Arc::new(RwLock::new(HashMap::new()));
     let files: FilesMap = Arc::new(RwLock::new(HashMap::new()));
     let metadata_tree = Arc::new(RwLock::new(FileMetadata::create_root()));
     let server = init_service(
         App::new()
+            .data(raft)
+            .data(DeletionQueue::default())
             .data(chunks)
             .data(files)
             .data(metadata_tree)
@@ -35,6 +43,7 @@ async fn test_upload_completed_non_existing_file() -> std::io::Result<()> {
 
 #[actix_rt::test]
 async fn test_upload_completed() -> std::io::Result<()> {
+    let (_raft_dir, raft) = test_raft().await;
     let mut map = HashMap::new();
     let chunk = Chunk::new(Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
     let mut new_file = FileMetadata::create_file("test.txt".into(), 10, vec![chunk.id]);
@@ -52,6 +61,8 @@ async fn test_upload_completed() -> std::io::Result<()> {
     let metadata_tree = Arc::new(RwLock::new(FileMetadata::create_root()));
     let server = init_service(
         App::new()
+            .data(raft)
+            .data(DeletionQueue::default())
             .data(chunks.clone())
             .data(files.clone())
             .data(metadata_tree.clone())
@@ -86,6 +97,7 @@ async fn test_upload_completed() -> std::io::Result<()> {
 
 #[actix_rt::test]
 async fn test_upload_completed_part() -> std::io::Result<()> {
+    let (_raft_dir, raft) = test_raft().await;
     let mut map = HashMap::new();
     let chunk = Chunk::new(Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
     let chunk2_id = Uuid::new_v4();
@@ -104,6 +116,8 @@ async fn test_upload_completed_part() -> std::io::Result<()> {
     let metadata_tree = Arc::new(RwLock::new(FileMetadata::create_root()));
     let server = init_service(
         App::new()
+            .data(raft)
+            .data(DeletionQueue::default())
             .data(chunks.clone())
             .data(files.clone())
             .data(metadata_tree.clone())
@@ -202,3 +216,122 @@ async fn test_get_file_chunks() -> std::io::Result<()> {
     assert!(ch2_set.iter().all(|val| data[1].contains(val)));
     Ok(())
 }
+
+#[actix_rt::test]
+async fn test_get_referenced_chunks() -> std::io::Result<()> {
+    let server_id = Uuid::new_v4();
+    let mut tree = FileMetadata::create_root();
+    let completed_chunk_id = Uuid::new_v4();
+    tree.insert_file("test.txt", 10, vec![completed_chunk_id])
+        .unwrap();
+    let completed_file_id = match &tree.traverse("test.txt").unwrap().file_info {
+        ccfs_commons::FileInfo::File { id, .. } => *id,
+        _ => unreachable!(),
+    };
+    let started_chunk_id = Uuid::new_v4();
+    let started_file = FileMetadata::create_file("test2.txt".into(), 10, vec![started_chunk_id]);
+    let started_file_id = match &started_file.file_info {
+        ccfs_commons::FileInfo::File { id, .. } => *id,
+        _ => unreachable!(),
+    };
+    let mut map = HashMap::new();
+    map.insert(started_file_id, (String::from(""), started_file));
+    let files: FilesMap = Arc::new(RwLock::new(map));
+    let metadata_tree = Arc::new(RwLock::new(tree));
+    let (_raft_dir, raft) = test_raft().await;
+    let server = init_service(
+        App::new()
+            .data(files)
+            .data(metadata_tree)
+            .data(raft.clone())
+            .service(web::scope("/api").service(get_referenced_chunks)),
+    )
+    .await;
+
+    let completed = Chunk::new(completed_chunk_id, completed_file_id, server_id);
+    let started = Chunk::new(started_chunk_id, started_file_id, server_id);
+    let orphaned = Chunk::new(Uuid::new_v4(), completed_file_id, server_id);
+    let stored = vec![completed, started, orphaned];
+    // the committed entries aren't applied yet
+    let req = TestRequest::post()
+        .uri("/api/chunks/referenced")
+        .set_json(&stored)
+        .to_request();
+    let resp = call_service(&server, req).await;
+    assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+    raft.lock().await.take_committed();
+    let req = TestRequest::post()
+        .uri("/api/chunks/referenced")
+        .set_json(&stored)
+        .to_request();
+    let data: Vec<Chunk> = read_response_json(&server, req).await;
+    assert_eq!(data, vec![completed, started]);
+    Ok(())
+}
+
+#[actix_rt::test]
+async fn test_get_referenced_chunks_empty_metadata() -> std::io::Result<()> {
+    let (_raft_dir, raft) = test_raft().await;
+    raft.lock().await.take_committed();
+    let server = init_service(
+        App::new()
+            .data(FilesMap::default())
+            .data(Arc::new(RwLock::new(FileMetadata::create_root())))
+            .data(raft)
+            .service(web::scope("/api").service(get_referenced_chunks)),
+    )
+    .await;
+
+    // a node restarted without its snapshot would list none of the chunks
+    let chunk = Chunk::new(Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+    let req = TestRequest::post()
+        .uri("/api/chunks/referenced")
+        .set_json(&vec![chunk])
+        .to_request();
+    let resp = call_service(&server, req).await;
+    assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+    Ok(())
+}
+
+#[actix_rt::test]
+async fn test_report_corrupted_chunks() -> std::io::Result<()> {
+    let (_raft_dir, raft) = test_raft().await;
+    let (file_id, chunk_id) = (Uuid::new_v4(), Uuid::new_v4());
+    let healthy = Chunk::new(chunk_id, file_id, Uuid::new_v4()).with_checksum(1);
+    let corrupted = Chunk::new(chunk_id, file_id, Uuid::new_v4()).with_checksum(1);
+    let only_replica = Chunk::new(Uuid::new_v4(), file_id, corrupted.server_id);
+    let mut map = HashMap::new();
+    map.insert(chunk_id, vec![healthy, corrupted].into_iter().collect());
+    map.insert(only_replica.id, vec![only_replica].into_iter().collect());
+    let chunks: ChunksMap = Arc::new(RwLock::new(map));
+    let files: FilesMap = Arc::new(RwLock::new(HashMap::new()));
+    let metadata_tree = Arc::new(RwLock::new(FileMetadata::create_root()));
+    let server = init_service(
+        App::new()
+            .data(raft)
+            .data(DeletionQueue::default())
+            .data(chunks.clone())
+            .data(files)
+            .data(metadata_tree)
+            .service(web::scope("/api").service(report_corrupted_chunks)),
+    )
+    .await;
+
+    // the reported checksums don't have to match the registered ones
+    let req = TestRequest::post()
+        .uri("/api/chunks/corrupted")
+        .set_json(&vec![
+            Chunk::new(chunk_id, file_id, corrupted.server_id),
+            only_replica,
+        ])
+        .to_request();
+    let resp = call_service(&server, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let chunks = chunks.read().await;
+    let expected: HashSet<Chunk> = vec![healthy].into_iter().collect();
+    assert_eq!(chunks.get(&chunk_id), Some(&expected));
+    assert_eq!(chunks.get(&only_replica.id), Some(&HashSet::new()));
+    Ok(())
+}
@@ -0,0 +1,27 @@
+use metadata_server::server_config::ServerConfig;
+use std::fs::write;
+use tempfile::tempdir;
+
+#[test]
+fn test_load_config_without_new_keys() -> std::io::Result<()> {
+    let temp = tempdir()?;
+    // the config of a single node setup, without the keys added with the later features
+    let content = format!(
+        "host: 0.0.0.0\n\
+         port: 4000\n\
+         snapshot_interval: 10\n\
+         snapshot_dir_path: {}\n\
+         snapshot_file_name: snapshot\n\
+         replication_interval: 3\n",
+        temp.path().join("snapshot").display()
+    );
+    let path = temp.path().join("ms_config.yml");
+    write(&path, content)?;
+
+    let config = ServerConfig::load_config(&path)?;
+    assert_eq!(config.node_id, 1);
+    assert!(config.peers.is_empty());
+    assert!(config.deletion_interval > 0);
+    assert!(config.election_timeout > config.heartbeat_interval);
+    Ok(())
+}
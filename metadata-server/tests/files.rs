@@ -1,12 +1,20 @@
+mod utils;
+
 use actix_http::http::StatusCode;
 use actix_web::{test, web, App};
-use ccfs_commons::{FileInfo, FileMetadata};
-use metadata_server::routes::api::{create_file, get_file};
-use metadata_server::FilesMap;
-use std::collections::HashMap;
+use ccfs_commons::{
+    Chunk, ChunkServer, FileInfo, FileMetadata, FileUpload, UploadStatus, CHUNK_SIZE,
+};
+use chrono::Duration;
+use metadata_server::routes::api::{
+    create_file, get_file, get_file_upload, remove_file, set_file_replicas,
+};
+use metadata_server::{ChunksMap, DeletionQueue, FilesMap, ServersMap};
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use test::{call_service, init_service, read_response_json, TestRequest};
 use tokio::sync::RwLock;
+use utils::{test_placement, test_raft};
 use uuid::Uuid;
 
 #[actix_rt::test]
@@ -110,10 +118,16 @@ async fn test_get_file_at_path() -> std::io::Result<()> {
 
 #[actix_rt::test]
 async fn test_upload_file() -> std::io::Result<()> {
+    let (_raft_dir, raft) = test_raft().await;
     let files: FilesMap = Arc::new(RwLock::new(HashMap::new()));
     let metadata_tree = Arc::new(RwLock::new(FileMetadata::create_root()));
     let server = init_service(
         App::new()
+            .data(raft)
+            .data(ChunksMap::default())
+            .data(ServersMap::default())
+            .data(test_placement())
+            .data(DeletionQueue::default())
             .data(files.clone())
             .data(metadata_tree)
             .service(web::scope("/api").service(create_file)),
@@ -149,15 +163,93 @@ async fn test_upload_file() -> std::io::Result<()> {
         assert_eq!(files_map.len(), 1);
         assert_eq!(files_map.get(file_id), Some(&("".into(), new_file.clone())));
     }
+
+    // the evaluated path of the target dir is returned, the clients record it next to the chunks
+    let req = TestRequest::post()
+        .uri("/api/files/upload")
+        .set_json(&FileMetadata::create_dir("dir1".into()))
+        .to_request();
+    let _: FileMetadata = read_response_json(&server, req).await;
+    let req = TestRequest::post()
+        .uri("/api/files/upload?path=./dir1/")
+        .set_json(&new_file)
+        .to_request();
+    let data: FileUpload = read_response_json(&server, req).await;
+    assert_eq!(data.path, "/dir1");
+    Ok(())
+}
+
+#[actix_rt::test]
+async fn test_upload_file_placement() -> std::io::Result<()> {
+    let (_raft_dir, raft) = test_raft().await;
+    let mut servers = HashMap::new();
+    for (available_space, pending_requests) in
+        [(10, 0), (CHUNK_SIZE * 4, 0), (CHUNK_SIZE * 4, 3)].iter()
+    {
+        let mut s = ChunkServer::new(Uuid::new_v4(), "http://localhost".into());
+        s.total_space = CHUNK_SIZE * 8;
+        s.available_space = *available_space;
+        s.pending_requests = *pending_requests;
+        servers.insert(s.id, s);
+    }
+    let mut inactive = ChunkServer::new(Uuid::new_v4(), "http://localhost".into());
+    inactive.latest_ping_time = inactive.latest_ping_time - Duration::seconds(10);
+    servers.insert(inactive.id, inactive.clone());
+    let servers: ServersMap = Arc::new(RwLock::new(servers));
+    let server = init_service(
+        App::new()
+            .data(raft)
+            .data(ChunksMap::default())
+            .data(servers.clone())
+            .data(test_placement())
+            .data(DeletionQueue::default())
+            .data(FilesMap::default())
+            .data(Arc::new(RwLock::new(FileMetadata::create_root())))
+            .service(web::scope("/api").service(create_file)),
+    )
+    .await;
+
+    let chunk_ids = vec![Uuid::new_v4(), Uuid::new_v4()];
+    let new_file = FileMetadata::create_file("test.txt".into(), 10, chunk_ids.clone());
+    let req = TestRequest::post()
+        .uri("/api/files/upload")
+        .set_json(&new_file)
+        .to_request();
+    let data: FileUpload = read_response_json(&server, req).await;
+    assert_eq!(data.file, new_file);
+    assert_eq!(data.placement.len(), 2);
+    for (placement, chunk_id) in data.placement.iter().zip(chunk_ids.iter()) {
+        assert_eq!(&placement.chunk_id, chunk_id);
+        // the inactive server and the server without enough space are skipped
+        assert_eq!(placement.servers.len(), 2);
+        assert!(placement.servers.iter().all(|s| s.id != inactive.id));
+        assert!(placement.servers.iter().all(|s| s.available_space > 10));
+    }
+    assert_eq!(data.placement[0].servers[0].pending_requests, 0);
+
+    let new_file = FileMetadata::create_file("test2.txt".into(), 10, vec![Uuid::new_v4()])
+        .with_replicas(Some(1));
+    let req = TestRequest::post()
+        .uri("/api/files/upload")
+        .set_json(&new_file)
+        .to_request();
+    let data: FileUpload = read_response_json(&server, req).await;
+    assert_eq!(data.placement[0].servers.len(), 1);
     Ok(())
 }
 
 #[actix_rt::test]
 async fn test_upload_empty_dir() -> std::io::Result<()> {
+    let (_raft_dir, raft) = test_raft().await;
     let files: FilesMap = Arc::new(RwLock::new(HashMap::new()));
     let metadata_tree = Arc::new(RwLock::new(FileMetadata::create_root()));
     let server = init_service(
         App::new()
+            .data(raft)
+            .data(ChunksMap::default())
+            .data(ServersMap::default())
+            .data(test_placement())
+            .data(DeletionQueue::default())
             .data(files.clone())
             .data(metadata_tree.clone())
             .service(web::scope("/api").service(create_file)),
@@ -181,10 +273,16 @@ async fn test_upload_empty_dir() -> std::io::Result<()> {
 
 #[actix_rt::test]
 async fn test_upload_to_non_existing_path() -> std::io::Result<()> {
+    let (_raft_dir, raft) = test_raft().await;
     let files: FilesMap = Arc::new(RwLock::new(HashMap::new()));
     let metadata_tree = Arc::new(RwLock::new(FileMetadata::create_root()));
     let server = init_service(
         App::new()
+            .data(raft)
+            .data(ChunksMap::default())
+            .data(ServersMap::default())
+            .data(test_placement())
+            .data(DeletionQueue::default())
             .data(files)
             .data(metadata_tree)
             .service(web::scope("/api").service(create_file)),
@@ -203,10 +301,16 @@ async fn test_upload_to_non_existing_path() -> std::io::Result<()> {
 
 #[actix_rt::test]
 async fn test_upload_invalid_path() -> std::io::Result<()> {
+    let (_raft_dir, raft) = test_raft().await;
     let files: FilesMap = Arc::new(RwLock::new(HashMap::new()));
     let metadata_tree = Arc::new(RwLock::new(FileMetadata::create_root()));
     let server = init_service(
         App::new()
+            .data(raft)
+            .data(ChunksMap::default())
+            .data(ServersMap::default())
+            .data(test_placement())
+            .data(DeletionQueue::default())
             .data(files)
             .data(metadata_tree)
             .service(web::scope("/api").service(create_file)),
@@ -225,12 +329,18 @@ async fn test_upload_invalid_path() -> std::io::Result<()> {
 
 #[actix_rt::test]
 async fn test_upload_at_path() -> std::io::Result<()> {
+    let (_raft_dir, raft) = test_raft().await;
     let files: FilesMap = Arc::new(RwLock::new(HashMap::new()));
     let mut tree = FileMetadata::create_root();
     tree.insert_dir("projects").unwrap();
     let metadata_tree = Arc::new(RwLock::new(tree));
     let server = init_service(
         App::new()
+            .data(raft)
+            .data(ChunksMap::default())
+            .data(ServersMap::default())
+            .data(test_placement())
+            .data(DeletionQueue::default())
             .data(files)
             .data(metadata_tree)
             .service(web::scope("/api").service(create_file)),
@@ -246,3 +356,252 @@ async fn test_upload_at_path() -> std::io::Result<()> {
     assert_eq!(data, new_dir);
     Ok(())
 }
+
+#[actix_rt::test]
+async fn test_remove_file() -> std::io::Result<()> {
+    let (_raft_dir, raft) = test_raft().await;
+    let mut tree = FileMetadata::create_root();
+    tree.insert_dir("projects").unwrap();
+    let chunk_id = Uuid::new_v4();
+    let file = FileMetadata::create_file("test.txt".into(), 10, vec![chunk_id]);
+    let file_id = match &file.file_info {
+        FileInfo::File { id, .. } => *id,
+        _ => unreachable!(),
+    };
+    tree.traverse_mut("projects")
+        .unwrap()
+        .children_mut()
+        .unwrap()
+        .insert(file.name.clone(), file.clone());
+    let chunk = Chunk::new(chunk_id, file_id, Uuid::new_v4());
+    let mut files_map = HashMap::new();
+    files_map.insert(file_id, ("/projects".to_string(), file.clone()));
+    let mut chunks_map = HashMap::new();
+    chunks_map.insert(chunk_id, vec![chunk].into_iter().collect::<HashSet<_>>());
+
+    let files: FilesMap = Arc::new(RwLock::new(files_map));
+    let chunks: ChunksMap = Arc::new(RwLock::new(chunks_map));
+    let deletion_queue: DeletionQueue = Arc::new(RwLock::new(HashSet::new()));
+    let metadata_tree = Arc::new(RwLock::new(tree));
+    let server = init_service(
+        App::new()
+            .data(raft)
+            .data(files.clone())
+            .data(chunks.clone())
+            .data(deletion_queue.clone())
+            .data(metadata_tree.clone())
+            .service(web::scope("/api").service(remove_file)),
+    )
+    .await;
+
+    let req = TestRequest::delete()
+        .uri("/api/files?path=/projects/test.txt")
+        .to_request();
+    let data: FileMetadata = read_response_json(&server, req).await;
+    assert_eq!(data, file);
+    let tree = metadata_tree.read().await;
+    assert!(tree
+        .traverse("projects")
+        .unwrap()
+        .children()
+        .unwrap()
+        .is_empty());
+    assert!(files.read().await.is_empty());
+    assert!(chunks.read().await.is_empty());
+    assert!(deletion_queue.read().await.contains(&chunk));
+    Ok(())
+}
+
+#[actix_rt::test]
+async fn test_remove_dir() -> std::io::Result<()> {
+    let (_raft_dir, raft) = test_raft().await;
+    let mut tree = FileMetadata::create_root();
+    tree.insert_dir("projects").unwrap();
+    let chunk_id = Uuid::new_v4();
+    let projects = tree.traverse_mut("projects").unwrap();
+    projects
+        .insert_file("test.txt", 10, vec![chunk_id])
+        .unwrap();
+    let files: FilesMap = Arc::new(RwLock::new(HashMap::new()));
+    let chunks: ChunksMap = Arc::new(RwLock::new(HashMap::new()));
+    let deletion_queue: DeletionQueue = Arc::new(RwLock::new(HashSet::new()));
+    let metadata_tree = Arc::new(RwLock::new(tree));
+    let server = init_service(
+        App::new()
+            .data(raft)
+            .data(files)
+            .data(chunks)
+            .data(deletion_queue)
+            .data(metadata_tree.clone())
+            .service(web::scope("/api").service(remove_file)),
+    )
+    .await;
+
+    let req = TestRequest::delete()
+        .uri("/api/files?path=/projects")
+        .to_request();
+    let resp = call_service(&server, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    assert!(metadata_tree.read().await.traverse("projects").is_ok());
+
+    let req = TestRequest::delete()
+        .uri("/api/files?path=/projects&recursive=true")
+        .to_request();
+    let data: FileMetadata = read_response_json(&server, req).await;
+    assert_eq!(data.name, "projects");
+    let tree = metadata_tree.read().await;
+    assert!(tree.children().unwrap().is_empty());
+    Ok(())
+}
+
+#[actix_rt::test]
+async fn test_remove_root() -> std::io::Result<()> {
+    let (_raft_dir, raft) = test_raft().await;
+    let files: FilesMap = Arc::new(RwLock::new(HashMap::new()));
+    let chunks: ChunksMap = Arc::new(RwLock::new(HashMap::new()));
+    let deletion_queue: DeletionQueue = Arc::new(RwLock::new(HashSet::new()));
+    let metadata_tree = Arc::new(RwLock::new(FileMetadata::create_root()));
+    let server = init_service(
+        App::new()
+            .data(raft)
+            .data(files)
+            .data(chunks)
+            .data(deletion_queue)
+            .data(metadata_tree)
+            .service(web::scope("/api").service(remove_file)),
+    )
+    .await;
+
+    let req = TestRequest::delete()
+        .uri("/api/files?path=/&recursive=true")
+        .to_request();
+    let resp = call_service(&server, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    let req = TestRequest::delete()
+        .uri("/api/files?path=/not-existing.txt")
+        .to_request();
+    let resp = call_service(&server, req).await;
+    assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    Ok(())
+}
+
+#[actix_rt::test]
+async fn test_set_file_replicas() -> std::io::Result<()> {
+    let (_raft_dir, raft) = test_raft().await;
+    let mut tree = FileMetadata::create_root();
+    let file = FileMetadata::create_file("test.txt".into(), 10, vec![Uuid::new_v4()]);
+    let file_id = match &file.file_info {
+        FileInfo::File { id, .. } => *id,
+        _ => unreachable!(),
+    };
+    tree.children_mut()
+        .unwrap()
+        .insert(file.name.clone(), file.clone());
+    tree.insert_dir("projects").unwrap();
+    let mut files_map = HashMap::new();
+    files_map.insert(file_id, ("".to_string(), file.clone()));
+    let files: FilesMap = Arc::new(RwLock::new(files_map));
+    let metadata_tree = Arc::new(RwLock::new(tree));
+    let server = init_service(
+        App::new()
+            .data(raft)
+            .data(files.clone())
+            .data(ChunksMap::default())
+            .data(DeletionQueue::default())
+            .data(metadata_tree.clone())
+            .service(web::scope("/api").service(set_file_replicas)),
+    )
+    .await;
+
+    let req = TestRequest::put()
+        .uri("/api/files/replicas?path=/test.txt&replicas=5")
+        .to_request();
+    let data: FileMetadata = read_response_json(&server, req).await;
+    let expected = file.clone().with_replicas(Some(5));
+    assert_eq!(data, expected);
+    assert_eq!(
+        metadata_tree.read().await.traverse("test.txt").unwrap(),
+        &expected
+    );
+    assert_eq!(files.read().await.get(&file_id).unwrap().1, expected);
+
+    // the server default is used again when the replicas aren't set
+    let req = TestRequest::put()
+        .uri("/api/files/replicas?path=/test.txt")
+        .to_request();
+    let data: FileMetadata = read_response_json(&server, req).await;
+    assert_eq!(data, file);
+
+    for uri in [
+        "/api/files/replicas?path=/test.txt&replicas=0",
+        "/api/files/replicas?path=/test.txt&replicas=many",
+    ]
+    .iter()
+    {
+        let req = TestRequest::put().uri(uri).to_request();
+        let resp = call_service(&server, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+    let req = TestRequest::put()
+        .uri("/api/files/replicas?path=/projects&replicas=2")
+        .to_request();
+    let resp = call_service(&server, req).await;
+    assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    Ok(())
+}
+
+#[actix_rt::test]
+async fn test_get_file_upload() -> std::io::Result<()> {
+    let chunk_server = ChunkServer::new(Uuid::new_v4(), "http://localhost".into());
+    let servers: ServersMap = Arc::new(RwLock::new(
+        vec![(chunk_server.id, chunk_server.clone())]
+            .into_iter()
+            .collect(),
+    ));
+    let chunk_ids = vec![Uuid::new_v4(), Uuid::new_v4()];
+    let file = FileMetadata::create_file("test.txt".into(), CHUNK_SIZE + 5, chunk_ids.clone());
+    let file_id = match &file.file_info {
+        FileInfo::File { id, .. } => *id,
+        _ => unreachable!(),
+    };
+    let files: FilesMap = Arc::new(RwLock::new(HashMap::new()));
+    files
+        .write()
+        .await
+        .insert(file_id, ("/dir".into(), file.clone()));
+    let chunks = ChunksMap::default();
+    let uploaded = Chunk::new(chunk_ids[0], file_id, chunk_server.id);
+    chunks.write().await.insert(
+        uploaded.id,
+        vec![uploaded].into_iter().collect::<HashSet<_>>(),
+    );
+    let server = init_service(
+        App::new()
+            .data(chunks)
+            .data(servers)
+            .data(test_placement())
+            .data(files)
+            .service(web::scope("/api").service(get_file_upload)),
+    )
+    .await;
+
+    let req = TestRequest::get()
+        .uri(&format!("/api/files/{}/upload", file_id))
+        .to_request();
+    let data: UploadStatus = read_response_json(&server, req).await;
+    assert_eq!(data.upload.file, file);
+    assert_eq!(data.upload.path, "/dir");
+    assert_eq!(data.completed, vec![chunk_ids[0]]);
+    // only the remaining chunks are placed
+    assert_eq!(data.upload.placement.len(), 1);
+    assert_eq!(data.upload.placement[0].chunk_id, chunk_ids[1]);
+    assert_eq!(data.upload.placement[0].servers, vec![chunk_server]);
+
+    let req = TestRequest::get()
+        .uri(&format!("/api/files/{}/upload", Uuid::new_v4()))
+        .to_request();
+    let resp = call_service(&server, req).await;
+    assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    Ok(())
+}
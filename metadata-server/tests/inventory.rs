@@ -0,0 +1,69 @@
+use ccfs_commons::{Chunk, ChunkReport, FileInfo, FileMetadata};
+use metadata_server::inventory::reconcile_chunks;
+use metadata_server::{Chunks, Files};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// Creates a file with 5 chunks, the first 3 of them registered on the server
+fn setup(server_id: Uuid) -> (Files, Chunks, Vec<Chunk>) {
+    let chunk_ids = (0..5).map(|_| Uuid::new_v4()).collect::<Vec<_>>();
+    let file = FileMetadata::create_file("a.txt".into(), 10, chunk_ids.clone());
+    let file_id = match &file.file_info {
+        FileInfo::File { id, .. } => *id,
+        _ => unreachable!(),
+    };
+    let replicas = chunk_ids
+        .iter()
+        .map(|id| Chunk::new(*id, file_id, server_id).with_checksum(1))
+        .collect::<Vec<_>>();
+    let mut files = HashMap::new();
+    files.insert(file_id, ("/".into(), file));
+    let mut chunks = HashMap::new();
+    for replica in replicas[..3].iter() {
+        chunks.insert(replica.id, vec![*replica].into_iter().collect());
+    }
+    (files, chunks, replicas)
+}
+
+#[test]
+fn test_reconcile_full_report() {
+    let server_id = Uuid::new_v4();
+    let (files, mut chunks, replicas) = setup(server_id);
+    // the last chunk is registered on another server with a different checksum
+    let mismatched = Chunk::new(Uuid::new_v4(), replicas[0].file_id, server_id).with_checksum(2);
+    let other_replica = Chunk::new(mismatched.id, mismatched.file_id, Uuid::new_v4());
+    chunks.insert(
+        mismatched.id,
+        vec![other_replica.with_checksum(1)].into_iter().collect(),
+    );
+    let orphaned = Chunk::new(Uuid::new_v4(), Uuid::new_v4(), server_id);
+
+    let report = ChunkReport {
+        server_id,
+        full: true,
+        stored: vec![replicas[0], replicas[1], replicas[3], mismatched, orphaned],
+        removed: Vec::new(),
+    };
+    let result = reconcile_chunks(&report, &files, &chunks);
+    assert_eq!(result.unregistered, vec![replicas[2]]);
+    assert_eq!(result.registered, vec![replicas[3]]);
+    assert_eq!(result.unknown, vec![mismatched, orphaned]);
+}
+
+#[test]
+fn test_reconcile_incremental_report() {
+    let server_id = Uuid::new_v4();
+    let (files, chunks, replicas) = setup(server_id);
+
+    // only the listed chunks are reconciled
+    let report = ChunkReport {
+        server_id,
+        full: false,
+        stored: vec![replicas[3]],
+        removed: vec![Chunk::new(replicas[2].id, replicas[2].file_id, server_id)],
+    };
+    let result = reconcile_chunks(&report, &files, &chunks);
+    assert_eq!(result.unregistered, vec![replicas[2]]);
+    assert_eq!(result.registered, vec![replicas[3]]);
+    assert!(result.unknown.is_empty());
+}
@@ -0,0 +1,168 @@
+use ccfs_commons::test_utils::build_tree;
+use ccfs_commons::{Chunk, FileInfo, FileMetadata};
+use metadata_server::operations::Operation;
+use metadata_server::oplog::{read_entries, OperationLog};
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::Write;
+use tempfile::tempdir;
+use uuid::Uuid;
+
+fn create_file_op(path: &str, name: &str) -> Operation {
+    Operation::CreateFile {
+        path: path.into(),
+        file: FileMetadata::create_file(name.into(), 10, vec![Uuid::new_v4()]),
+    }
+}
+
+#[actix_rt::test]
+async fn test_oplog_reopen_after_snapshot() -> std::io::Result<()> {
+    let temp = tempdir()?;
+    let path = temp.path().join("oplog");
+    let operations = vec![
+        create_file_op("/", "a.txt"),
+        create_file_op("/dir1", "b.txt"),
+        create_file_op("/dir2", "c.txt"),
+    ];
+    {
+        let mut oplog = OperationLog::open(&path, 0, 0).await.unwrap();
+        assert_eq!(oplog.last_seq(), 0);
+        for (i, op) in operations.iter().enumerate() {
+            assert_eq!(oplog.append(1, op.clone()).await.unwrap(), i as u64 + 1);
+        }
+    }
+
+    let oplog = OperationLog::open(&path, 0, 0).await.unwrap();
+    assert_eq!((oplog.last_seq(), oplog.last_term()), (3, 1));
+    let pending = oplog.entries_from(1).iter().map(|e| e.operation.clone());
+    assert_eq!(pending.collect::<Vec<_>>(), operations);
+    let oplog = OperationLog::open(&path, 2, 1).await.unwrap();
+    assert_eq!(oplog.last_seq(), 3);
+    assert_eq!(oplog.term_at(2), Some(1));
+    assert_eq!(oplog.term_at(1), None);
+    assert_eq!(oplog.entries_from(1), oplog.entries_from(3));
+    assert_eq!(oplog.entry(3).unwrap().operation, operations[2]);
+    // the snapshot can be newer than the log, when the log was truncated
+    let oplog = OperationLog::open(&path, 5, 2).await.unwrap();
+    assert_eq!((oplog.last_seq(), oplog.last_term()), (5, 2));
+    assert!(oplog.entries_from(1).is_empty());
+    Ok(())
+}
+
+#[actix_rt::test]
+async fn test_oplog_truncate() -> std::io::Result<()> {
+    let temp = tempdir()?;
+    let path = temp.path().join("oplog");
+    let mut oplog = OperationLog::open(&path, 0, 0).await.unwrap();
+    for (term, name) in &[(1, "a.txt"), (1, "b.txt"), (2, "c.txt")] {
+        oplog
+            .append(*term, create_file_op("/", name))
+            .await
+            .unwrap();
+    }
+    oplog.truncate(2).await.unwrap();
+    assert_eq!((oplog.snapshot_seq(), oplog.term_at(2)), (2, Some(1)));
+    let entries = read_entries(&path).await.unwrap();
+    assert_eq!(entries.iter().map(|e| e.seq).collect::<Vec<_>>(), vec![3]);
+
+    assert_eq!(
+        oplog.append(2, create_file_op("/", "d.txt")).await.unwrap(),
+        4
+    );
+    let entries = read_entries(&path).await.unwrap();
+    assert_eq!(
+        entries.iter().map(|e| e.seq).collect::<Vec<_>>(),
+        vec![3, 4]
+    );
+
+    // conflicting entries are replaced
+    oplog.truncate_from(4).await.unwrap();
+    assert_eq!(
+        oplog.append(3, create_file_op("/", "e.txt")).await.unwrap(),
+        4
+    );
+    let entries = read_entries(&path).await.unwrap();
+    let terms = entries.iter().map(|e| (e.seq, e.term)).collect::<Vec<_>>();
+    assert_eq!(terms, vec![(3, 2), (4, 3)]);
+    Ok(())
+}
+
+#[actix_rt::test]
+async fn test_oplog_torn_write() -> std::io::Result<()> {
+    let temp = tempdir()?;
+    let path = temp.path().join("oplog");
+    let operation = create_file_op("/", "a.txt");
+    {
+        let mut oplog = OperationLog::open(&path, 0, 0).await.unwrap();
+        oplog.append(1, operation.clone()).await.unwrap();
+    }
+    // simulate a crash in the middle of writing the second record
+    let mut file = OpenOptions::new().append(true).open(&path)?;
+    file.write_all(&100u32.to_le_bytes())?;
+    file.write_all(&[1, 2, 3])?;
+
+    let mut oplog = OperationLog::open(&path, 0, 0).await.unwrap();
+    assert_eq!(oplog.last_seq(), 1);
+    assert_eq!(oplog.entry(1).unwrap().operation, operation);
+    oplog.append(1, create_file_op("/", "b.txt")).await.unwrap();
+    let entries = read_entries(&path).await.unwrap();
+    assert_eq!(
+        entries.iter().map(|e| e.seq).collect::<Vec<_>>(),
+        vec![1, 2]
+    );
+    Ok(())
+}
+
+#[actix_rt::test]
+async fn test_apply_operations() -> std::io::Result<()> {
+    let mut tree = build_tree().unwrap();
+    let mut files = HashMap::new();
+    let mut chunks = HashMap::new();
+    let chunk_id = Uuid::new_v4();
+    let file = FileMetadata::create_file("new.txt".into(), 10, vec![chunk_id]);
+    let file_id = match &file.file_info {
+        FileInfo::File { id, .. } => *id,
+        _ => unreachable!(),
+    };
+    let operations = vec![
+        Operation::CreateFile {
+            path: "/dir1".into(),
+            file,
+        },
+        Operation::CompleteChunk {
+            chunk: Chunk::new(chunk_id, file_id, Uuid::new_v4()),
+        },
+        Operation::RemoveFile {
+            path: "/dir2".into(),
+        },
+    ];
+    for op in operations {
+        op.apply(&mut tree, &mut files, &mut chunks).unwrap();
+    }
+    assert_eq!(tree.print_current_dir().unwrap(), "dir1\nsome.zip");
+    assert_eq!(
+        tree.traverse("dir1").unwrap().print_current_dir().unwrap(),
+        "new.txt"
+    );
+    assert_eq!(files.len(), 1);
+    assert_eq!(chunks.get(&chunk_id).map(|c| c.len()), Some(1));
+    Ok(())
+}
+
+#[test]
+fn test_remove_replicas_keeps_last_replica() {
+    let mut tree = build_tree().unwrap();
+    let (mut files, mut chunks) = (HashMap::new(), HashMap::new());
+    let (file_id, chunk_id) = (Uuid::new_v4(), Uuid::new_v4());
+    let replicas = (0..2)
+        .map(|_| Chunk::new(chunk_id, file_id, Uuid::new_v4()))
+        .collect::<Vec<_>>();
+    chunks.insert(chunk_id, replicas.iter().cloned().collect());
+
+    let op = Operation::RemoveReplicas {
+        replicas: replicas.clone(),
+    };
+    let removed = op.apply(&mut tree, &mut files, &mut chunks).unwrap();
+    assert_eq!(removed, vec![replicas[0]]);
+    assert_eq!(chunks.get(&chunk_id).map(|c| c.len()), Some(1));
+}
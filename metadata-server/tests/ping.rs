@@ -98,3 +98,73 @@ async fn test_ping_server_when_already_exists() -> std::io::Result<()> {
     assert!(s.latest_ping_time.signed_duration_since(old_time) > Duration::seconds(0));
     Ok(())
 }
+
+#[actix_rt::test]
+async fn test_ping_server_updates_stats() -> std::io::Result<()> {
+    let mut map = HashMap::new();
+    let mut s = ChunkServer::new(
+        Uuid::from_str("1a6e7006-12a7-4935-b8c0-58fa7ea84b09").unwrap(),
+        "http://localhost:7654".into(),
+    );
+    s.available_space = 100;
+    s.total_space = 200;
+    s.chunks_count = 3;
+    map.insert(s.id, s);
+    let servers: ServersMap = Arc::new(RwLock::new(map));
+    let server = init_service(
+        App::new()
+            .data(servers.clone())
+            .service(web::scope("/api").service(chunk_server_ping)),
+    )
+    .await;
+
+    let req = TestRequest::post()
+        .uri("/api/ping")
+        .insert_header((
+            "x-ccfs-chunk-server-id",
+            "1a6e7006-12a7-4935-b8c0-58fa7ea84b09",
+        ))
+        .insert_header(("x-ccfs-chunk-server-address", "http://localhost:7654"))
+        .insert_header(("x-ccfs-chunk-server-available-space", "50"))
+        .insert_header(("x-ccfs-chunk-server-total-space", "200"))
+        .insert_header(("x-ccfs-chunk-server-chunks-count", "4"))
+        .insert_header(("x-ccfs-chunk-server-pending-requests", "2"))
+        .insert_header(("x-ccfs-chunk-server-zone", "rack-1"))
+        .to_request();
+    let resp = call_service(&server, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let servers_map = servers.read().await;
+    let s = servers_map.values().next().unwrap();
+    assert_eq!(s.available_space, 50);
+    assert_eq!(s.total_space, 200);
+    assert_eq!(s.chunks_count, 4);
+    assert_eq!(s.pending_requests, 2);
+    assert_eq!(s.zone.as_deref(), Some("rack-1"));
+    Ok(())
+}
+
+#[actix_rt::test]
+async fn test_ping_server_invalid_stats() -> std::io::Result<()> {
+    let servers: ServersMap = Arc::new(RwLock::new(HashMap::new()));
+    let server = init_service(
+        App::new()
+            .data(servers.clone())
+            .service(web::scope("/api").service(chunk_server_ping)),
+    )
+    .await;
+
+    let req = TestRequest::post()
+        .uri("/api/ping")
+        .insert_header((
+            "x-ccfs-chunk-server-id",
+            "1a6e7006-12a7-4935-b8c0-58fa7ea84b09",
+        ))
+        .insert_header(("x-ccfs-chunk-server-address", "http://localhost:7654"))
+        .insert_header(("x-ccfs-chunk-server-available-space", "-1"))
+        .to_request();
+    let resp = call_service(&server, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    assert!(servers.read().await.is_empty());
+    Ok(())
+}
@@ -0,0 +1,104 @@
+use ccfs_commons::{ChunkServer, CHUNK_SIZE};
+use metadata_server::placement::{
+    candidates, spread_across_zones, BalancedPlacement, PlacementPolicy, RandomPlacement,
+};
+use std::collections::HashSet;
+use uuid::Uuid;
+
+fn servers(stats: &[(u64, u64)]) -> Vec<ChunkServer> {
+    stats
+        .iter()
+        .map(|(available_space, pending_requests)| {
+            let mut s = ChunkServer::new(Uuid::new_v4(), "http://localhost".into());
+            s.available_space = *available_space;
+            s.pending_requests = *pending_requests;
+            s
+        })
+        .collect()
+}
+
+#[test]
+fn test_balanced_placement_prefers_free_space_and_low_load() {
+    let servers = servers(&[(CHUNK_SIZE, 0), (CHUNK_SIZE * 10, 0), (CHUNK_SIZE * 10, 9)]);
+    let placement = BalancedPlacement.place(&servers, &[Uuid::new_v4()], 2);
+    let ids = placement[0]
+        .servers
+        .iter()
+        .map(|s| s.id)
+        .collect::<Vec<_>>();
+    assert_eq!(ids, vec![servers[1].id, servers[0].id]);
+}
+
+#[test]
+fn test_balanced_placement_spreads_chunks() {
+    // servers which didn't report their stats yet
+    let servers = servers(&[(0, 0), (0, 0), (0, 0)]);
+    let chunks = (0..3).map(|_| Uuid::new_v4()).collect::<Vec<_>>();
+    let placement = BalancedPlacement.place(&servers, &chunks, 1);
+    let primaries = placement
+        .iter()
+        .map(|p| p.servers[0].id)
+        .collect::<HashSet<_>>();
+    assert_eq!(primaries.len(), 3);
+}
+
+#[test]
+fn test_balanced_placement_spreads_replicas_across_zones() {
+    let mut servers = servers(&[(CHUNK_SIZE * 10, 0), (CHUNK_SIZE * 9, 0), (CHUNK_SIZE, 0)]);
+    servers[0].zone = Some("rack-1".into());
+    servers[1].zone = Some("rack-1".into());
+    servers[2].zone = Some("rack-2".into());
+    let placement = BalancedPlacement.place(&servers, &[Uuid::new_v4()], 2);
+    let ids = placement[0]
+        .servers
+        .iter()
+        .map(|s| s.id)
+        .collect::<Vec<_>>();
+    assert_eq!(ids, vec![servers[0].id, servers[2].id]);
+}
+
+#[test]
+fn test_spread_across_zones() {
+    let mut servers = servers(&[(0, 0), (0, 0), (0, 0), (0, 0), (0, 0)]);
+    let zones = [Some("a"), Some("b"), Some("b"), None, Some("c")];
+    for (s, zone) in servers.iter_mut().zip(zones.iter()) {
+        s.zone = zone.map(String::from);
+    }
+    let used_zones = ["a"].iter().cloned().collect();
+    let ordered = spread_across_zones(servers.iter().collect(), &used_zones)
+        .iter()
+        .map(|s| s.id)
+        .collect::<Vec<_>>();
+    let expected = [1, 3, 4, 0, 2]
+        .iter()
+        .map(|i| servers[*i].id)
+        .collect::<Vec<_>>();
+    assert_eq!(ordered, expected);
+}
+
+#[test]
+fn test_random_placement_distinct_servers() {
+    let servers = servers(&[(0, 0), (0, 0)]);
+    let chunk_id = Uuid::new_v4();
+    let placement = RandomPlacement.place(&servers, &[chunk_id], 3);
+    assert_eq!(placement[0].chunk_id, chunk_id);
+    let ids = placement[0]
+        .servers
+        .iter()
+        .map(|s| s.id)
+        .collect::<HashSet<_>>();
+    assert_eq!(ids.len(), 2);
+}
+
+#[test]
+fn test_candidates_skip_full_servers() {
+    let mut servers = servers(&[(0, 0), (CHUNK_SIZE - 1, 0), (CHUNK_SIZE, 0)]);
+    for s in servers.iter_mut().skip(1) {
+        s.total_space = CHUNK_SIZE * 2;
+    }
+    let ids = candidates(&servers)
+        .iter()
+        .map(|s| s.id)
+        .collect::<Vec<_>>();
+    assert_eq!(ids, vec![servers[0].id, servers[2].id]);
+}
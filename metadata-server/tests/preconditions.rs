@@ -0,0 +1,167 @@
+mod utils;
+
+use actix_http::http::StatusCode;
+use actix_web::http::header::{ETAG, IF_MATCH};
+use actix_web::{test, web, App};
+use ccfs_commons::{Chunk, FileInfo, FileMetadata, FileUpload};
+use metadata_server::routes::api::{
+    create_file, get_file, remove_file, set_file_versions, signal_chuck_upload_completed,
+};
+use metadata_server::{ChunksMap, DeletionQueue, FilesMap, ServersMap};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use test::{call_service, init_service, read_response_json, TestRequest};
+use tokio::sync::RwLock;
+use utils::{test_placement, test_raft};
+use uuid::Uuid;
+
+fn new_file() -> (FileMetadata, Uuid) {
+    let file = FileMetadata::create_file("test.txt".into(), 10, vec![Uuid::new_v4()]);
+    let file_id = match &file.file_info {
+        FileInfo::File { id, .. } => *id,
+        _ => unreachable!(),
+    };
+    (file, file_id)
+}
+
+#[actix_rt::test]
+async fn test_conditional_file_changes() -> std::io::Result<()> {
+    let (_raft_dir, raft) = test_raft().await;
+    let mut tree = FileMetadata::create_root();
+    let (file, _) = new_file();
+    tree.children_mut().unwrap().insert(file.name.clone(), file);
+    let metadata_tree = Arc::new(RwLock::new(tree));
+    let server = init_service(
+        App::new()
+            .data(raft)
+            .data(FilesMap::default())
+            .data(ChunksMap::default())
+            .data(DeletionQueue::default())
+            .data(metadata_tree.clone())
+            .service(
+                web::scope("/api")
+                    .service(get_file)
+                    .service(remove_file)
+                    .service(set_file_versions),
+            ),
+    )
+    .await;
+
+    let req = TestRequest::get()
+        .uri("/api/files?path=/test.txt")
+        .to_request();
+    let resp = call_service(&server, req).await;
+    assert_eq!(resp.headers().get(ETAG).unwrap(), "\"1\"");
+
+    for (value, status) in [
+        ("2", StatusCode::CONFLICT),
+        ("latest", StatusCode::BAD_REQUEST),
+        ("\"1\"", StatusCode::OK),
+    ]
+    .iter()
+    {
+        let req = TestRequest::put()
+            .uri("/api/files/versions?path=/test.txt&versions=1")
+            .insert_header((IF_MATCH, *value))
+            .to_request();
+        let resp = call_service(&server, req).await;
+        assert_eq!(resp.status(), *status);
+    }
+
+    let req = TestRequest::delete()
+        .uri("/api/files?path=/test.txt")
+        .insert_header((IF_MATCH, "0"))
+        .to_request();
+    let resp = call_service(&server, req).await;
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+    assert!(metadata_tree.read().await.traverse("/test.txt").is_ok());
+
+    let req = TestRequest::delete()
+        .uri("/api/files?path=/test.txt")
+        .insert_header((IF_MATCH, "1"))
+        .to_request();
+    let resp = call_service(&server, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert!(metadata_tree.read().await.traverse("/test.txt").is_err());
+    Ok(())
+}
+
+#[actix_rt::test]
+async fn test_overwritten_upload_is_rejected() -> std::io::Result<()> {
+    let (_raft_dir, raft) = test_raft().await;
+    let files: FilesMap = Arc::new(RwLock::new(HashMap::new()));
+    let deletion_queue: DeletionQueue = Arc::new(RwLock::new(HashSet::new()));
+    let metadata_tree = Arc::new(RwLock::new(FileMetadata::create_root()));
+    let server = init_service(
+        App::new()
+            .data(raft)
+            .data(ChunksMap::default())
+            .data(ServersMap::default())
+            .data(test_placement())
+            .data(deletion_queue.clone())
+            .data(files.clone())
+            .data(metadata_tree.clone())
+            .service(
+                web::scope("/api")
+                    .service(create_file)
+                    .service(signal_chuck_upload_completed),
+            ),
+    )
+    .await;
+
+    // the file doesn't exist yet, so both uploads expect version 0
+    let (first, first_id) = new_file();
+    let (second, second_id) = new_file();
+    for file in [&first, &second].iter() {
+        let req = TestRequest::post()
+            .uri("/api/files/upload")
+            .insert_header((IF_MATCH, "0"))
+            .set_json(file)
+            .to_request();
+        let upload: FileUpload = read_response_json(&server, req).await;
+        assert_eq!(upload.file.version, 1);
+    }
+
+    let chunk = Chunk::new(first.chunks().unwrap()[0], first_id, Uuid::new_v4());
+    let req = TestRequest::post()
+        .uri("/api/chunk/completed")
+        .set_json(&chunk)
+        .to_request();
+    let resp = call_service(&server, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(
+        metadata_tree
+            .read()
+            .await
+            .traverse("/test.txt")
+            .unwrap()
+            .version,
+        1
+    );
+
+    // the second upload would silently replace the first one, so it's canceled
+    let chunk = Chunk::new(second.chunks().unwrap()[0], second_id, Uuid::new_v4());
+    let req = TestRequest::post()
+        .uri("/api/chunk/completed")
+        .set_json(&chunk)
+        .to_request();
+    let resp = call_service(&server, req).await;
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+    assert!(!files.read().await.contains_key(&second_id));
+    assert!(deletion_queue.read().await.contains(&chunk));
+    let tree = metadata_tree.read().await;
+    let file = tree.traverse("/test.txt").unwrap();
+    assert_eq!(file.chunks().unwrap(), first.chunks().unwrap());
+
+    // a new upload can't expect a file version which doesn't exist
+    let (third, _) = new_file();
+    drop(tree);
+    let req = TestRequest::post()
+        .uri("/api/files/upload")
+        .insert_header((IF_MATCH, "0"))
+        .set_json(&third)
+        .to_request();
+    let resp = call_service(&server, req).await;
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+    Ok(())
+}
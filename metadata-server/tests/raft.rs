@@ -0,0 +1,455 @@
+mod utils;
+
+use actix_http::http::{header::LOCATION, StatusCode};
+use actix_web::{test, web, App};
+use ccfs_commons::{FileMetadata, FileUpload};
+use futures::FutureExt;
+use metadata_server::jobs::snapshot::{write_snapshot, Snapshot};
+use metadata_server::operations::Operation;
+use metadata_server::raft::{commit, commit_and_apply, lock_writes, RaftMessage, RaftNode, Role};
+use metadata_server::routes::api::{chunk_server_ping, create_file};
+use metadata_server::{ChunksMap, DeletionQueue, FileMetadataTree, FilesMap, ServersMap};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use tempfile::{tempdir, TempDir};
+use test::{call_service, init_service, read_body_json, TestRequest};
+use tokio::sync::mpsc::UnboundedReceiver;
+use tokio::sync::Mutex;
+use tokio::time::{sleep, timeout, Duration};
+use utils::{raft_config, test_node, test_placement};
+use uuid::Uuid;
+
+struct TestCluster {
+    _dir: TempDir,
+    nodes: HashMap<u64, RaftNode>,
+    outboxes: HashMap<u64, UnboundedReceiver<(u64, RaftMessage)>>,
+    /// Nodes whose messages are dropped
+    disconnected: HashSet<u64>,
+}
+
+impl TestCluster {
+    /// Creates a cluster where the elections are started only by the ticked nodes
+    async fn new(size: u64) -> Self {
+        let dir = tempdir().unwrap();
+        let mut nodes = HashMap::new();
+        let mut outboxes = HashMap::new();
+        for id in 1..=size {
+            let peers = (1..=size).filter(|p| *p != id).collect::<Vec<_>>();
+            let mut config = raft_config(dir.path(), id, &peers);
+            config.election_timeout = Duration::from_millis(1);
+            let (node, outbox) = test_node(config, dir.path()).await;
+            nodes.insert(id, node);
+            outboxes.insert(id, outbox);
+        }
+        Self {
+            _dir: dir,
+            nodes,
+            outboxes,
+            disconnected: HashSet::new(),
+        }
+    }
+
+    fn node(&self, id: u64) -> &RaftNode {
+        self.nodes.get(&id).unwrap()
+    }
+
+    fn node_mut(&mut self, id: u64) -> &mut RaftNode {
+        self.nodes.get_mut(&id).unwrap()
+    }
+
+    /// Delivers the sent messages until there are no more of them
+    async fn deliver(&mut self) {
+        loop {
+            let mut messages = Vec::new();
+            for (from, outbox) in self.outboxes.iter_mut() {
+                while let Some(Some((to, message))) = outbox.recv().now_or_never() {
+                    messages.push((*from, to, message));
+                }
+            }
+            if messages.is_empty() {
+                break;
+            }
+            for (from, to, message) in messages {
+                if self.disconnected.contains(&from) || self.disconnected.contains(&to) {
+                    continue;
+                }
+                self.node_mut(to).handle(from, message).await.unwrap();
+            }
+        }
+    }
+
+    /// Lets the node's timers expire, and delivers the messages it sends
+    async fn tick(&mut self, id: u64, wait: u64) {
+        sleep(Duration::from_millis(wait)).await;
+        self.node_mut(id).tick().await.unwrap();
+        self.deliver().await;
+    }
+}
+
+fn create_dir_op(name: &str) -> Operation {
+    Operation::CreateFile {
+        path: "/".into(),
+        file: FileMetadata::create_dir(name.into()),
+    }
+}
+
+#[actix_rt::test]
+async fn test_single_node_is_leader() -> std::io::Result<()> {
+    let dir = tempdir()?;
+    let (mut node, _) = test_node(raft_config(dir.path(), 1, &[]), dir.path()).await;
+    assert!(node.is_leader());
+    assert_eq!((node.term(), node.commit_index()), (1, 1));
+    let seq = node.propose(create_dir_op("dir1")).await.unwrap();
+    assert_eq!(node.commit_index(), seq);
+    let (_, entries) = node.take_committed();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].operation, Operation::Noop);
+    Ok(())
+}
+
+#[actix_rt::test]
+async fn test_leader_election() -> std::io::Result<()> {
+    let mut cluster = TestCluster::new(3).await;
+    cluster.tick(1, 5).await;
+
+    let leader = cluster.node(1);
+    assert_eq!((leader.role(), leader.term()), (Role::Leader, 1));
+    for id in 2..=3 {
+        let follower = cluster.node(id);
+        assert_eq!((follower.role(), follower.term()), (Role::Follower, 1));
+        assert_eq!(follower.leader_id(), Some(1));
+        assert_eq!(follower.leader_address(), Some("http://node1:4000"));
+    }
+    Ok(())
+}
+
+#[actix_rt::test]
+async fn test_log_replication() -> std::io::Result<()> {
+    let mut cluster = TestCluster::new(3).await;
+    cluster.tick(1, 5).await;
+
+    let operation = create_dir_op("dir1");
+    let seq = cluster
+        .node_mut(1)
+        .propose(operation.clone())
+        .await
+        .unwrap();
+    cluster.deliver().await;
+    assert_eq!(cluster.node(1).commit_index(), seq);
+    // followers learn about the commit index with the next heartbeat
+    cluster.tick(1, 60).await;
+    for id in 1..=3 {
+        let node = cluster.node_mut(id);
+        assert_eq!((node.commit_index(), node.log().last_seq()), (seq, seq));
+        let (_, entries) = node.take_committed();
+        assert_eq!(entries.last().unwrap().operation, operation);
+        assert_eq!(node.last_applied(), seq);
+    }
+    Ok(())
+}
+
+#[actix_rt::test]
+async fn test_commit_requires_majority() -> std::io::Result<()> {
+    let mut cluster = TestCluster::new(3).await;
+    cluster.tick(1, 5).await;
+
+    cluster.disconnected = vec![2, 3].into_iter().collect();
+    let seq = cluster
+        .node_mut(1)
+        .propose(create_dir_op("dir1"))
+        .await
+        .unwrap();
+    cluster.deliver().await;
+    assert_eq!(cluster.node(1).commit_index(), seq - 1);
+
+    cluster.disconnected.remove(&2);
+    cluster.tick(1, 60).await;
+    assert_eq!(cluster.node(1).commit_index(), seq);
+    assert_eq!(cluster.node(3).log().last_seq(), seq - 1);
+    Ok(())
+}
+
+#[actix_rt::test]
+async fn test_conflicting_entries_are_replaced() -> std::io::Result<()> {
+    let mut cluster = TestCluster::new(3).await;
+    cluster.tick(1, 5).await;
+
+    // the isolated leader can't commit its entry
+    cluster.disconnected.insert(1);
+    let stale_seq = cluster
+        .node_mut(1)
+        .propose(create_dir_op("stale"))
+        .await
+        .unwrap();
+    cluster.deliver().await;
+
+    // node 2 is elected by node 3 in the next term
+    cluster.tick(2, 5).await;
+    assert_eq!(
+        (cluster.node(2).role(), cluster.node(2).term()),
+        (Role::Leader, 2)
+    );
+    cluster.disconnected.clear();
+    let operation = create_dir_op("dir1");
+    let seq = cluster
+        .node_mut(2)
+        .propose(operation.clone())
+        .await
+        .unwrap();
+    cluster.deliver().await;
+
+    let old_leader = cluster.node(1);
+    assert_eq!(old_leader.role(), Role::Follower);
+    assert_eq!(old_leader.leader_id(), Some(2));
+    let replaced = old_leader.log().entry(stale_seq).unwrap();
+    assert_eq!((replaced.term, &replaced.operation), (2, &Operation::Noop));
+    assert_eq!(old_leader.log().entry(seq).unwrap().operation, operation);
+    Ok(())
+}
+
+#[actix_rt::test]
+async fn test_vote_survives_restart() -> std::io::Result<()> {
+    let dir = tempdir()?;
+    let config = raft_config(dir.path(), 1, &[2, 3]);
+    let request_vote = RaftMessage::RequestVote {
+        term: 1,
+        last_log_seq: 0,
+        last_log_term: 0,
+    };
+    {
+        let (mut node, mut outbox) = test_node(config.clone(), dir.path()).await;
+        node.handle(2, request_vote.clone()).await.unwrap();
+        let vote = RaftMessage::Vote {
+            term: 1,
+            granted: true,
+        };
+        assert_eq!(outbox.recv().await, Some((2, vote)));
+    }
+    let (mut node, mut outbox) = test_node(config, dir.path()).await;
+    assert_eq!(node.term(), 1);
+    // the node already voted for node 2 in this term
+    node.handle(3, request_vote).await.unwrap();
+    let vote = RaftMessage::Vote {
+        term: 1,
+        granted: false,
+    };
+    assert_eq!(outbox.recv().await, Some((3, vote)));
+    Ok(())
+}
+
+#[actix_rt::test]
+async fn test_install_snapshot() -> std::io::Result<()> {
+    let mut cluster = TestCluster::new(3).await;
+    cluster.disconnected.insert(3);
+    cluster.tick(1, 5).await;
+    let seq = cluster
+        .node_mut(1)
+        .propose(create_dir_op("dir1"))
+        .await
+        .unwrap();
+    cluster.deliver().await;
+
+    // the leader compacts its log, so node 3 can catch up only with the snapshot
+    let mut tree = FileMetadata::create_root();
+    tree.insert_dir("dir1").unwrap();
+    let snapshot = Snapshot {
+        tree,
+        last_op_seq: seq,
+        last_op_term: 1,
+        ..Default::default()
+    };
+    let snapshot_path = cluster._dir.path().join("snapshot1");
+    let temp_path = cluster._dir.path().join("tmp_snapshot1");
+    write_snapshot(&snapshot_path, &temp_path, &snapshot)
+        .await
+        .unwrap();
+    let leader = cluster.node_mut(1);
+    leader.take_committed();
+    leader.compact_log(seq).await.unwrap();
+    assert_eq!(leader.log().snapshot_seq(), seq);
+
+    cluster.disconnected.clear();
+    cluster.tick(1, 60).await;
+    let node = cluster.node_mut(3);
+    assert_eq!(node.commit_index(), seq);
+    assert!(node.has_pending_snapshot());
+    let (received, entries) = node.take_committed();
+    assert_eq!(received, Some(snapshot));
+    assert!(entries.is_empty());
+    assert_eq!(node.last_applied(), seq);
+    Ok(())
+}
+
+#[actix_rt::test]
+async fn test_follower_redirects_writes() -> std::io::Result<()> {
+    let mut cluster = TestCluster::new(2).await;
+    cluster.tick(1, 5).await;
+    let follower = cluster.nodes.remove(&2).unwrap();
+    let server = init_service(
+        App::new()
+            .data(Arc::new(Mutex::new(follower)))
+            .data(FileMetadataTree::default())
+            .data(FilesMap::default())
+            .data(ChunksMap::default())
+            .data(ServersMap::default())
+            .data(test_placement())
+            .data(DeletionQueue::default())
+            .service(web::scope("/api").service(create_file)),
+    )
+    .await;
+
+    let req = TestRequest::post()
+        .uri("/api/files/upload?path=dir1")
+        .set_json(&FileMetadata::create_dir("dir2".into()))
+        .to_request();
+    let resp = call_service(&server, req).await;
+    assert_eq!(resp.status(), StatusCode::TEMPORARY_REDIRECT);
+    assert_eq!(
+        resp.headers().get(LOCATION).unwrap(),
+        "http://node1:4000/api/files/upload?path=dir1"
+    );
+    Ok(())
+}
+
+#[actix_rt::test]
+async fn test_commit_doesnt_block_reads() -> std::io::Result<()> {
+    let mut cluster = TestCluster::new(2).await;
+    cluster.tick(1, 5).await;
+    // the follower doesn't receive the entry, so it can't be committed
+    let raft = Arc::new(Mutex::new(cluster.nodes.remove(&1).unwrap()));
+    let tree = FileMetadataTree::default();
+    let files = FilesMap::default();
+    let chunks = ChunksMap::default();
+
+    let _writes = lock_writes(&raft).await;
+    let pending = commit_and_apply(&raft, create_dir_op("dir1"), &tree, &files, &chunks);
+    let reads = async {
+        sleep(Duration::from_millis(50)).await;
+        let wait = Duration::from_millis(10);
+        assert!(timeout(wait, tree.read()).await.is_ok());
+        assert!(timeout(wait, files.write()).await.is_ok());
+        assert!(timeout(wait, chunks.write()).await.is_ok());
+        // the other changes wait until the pending one is applied
+        assert!(timeout(wait, lock_writes(&raft)).await.is_err());
+    };
+    tokio::select! {
+        _ = pending => panic!("the entry was committed without the majority"),
+        _ = reads => {}
+    }
+    Ok(())
+}
+
+#[actix_rt::test]
+async fn test_upload_after_failover() -> std::io::Result<()> {
+    let mut cluster = TestCluster::new(3).await;
+    cluster.tick(1, 5).await;
+    assert!(cluster.node(1).is_leader());
+
+    // the chunk servers ping the followers as well, the servers map isn't replicated
+    let servers = ServersMap::default();
+    let follower = init_service(
+        App::new()
+            .data(servers.clone())
+            .service(web::scope("/api").service(chunk_server_ping)),
+    )
+    .await;
+    let server_id = Uuid::new_v4();
+    let req = TestRequest::post()
+        .uri("/api/ping")
+        .insert_header(("x-ccfs-chunk-server-id", server_id.to_string()))
+        .insert_header(("x-ccfs-chunk-server-address", "http://localhost:7654"))
+        .to_request();
+    assert_eq!(call_service(&follower, req).await.status(), StatusCode::OK);
+
+    cluster.disconnected.insert(1);
+    cluster.tick(2, 5).await;
+    assert!(cluster.node(2).is_leader());
+    let raft = Arc::new(Mutex::new(cluster.nodes.remove(&2).unwrap()));
+    let mut outbox = cluster.outboxes.remove(&2).unwrap();
+    let leader = init_service(
+        App::new()
+            .data(raft.clone())
+            .data(FileMetadataTree::default())
+            .data(FilesMap::default())
+            .data(ChunksMap::default())
+            .data(servers)
+            .data(test_placement())
+            .data(DeletionQueue::default())
+            .service(web::scope("/api").service(create_file)),
+    )
+    .await;
+
+    let file = FileMetadata::create_file("test.txt".into(), 10, vec![Uuid::new_v4()]);
+    let req = TestRequest::post()
+        .uri("/api/files/upload")
+        .set_json(&file)
+        .to_request();
+    let upload = call_service(&leader, req);
+    // the new leader commits the file with the vote of the remaining follower
+    let replicate = async {
+        loop {
+            sleep(Duration::from_millis(5)).await;
+            while let Some(Some((_, message))) = outbox.recv().now_or_never() {
+                cluster.node_mut(3).handle(2, message).await.unwrap();
+            }
+            let responses = cluster.outboxes.get_mut(&3).unwrap();
+            while let Some(Some((to, message))) = responses.recv().now_or_never() {
+                if to == 2 {
+                    raft.lock().await.handle(3, message).await.unwrap();
+                }
+            }
+        }
+    };
+    let resp = tokio::select! {
+        resp = upload => resp,
+        _ = replicate => unreachable!(),
+    };
+    assert_eq!(resp.status(), StatusCode::OK);
+    let upload: FileUpload = read_body_json(resp).await;
+    assert_eq!(upload.placement.len(), 1);
+    let servers = &upload.placement[0].servers;
+    assert!(servers.iter().any(|s| s.id == server_id));
+    Ok(())
+}
+
+#[actix_rt::test]
+async fn test_commit_of_compacted_entry() -> std::io::Result<()> {
+    let mut cluster = TestCluster::new(2).await;
+    cluster.tick(1, 5).await;
+    let raft = Arc::new(Mutex::new(cluster.nodes.remove(&1).unwrap()));
+    let mut outbox = cluster.outboxes.remove(&1).unwrap();
+
+    let pending = commit(&raft, create_dir_op("dir1"));
+    // the entry is committed together with the next one, and both are compacted
+    let replicate = async {
+        sleep(Duration::from_millis(5)).await;
+        let last = raft
+            .lock()
+            .await
+            .propose(create_dir_op("dir2"))
+            .await
+            .unwrap();
+        loop {
+            while let Some(Some((_, message))) = outbox.recv().now_or_never() {
+                cluster.node_mut(2).handle(1, message).await.unwrap();
+            }
+            let responses = cluster.outboxes.get_mut(&2).unwrap();
+            let mut raft = raft.lock().await;
+            while let Some(Some((_, message))) = responses.recv().now_or_never() {
+                raft.handle(2, message).await.unwrap();
+            }
+            if raft.commit_index() >= last {
+                raft.take_committed();
+                raft.compact_log(last).await.unwrap();
+                break;
+            }
+        }
+        futures::future::pending::<()>().await
+    };
+    let committed = tokio::select! {
+        committed = pending => committed,
+        _ = replicate => unreachable!(),
+    };
+    assert!(committed.is_ok());
+    Ok(())
+}
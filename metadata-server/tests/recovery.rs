@@ -0,0 +1,199 @@
+use ccfs_commons::CHUNK_SIZE;
+use ccfs_commons::{Chunk, ChunkMetadata, FileInfo, FileMetadata, FileStatus, StoredChunk};
+use httpmock::{Method, MockServer};
+use metadata_server::jobs::snapshot::load_snapshot;
+use metadata_server::operations::Operation;
+use metadata_server::oplog::OperationLog;
+use metadata_server::recovery::{rebuild_metadata, recover};
+use metadata_server::server_config::ServerConfig;
+use std::path::Path;
+use tempfile::tempdir;
+use uuid::Uuid;
+
+/// Creates the stored replicas of each chunk of the file, one on each server
+fn stored_file(path: &str, file: &FileMetadata, servers: &[Uuid]) -> Vec<StoredChunk> {
+    let file_id = match &file.file_info {
+        FileInfo::File { id, .. } => *id,
+        _ => unreachable!(),
+    };
+    let mut stored = Vec::new();
+    for (index, chunk_id) in file.chunks().unwrap().iter().enumerate() {
+        for server_id in servers {
+            stored.push(StoredChunk {
+                chunk: Chunk::new(*chunk_id, file_id, *server_id).with_checksum(1),
+                metadata: ChunkMetadata::new(path, file, index),
+            });
+        }
+    }
+    stored
+}
+
+fn test_config(dir: &Path) -> ServerConfig {
+    ServerConfig {
+        host: "127.0.0.1".into(),
+        port: 4000,
+        snapshot_interval: 10,
+        snapshot_dir_path: dir.into(),
+        snapshot_file_name: "snapshot".into(),
+        replication_interval: 3,
+        replication_factor: 3,
+        dead_server_timeout: 600,
+        deletion_interval: 5,
+        upload_lease: 3600,
+        upload_expiry_interval: 60,
+        node_id: 1,
+        peers: Vec::new(),
+        heartbeat_interval: 50,
+        election_timeout: 300,
+        placement_policy: Default::default(),
+    }
+}
+
+#[test]
+fn test_rebuild_metadata() {
+    let servers = [Uuid::new_v4(), Uuid::new_v4()];
+    let chunk_ids = vec![Uuid::new_v4(), Uuid::new_v4()];
+    let file = FileMetadata::create_file("a.txt".into(), CHUNK_SIZE + 5, chunk_ids.clone())
+        .with_replicas(Some(2));
+    let root_file = FileMetadata::create_file("b.txt".into(), 3, vec![Uuid::new_v4()]);
+    let mut stored = stored_file("/dir/subdir", &file, &servers);
+    stored.extend(stored_file("", &root_file, &servers[..1]));
+    // the order of the chunks is restored from their indexes
+    stored.reverse();
+
+    let snapshot = rebuild_metadata(stored);
+    assert_eq!(
+        snapshot.tree.print_subtree(),
+        "/\n├─ b.txt\n└─ dir\n   └─ subdir\n      └─ a.txt"
+    );
+    let recovered = snapshot.tree.traverse("/dir/subdir/a.txt").unwrap();
+    assert_eq!(recovered.name, "a.txt");
+    assert!(matches!(
+        &recovered.file_info,
+        FileInfo::File {
+            size,
+            chunks,
+            num_of_completed_chunks: 2,
+            status: FileStatus::Completed,
+            replicas: Some(2),
+            ..
+        } if *size == CHUNK_SIZE + 5 && *chunks == chunk_ids
+    ));
+    assert_eq!(snapshot.files.len(), 2);
+    assert_eq!(snapshot.chunks.len(), 3);
+    assert_eq!(snapshot.chunks[&chunk_ids[0]].len(), 2);
+    assert_eq!(snapshot.last_op_seq, 0);
+}
+
+#[test]
+fn test_rebuild_metadata_keeps_previous_versions() {
+    let server_id = Uuid::new_v4();
+    let old_file = FileMetadata::create_file("a.txt".into(), 3, vec![Uuid::new_v4()]);
+    let mut new_file = FileMetadata::create_file("a.txt".into(), 3, vec![Uuid::new_v4()]);
+    new_file.version = 2;
+    let mut stored = stored_file("/", &new_file, &[server_id]);
+    stored.extend(stored_file("/", &old_file, &[server_id]));
+
+    let snapshot = rebuild_metadata(stored);
+    let recovered = snapshot.tree.traverse("a.txt").unwrap();
+    assert_eq!(recovered.version, 2);
+    // the previous version is kept, so its chunks aren't collected as garbage
+    let previous = recovered.version(1).unwrap();
+    assert_eq!(previous.chunks().unwrap(), old_file.chunks().unwrap());
+    assert!(matches!(
+        recovered.file_info,
+        FileInfo::File {
+            kept_versions: 1,
+            ..
+        }
+    ));
+    assert_eq!(snapshot.files.len(), 2);
+    assert_eq!(snapshot.chunks.len(), 2);
+    assert!(snapshot.chunks.contains_key(&new_file.chunks().unwrap()[0]));
+    assert!(snapshot.chunks.contains_key(&old_file.chunks().unwrap()[0]));
+}
+
+#[test]
+fn test_rebuild_streamed_file() {
+    let chunk_ids = vec![Uuid::new_v4(), Uuid::new_v4()];
+    let file = FileMetadata::create_file("a.txt".into(), CHUNK_SIZE + 5, chunk_ids.clone());
+    let mut stored = stored_file("/", &file, &[Uuid::new_v4()]);
+    // the chunks of a streamed file record the size written when they were uploaded
+    stored[0].metadata.size = CHUNK_SIZE;
+    stored.reverse();
+
+    let snapshot = rebuild_metadata(stored);
+    let recovered = snapshot.tree.traverse("a.txt").unwrap();
+    assert!(matches!(recovered.file_info, FileInfo::File { size, .. } if size == CHUNK_SIZE + 5));
+    assert_eq!(recovered.chunks().unwrap(), &chunk_ids);
+}
+
+#[test]
+fn test_rebuild_metadata_missing_chunk() {
+    let chunk_ids = vec![Uuid::new_v4(), Uuid::new_v4()];
+    let file = FileMetadata::create_file("a.txt".into(), CHUNK_SIZE + 5, chunk_ids.clone());
+    let mut stored = stored_file("/", &file, &[Uuid::new_v4()]);
+    stored.remove(0);
+
+    let snapshot = rebuild_metadata(stored);
+    let recovered = snapshot.tree.traverse("a.txt").unwrap().chunks().unwrap();
+    // the missing chunk gets a new id, without any replicas
+    assert_eq!(recovered.len(), 2);
+    assert_ne!(recovered[0], chunk_ids[0]);
+    assert_eq!(recovered[1], chunk_ids[1]);
+    assert!(!snapshot.chunks.contains_key(&recovered[0]));
+}
+
+#[actix_rt::test]
+async fn test_recover() -> std::io::Result<()> {
+    let temp = tempdir()?;
+    let config = test_config(temp.path());
+    let file = FileMetadata::create_file("a.txt".into(), 3, vec![Uuid::new_v4()]);
+    let stored = stored_file("/dir", &file, &[Uuid::new_v4()]);
+    let chunk_server = MockServer::start();
+    let metadata_mock = chunk_server.mock(|when, then| {
+        when.method(Method::GET).path("/api/chunks/metadata");
+        then.status(200)
+            .header("Content-Type", "application/json")
+            .body(serde_json::to_string(&stored).unwrap());
+    });
+
+    // the entries of the lost snapshot's log are superseded by the recovered one
+    let mut oplog = OperationLog::open(&config.oplog_path(), 0, 0)
+        .await
+        .unwrap();
+    oplog.append(3, Operation::Noop).await.unwrap();
+
+    let snapshot = recover(&config, &[chunk_server.base_url()]).await.unwrap();
+    metadata_mock.assert();
+    assert_eq!((snapshot.last_op_seq, snapshot.last_op_term), (1, 3));
+    assert_eq!(snapshot.files.len(), 1);
+    assert!(snapshot.tree.traverse("/dir/a.txt").is_ok());
+    assert_eq!(
+        load_snapshot(&config.snapshot_path()).await.unwrap(),
+        snapshot
+    );
+
+    // the existing snapshot isn't overwritten
+    let err = recover(&config, &[chunk_server.base_url()])
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("already exists"));
+    metadata_mock.assert_hits(1);
+    Ok(())
+}
+
+#[actix_rt::test]
+async fn test_recover_unreachable_server() -> std::io::Result<()> {
+    let temp = tempdir()?;
+    let config = test_config(temp.path());
+    let chunk_server = MockServer::start();
+    chunk_server.mock(|when, then| {
+        when.method(Method::GET).path("/api/chunks/metadata");
+        then.status(500);
+    });
+
+    assert!(recover(&config, &[chunk_server.base_url()]).await.is_err());
+    assert!(!config.snapshot_path().exists());
+    Ok(())
+}
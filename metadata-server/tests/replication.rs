@@ -0,0 +1,267 @@
+mod utils;
+
+use actix_web::{test, web, App};
+use ccfs_commons::{Chunk, ChunkServer, FileInfo, FileMetadata, CHUNK_SIZE};
+use chrono::{Duration, Utc};
+use httpmock::{Method, MockServer};
+use metadata_server::jobs::replication::{
+    check_replication, find_excess_replicas, replicate_files, ChunkReplicationStatus,
+    ReplicationReport,
+};
+use metadata_server::routes::api::get_replication_status;
+use metadata_server::server_config::ServerConfig;
+use metadata_server::{Raft, ReplicationStatus};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use test::{init_service, read_response_json, TestRequest};
+use tokio::sync::{Mutex, RwLock};
+use utils::{raft_config, test_node, test_raft};
+use uuid::Uuid;
+
+fn server(last_ping_secs_ago: i64) -> ChunkServer {
+    let mut s = ChunkServer::new(Uuid::new_v4(), "http://localhost".into());
+    s.latest_ping_time = s.latest_ping_time - Duration::seconds(last_ping_secs_ago);
+    s
+}
+
+fn ids(statuses: &[ChunkReplicationStatus]) -> Vec<Uuid> {
+    statuses.iter().map(|s| s.chunk_id).collect()
+}
+
+#[test]
+fn test_check_replication() {
+    let (active1, active2, down, dead) = (server(0), server(0), server(30), server(120));
+    let chunk_ids = (0..6).map(|_| Uuid::new_v4()).collect::<Vec<_>>();
+    let mut tree = FileMetadata::create_root();
+    tree.insert_dir("dir").unwrap();
+    let file = FileMetadata::create_file("a.txt".into(), 10, chunk_ids.clone());
+    let file_id = match &file.file_info {
+        FileInfo::File { id, .. } => *id,
+        _ => unreachable!(),
+    };
+    tree.traverse_mut("dir")
+        .unwrap()
+        .children_mut()
+        .unwrap()
+        .insert(file.name.clone(), file);
+
+    let replicas = vec![
+        // fully replicated
+        vec![&active1, &active2],
+        // one of the replicas is on a dead server
+        vec![&active1, &dead],
+        // the only live replica is on a server which is down
+        vec![&down, &dead],
+        // all replicas are on dead servers
+        vec![&dead],
+        // no replicas at all
+        vec![],
+    ];
+    let mut chunks = HashMap::new();
+    for (chunk_id, servers) in chunk_ids.iter().zip(replicas.iter()) {
+        let set = servers
+            .iter()
+            .map(|s| Chunk::new(*chunk_id, file_id, s.id))
+            .collect::<HashSet<_>>();
+        chunks.insert(*chunk_id, set);
+    }
+    // the last chunk isn't registered in the chunks map
+    let servers = [&active1, &active2, &down, &dead]
+        .iter()
+        .map(|s| (s.id, (*s).clone()))
+        .collect::<HashMap<_, _>>();
+
+    let timeout = Duration::seconds(60);
+    let report = check_replication(&tree, &chunks, &servers, 2, timeout, Utc::now());
+    assert_eq!(ids(&report.under_replicated), vec![chunk_ids[1]]);
+    assert_eq!(report.under_replicated[0].live_replicas, 1);
+    assert_eq!(report.under_replicated[0].required_replicas, 2);
+    assert_eq!(report.under_replicated[0].path, "/dir/a.txt");
+    assert_eq!(ids(&report.unavailable), vec![chunk_ids[2]]);
+    assert_eq!(
+        ids(&report.lost),
+        vec![chunk_ids[3], chunk_ids[4], chunk_ids[5]]
+    );
+
+    // the down server didn't ping since the metadata server restarted
+    let unknown = servers
+        .into_iter()
+        .filter(|(id, _)| *id != down.id)
+        .collect::<HashMap<_, _>>();
+    let report = check_replication(&tree, &chunks, &unknown, 2, timeout, Utc::now());
+    assert_eq!(ids(&report.unavailable), vec![chunk_ids[2]]);
+    let started = Utc::now() - Duration::seconds(120);
+    let report = check_replication(&tree, &chunks, &unknown, 2, timeout, started);
+    assert!(report.unavailable.is_empty());
+    assert_eq!(
+        ids(&report.lost),
+        vec![chunk_ids[2], chunk_ids[3], chunk_ids[4], chunk_ids[5]]
+    );
+}
+
+#[test]
+fn test_check_replication_most_endangered_first() {
+    let servers = (0..3).map(|_| server(0)).collect::<Vec<_>>();
+    let mut tree = FileMetadata::create_root();
+    let mut chunks = HashMap::new();
+    // file `i` has `i + 1` live replicas of its chunk
+    for i in 0..3 {
+        let chunk_id = Uuid::new_v4();
+        let file = FileMetadata::create_file(format!("{}.txt", i), 10, vec![chunk_id]);
+        let file_id = match &file.file_info {
+            FileInfo::File { id, .. } => *id,
+            _ => unreachable!(),
+        };
+        let set = servers[..=i]
+            .iter()
+            .map(|s| Chunk::new(chunk_id, file_id, s.id))
+            .collect::<HashSet<_>>();
+        chunks.insert(chunk_id, set);
+        tree.children_mut().unwrap().insert(file.name.clone(), file);
+    }
+    let servers = servers
+        .into_iter()
+        .map(|s| (s.id, s))
+        .collect::<HashMap<_, _>>();
+
+    let report = check_replication(
+        &tree,
+        &chunks,
+        &servers,
+        4,
+        Duration::seconds(60),
+        Utc::now(),
+    );
+    let live = report
+        .under_replicated
+        .iter()
+        .map(|s| s.live_replicas)
+        .collect::<Vec<_>>();
+    assert_eq!(live, vec![1, 2, 3]);
+}
+
+#[test]
+fn test_find_excess_replicas() {
+    let chunk_id = Uuid::new_v4();
+    let mut servers = (0..4).map(|_| server(0)).collect::<Vec<_>>();
+    // the least suitable server is the only one in its zone, so it's kept
+    for (s, (zone, space)) in servers
+        .iter_mut()
+        .zip([("a", 100), ("a", 50), ("a", 10), ("b", 1)])
+    {
+        s.zone = Some(zone.into());
+        s.available_space = space * CHUNK_SIZE;
+    }
+    let mut tree = FileMetadata::create_root();
+    let file = FileMetadata::create_file("a.txt".into(), 10, vec![chunk_id]).with_replicas(Some(2));
+    let file_id = match &file.file_info {
+        FileInfo::File { id, .. } => *id,
+        _ => unreachable!(),
+    };
+    tree.children_mut().unwrap().insert(file.name.clone(), file);
+    let replicas = servers
+        .iter()
+        .map(|s| Chunk::new(chunk_id, file_id, s.id))
+        .collect::<HashSet<_>>();
+    let chunks = vec![(chunk_id, replicas)].into_iter().collect();
+    let servers_map = servers
+        .iter()
+        .map(|s| (s.id, s.clone()))
+        .collect::<HashMap<_, _>>();
+
+    let report = check_replication(
+        &tree,
+        &chunks,
+        &servers_map,
+        3,
+        Duration::seconds(60),
+        Utc::now(),
+    );
+    assert_eq!(ids(&report.over_replicated), vec![chunk_id]);
+    assert_eq!(report.over_replicated[0].live_replicas, 4);
+    let excess = find_excess_replicas(&report, &chunks, &servers_map)
+        .iter()
+        .map(|c| c.server_id)
+        .collect::<HashSet<_>>();
+    let expected = vec![servers[1].id, servers[2].id].into_iter().collect();
+    assert_eq!(excess, expected);
+}
+
+#[actix_rt::test]
+async fn test_get_replication_status() -> std::io::Result<()> {
+    let status = ReplicationStatus::default();
+    let server = init_service(
+        App::new()
+            .data(status.clone())
+            .service(web::scope("/api").service(get_replication_status)),
+    )
+    .await;
+
+    let req = TestRequest::get().uri("/api/admin/chunks").to_request();
+    let data: ReplicationReport = read_response_json(&server, req).await;
+    assert_eq!(data, ReplicationReport::default());
+    Ok(())
+}
+
+#[actix_rt::test]
+async fn test_only_leader_replicates() -> std::io::Result<()> {
+    let (from, to) = (MockServer::start(), MockServer::start());
+    let replicate_mock = from.mock(|when, then| {
+        when.method(Method::POST).path("/api/replicate");
+        then.status(200);
+    });
+    let servers = [&from, &to]
+        .iter()
+        .map(|mock| {
+            let s = ChunkServer::new(Uuid::new_v4(), mock.base_url());
+            (s.id, s)
+        })
+        .collect::<HashMap<_, _>>();
+    let from_id = servers
+        .values()
+        .find(|s| s.address == from.base_url())
+        .unwrap()
+        .id;
+    let chunk_id = Uuid::new_v4();
+    let mut tree = FileMetadata::create_root();
+    let file = FileMetadata::create_file("a.txt".into(), 10, vec![chunk_id]);
+    let file_id = match &file.file_info {
+        FileInfo::File { id, .. } => *id,
+        _ => unreachable!(),
+    };
+    tree.children_mut().unwrap().insert(file.name.clone(), file);
+    let replica = Chunk::new(chunk_id, file_id, from_id);
+    let chunks = vec![(chunk_id, vec![replica].into_iter().collect())];
+
+    let config: ServerConfig = serde_yaml::from_str(
+        "{host: 127.0.0.1, port: 4000, snapshot_interval: 10, snapshot_dir_path: ., \
+          snapshot_file_name: snapshot, replication_interval: 3, replication_factor: 2, \
+          deletion_interval: 5, node_id: 1, heartbeat_interval: 50, election_timeout: 300}",
+    )
+    .unwrap();
+    let config = Arc::new(config);
+    let (_leader_dir, leader) = test_raft().await;
+    let follower_dir = tempfile::tempdir()?;
+    let follower_config = raft_config(follower_dir.path(), 2, &[1]);
+    let (follower, _) = test_node(follower_config, follower_dir.path()).await;
+    let follower: Raft = Arc::new(Mutex::new(follower));
+
+    for (raft, hits) in [(follower, 0), (leader, 1)].iter() {
+        let status = ReplicationStatus::default();
+        replicate_files(
+            config.clone(),
+            Arc::new(RwLock::new(tree.clone())),
+            Arc::new(RwLock::new(chunks.iter().cloned().collect())),
+            Arc::new(RwLock::new(servers.clone())),
+            raft.clone(),
+            status.clone(),
+            Utc::now(),
+        )
+        .await
+        .unwrap();
+        // the followers still report the under-replicated chunks
+        assert_eq!(ids(&status.read().await.under_replicated), vec![chunk_id]);
+        replicate_mock.assert_hits(*hits);
+    }
+    Ok(())
+}
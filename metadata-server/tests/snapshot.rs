@@ -0,0 +1,106 @@
+mod utils;
+
+use ccfs_commons::test_utils::build_tree;
+use ccfs_commons::{Chunk, FileInfo, FileMetadata};
+use metadata_server::jobs::snapshot::{create_snapshot, load_snapshot};
+use metadata_server::operations::Operation;
+use metadata_server::oplog::read_entries;
+use metadata_server::raft::{apply_committed, commit};
+use metadata_server::{ChunksMap, FilesMap};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use tempfile::tempdir;
+use tokio::fs::write;
+use tokio::sync::RwLock;
+use utils::test_raft;
+use uuid::Uuid;
+
+#[actix_rt::test]
+async fn test_load_not_existing_snapshot() -> std::io::Result<()> {
+    let temp = tempdir()?;
+    let snapshot = load_snapshot(&temp.path().join("snapshot")).await.unwrap();
+    assert_eq!(snapshot.tree.name, "/");
+    assert!(snapshot.tree.children().unwrap().is_empty());
+    assert!(snapshot.files.is_empty());
+    assert!(snapshot.chunks.is_empty());
+    Ok(())
+}
+
+#[actix_rt::test]
+async fn test_snapshot_roundtrip() -> std::io::Result<()> {
+    let temp = tempdir()?;
+    let snapshot_path = Arc::new(temp.path().join("snapshot"));
+    let temp_path = Arc::new(temp.path().join("tmp_snapshot"));
+
+    let chunk_id = Uuid::new_v4();
+    let file = FileMetadata::create_file("test.txt".into(), 10, vec![chunk_id]);
+    let file_id = match &file.file_info {
+        FileInfo::File { id, .. } => *id,
+        _ => unreachable!(),
+    };
+    let chunk = Chunk::new(chunk_id, file_id, Uuid::new_v4());
+    let mut files_map = HashMap::new();
+    files_map.insert(file_id, ("/dir1".to_string(), file));
+    let mut chunks_map = HashMap::new();
+    chunks_map.insert(chunk_id, vec![chunk].into_iter().collect::<HashSet<_>>());
+    let tree = Arc::new(RwLock::new(build_tree().unwrap()));
+    let files: FilesMap = Arc::new(RwLock::new(files_map));
+    let chunks: ChunksMap = Arc::new(RwLock::new(chunks_map));
+    let (_raft_dir, raft) = test_raft().await;
+    {
+        // applies the entry appended by the new leader
+        let mut tree = tree.write().await;
+        let mut files = files.write().await;
+        let mut chunks = chunks.write().await;
+        apply_committed(&raft, &mut tree, &mut files, &mut chunks).await;
+    }
+    // committed, but not applied yet
+    let operation = Operation::RemoveFile {
+        path: "/dir1".into(),
+    };
+    commit(&raft, operation).await.unwrap();
+
+    create_snapshot(
+        snapshot_path.clone(),
+        temp_path,
+        tree.clone(),
+        files.clone(),
+        chunks.clone(),
+        raft.clone(),
+    )
+    .await
+    .unwrap();
+    let snapshot = load_snapshot(&snapshot_path).await.unwrap();
+    assert_eq!(snapshot.tree, *tree.read().await);
+    assert_eq!(snapshot.files, *files.read().await);
+    assert_eq!(snapshot.chunks, *chunks.read().await);
+    assert_eq!((snapshot.last_op_seq, snapshot.last_op_term), (1, 1));
+    // the log entries included in the snapshot are removed
+    let raft = raft.lock().await;
+    let entries = read_entries(raft.log().path()).await.unwrap();
+    assert_eq!(entries.iter().map(|e| e.seq).collect::<Vec<_>>(), vec![2]);
+    Ok(())
+}
+
+#[actix_rt::test]
+async fn test_load_tree_only_snapshot() -> std::io::Result<()> {
+    let temp = tempdir()?;
+    let snapshot_path = temp.path().join("snapshot");
+    let tree = build_tree().unwrap();
+    write(&snapshot_path, bincode::serialize(&tree).unwrap()).await?;
+
+    let snapshot = load_snapshot(&snapshot_path).await.unwrap();
+    assert_eq!(snapshot.tree, tree);
+    assert!(snapshot.chunks.is_empty());
+    assert_eq!(snapshot.files.len(), 3);
+    let file = tree.traverse("dir2/subdir/file").unwrap();
+    let file_id = match &file.file_info {
+        FileInfo::File { id, .. } => *id,
+        _ => unreachable!(),
+    };
+    assert_eq!(
+        snapshot.files.get(&file_id),
+        Some(&("/dir2/subdir".to_string(), file.clone()))
+    );
+    Ok(())
+}
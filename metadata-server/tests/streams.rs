@@ -0,0 +1,202 @@
+mod utils;
+
+use actix_http::http::StatusCode;
+use actix_web::{test, web, App};
+use ccfs_commons::{Chunk, ChunkPlacement, FileInfo, FileMetadata, FileStatus, CHUNK_SIZE};
+use metadata_server::routes::api::{
+    append_file_chunks, commit_file, create_file, signal_chuck_upload_completed,
+};
+use metadata_server::{ChunksMap, DeletionQueue, FilesMap, ServersMap};
+use std::sync::Arc;
+use test::{call_service, init_service, read_response_json, TestRequest};
+use tokio::sync::RwLock;
+use utils::{test_placement, test_raft};
+use uuid::Uuid;
+
+fn open_file(name: &str) -> (Uuid, FileMetadata) {
+    let mut file = FileMetadata::create_file(name.into(), 0, Vec::new());
+    match &mut file.file_info {
+        FileInfo::File { id, status, .. } => {
+            *status = FileStatus::Open;
+            (*id, file)
+        }
+        _ => unreachable!(),
+    }
+}
+
+#[actix_rt::test]
+async fn test_streamed_file() -> std::io::Result<()> {
+    let (_raft_dir, raft) = test_raft().await;
+    let files = FilesMap::default();
+    let metadata_tree = Arc::new(RwLock::new(FileMetadata::create_root()));
+    let server = init_service(
+        App::new()
+            .data(raft)
+            .data(ChunksMap::default())
+            .data(ServersMap::default())
+            .data(test_placement())
+            .data(DeletionQueue::default())
+            .data(files.clone())
+            .data(metadata_tree.clone())
+            .service(
+                web::scope("/api")
+                    .service(create_file)
+                    .service(append_file_chunks)
+                    .service(commit_file)
+                    .service(signal_chuck_upload_completed),
+            ),
+    )
+    .await;
+
+    let (file_id, new_file) = open_file("test.txt");
+    let req = TestRequest::post()
+        .uri("/api/files/upload")
+        .set_json(&new_file)
+        .to_request();
+    let resp = call_service(&server, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let chunk_ids = vec![Uuid::new_v4(), Uuid::new_v4()];
+    for chunk_id in chunk_ids.iter() {
+        let req = TestRequest::post()
+            .uri(&format!("/api/files/{}/chunks", file_id))
+            .set_json(&vec![*chunk_id])
+            .to_request();
+        let placement: Vec<ChunkPlacement> = read_response_json(&server, req).await;
+        assert_eq!(placement.len(), 1);
+        assert_eq!(&placement[0].chunk_id, chunk_id);
+
+        let chunk = Chunk::new(*chunk_id, file_id, Uuid::new_v4());
+        let req = TestRequest::post()
+            .uri("/api/chunk/completed")
+            .set_json(&chunk)
+            .to_request();
+        let resp = call_service(&server, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+    // the uploaded chunks don't complete the file before it's committed
+    assert!(metadata_tree.read().await.traverse("test.txt").is_err());
+
+    let req = TestRequest::post()
+        .uri(&format!("/api/files/{}/commit?size=5", file_id))
+        .to_request();
+    let resp = call_service(&server, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    let size = CHUNK_SIZE + 5;
+    let req = TestRequest::post()
+        .uri(&format!("/api/files/{}/commit?size={}", file_id, size))
+        .to_request();
+    let data: FileMetadata = read_response_json(&server, req).await;
+    assert!(matches!(
+        &data.file_info,
+        FileInfo::File { size: s, status: FileStatus::Completed, chunks, .. }
+            if *s == size && chunks == &chunk_ids
+    ));
+    assert_eq!(
+        metadata_tree.read().await.traverse("test.txt").unwrap(),
+        &data
+    );
+
+    // the committed file can't be appended to anymore
+    let req = TestRequest::post()
+        .uri(&format!("/api/files/{}/chunks", file_id))
+        .set_json(&vec![Uuid::new_v4()])
+        .to_request();
+    let resp = call_service(&server, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    Ok(())
+}
+
+#[actix_rt::test]
+async fn test_commit_before_chunks_completed() -> std::io::Result<()> {
+    let (_raft_dir, raft) = test_raft().await;
+    let files = FilesMap::default();
+    let metadata_tree = Arc::new(RwLock::new(FileMetadata::create_root()));
+    let (file_id, mut new_file) = open_file("test.txt");
+    let chunk = Chunk::new(Uuid::new_v4(), file_id, Uuid::new_v4());
+    if let FileInfo::File { chunks, .. } = &mut new_file.file_info {
+        chunks.push(chunk.id);
+    }
+    files.write().await.insert(file_id, ("".into(), new_file));
+    let server = init_service(
+        App::new()
+            .data(raft)
+            .data(ChunksMap::default())
+            .data(DeletionQueue::default())
+            .data(files.clone())
+            .data(metadata_tree.clone())
+            .service(
+                web::scope("/api")
+                    .service(commit_file)
+                    .service(signal_chuck_upload_completed),
+            ),
+    )
+    .await;
+
+    let req = TestRequest::post()
+        .uri(&format!("/api/files/{}/commit?size=10", file_id))
+        .to_request();
+    let data: FileMetadata = read_response_json(&server, req).await;
+    assert!(matches!(
+        data.file_info,
+        FileInfo::File {
+            size: 10,
+            status: FileStatus::Started,
+            ..
+        }
+    ));
+    assert!(metadata_tree.read().await.traverse("test.txt").is_err());
+
+    let req = TestRequest::post()
+        .uri("/api/chunk/completed")
+        .set_json(&chunk)
+        .to_request();
+    let resp = call_service(&server, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert!(metadata_tree.read().await.traverse("test.txt").is_ok());
+    Ok(())
+}
+
+#[actix_rt::test]
+async fn test_append_to_not_open_file() -> std::io::Result<()> {
+    let (_raft_dir, raft) = test_raft().await;
+    let files = FilesMap::default();
+    let file = FileMetadata::create_file("test.txt".into(), 10, vec![Uuid::new_v4()]);
+    let file_id = match &file.file_info {
+        FileInfo::File { id, .. } => *id,
+        _ => unreachable!(),
+    };
+    files.write().await.insert(file_id, ("".into(), file));
+    let server = init_service(
+        App::new()
+            .data(raft)
+            .data(ChunksMap::default())
+            .data(ServersMap::default())
+            .data(test_placement())
+            .data(DeletionQueue::default())
+            .data(files.clone())
+            .data(Arc::new(RwLock::new(FileMetadata::create_root())))
+            .service(
+                web::scope("/api")
+                    .service(append_file_chunks)
+                    .service(commit_file),
+            ),
+    )
+    .await;
+
+    let req = TestRequest::post()
+        .uri(&format!("/api/files/{}/chunks", file_id))
+        .set_json(&vec![Uuid::new_v4()])
+        .to_request();
+    let resp = call_service(&server, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    let req = TestRequest::post()
+        .uri(&format!("/api/files/{}/commit?size=10", Uuid::new_v4()))
+        .to_request();
+    let resp = call_service(&server, req).await;
+    assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(files.read().await.len(), 1);
+    Ok(())
+}
@@ -0,0 +1,162 @@
+mod utils;
+
+use actix_http::http::StatusCode;
+use actix_web::{test, web, App};
+use ccfs_commons::{Chunk, FileInfo, FileMetadata, FileStatus};
+use chrono::{Duration, Utc};
+use metadata_server::jobs::uploads::{cancel_expired_uploads, UploadLeases};
+use metadata_server::routes::api::cancel_upload;
+use metadata_server::{ChunksMap, DeletionQueue, Files, FilesMap};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use test::{call_service, init_service, read_response_json, TestRequest};
+use tokio::sync::RwLock;
+use utils::test_raft;
+use uuid::Uuid;
+
+fn file_id(file: &FileMetadata) -> Uuid {
+    match &file.file_info {
+        FileInfo::File { id, .. } => *id,
+        _ => unreachable!(),
+    }
+}
+
+fn completed(mut file: FileMetadata) -> FileMetadata {
+    if let FileInfo::File { status, .. } = &mut file.file_info {
+        *status = FileStatus::Completed;
+    }
+    file
+}
+
+#[test]
+fn test_upload_leases() {
+    let chunk_ids = vec![Uuid::new_v4(), Uuid::new_v4()];
+    let started = FileMetadata::create_file("a.txt".into(), 10, chunk_ids);
+    let done = completed(FileMetadata::create_file("b.txt".into(), 10, vec![]));
+    let mut files: Files = HashMap::new();
+    files.insert(file_id(&started), ("".into(), started.clone()));
+    files.insert(file_id(&done), ("".into(), done.clone()));
+
+    let mut leases = UploadLeases::default();
+    let lease = Duration::seconds(10);
+    let now = Utc::now();
+    assert!(leases.check(&files, now, lease).is_empty());
+    // the upload progressed, so its lease is renewed
+    if let Some((_, file)) = files.get_mut(&file_id(&started)) {
+        if let FileInfo::File {
+            num_of_completed_chunks,
+            ..
+        } = &mut file.file_info
+        {
+            *num_of_completed_chunks += 1;
+        }
+    }
+    assert!(leases
+        .check(&files, now + Duration::seconds(5), lease)
+        .is_empty());
+    assert!(leases
+        .check(&files, now + Duration::seconds(11), lease)
+        .is_empty());
+    assert_eq!(
+        leases.check(&files, now + Duration::seconds(15), lease),
+        vec![file_id(&started)]
+    );
+}
+
+#[actix_rt::test]
+async fn test_cancel_upload() -> std::io::Result<()> {
+    let (_raft_dir, raft) = test_raft().await;
+    let chunk_ids = vec![Uuid::new_v4(), Uuid::new_v4()];
+    let started = FileMetadata::create_file("a.txt".into(), 10, chunk_ids.clone());
+    let done = completed(FileMetadata::create_file("b.txt".into(), 10, vec![]));
+    let chunk = Chunk::new(chunk_ids[0], file_id(&started), Uuid::new_v4());
+    let mut files_map = HashMap::new();
+    files_map.insert(file_id(&started), ("/".to_string(), started.clone()));
+    files_map.insert(file_id(&done), ("/".to_string(), done.clone()));
+    let mut chunks_map = HashMap::new();
+    chunks_map.insert(chunk.id, vec![chunk].into_iter().collect::<HashSet<_>>());
+
+    let files: FilesMap = Arc::new(RwLock::new(files_map));
+    let chunks: ChunksMap = Arc::new(RwLock::new(chunks_map));
+    let deletion_queue: DeletionQueue = Arc::new(RwLock::new(HashSet::new()));
+    let server = init_service(
+        App::new()
+            .data(raft)
+            .data(files.clone())
+            .data(chunks.clone())
+            .data(deletion_queue.clone())
+            .data(Arc::new(RwLock::new(FileMetadata::create_root())))
+            .service(web::scope("/api").service(cancel_upload)),
+    )
+    .await;
+
+    let req = TestRequest::delete()
+        .uri(&format!("/api/files/{}/upload", file_id(&started)))
+        .to_request();
+    let data: FileMetadata = read_response_json(&server, req).await;
+    assert!(matches!(
+        data.file_info,
+        FileInfo::File {
+            status: FileStatus::Canceled,
+            ..
+        }
+    ));
+    assert!(!files.read().await.contains_key(&file_id(&started)));
+    assert!(chunks.read().await.is_empty());
+    assert!(deletion_queue.read().await.contains(&chunk));
+
+    // the completed files aren't canceled
+    let req = TestRequest::delete()
+        .uri(&format!("/api/files/{}/upload", file_id(&done)))
+        .to_request();
+    let resp = call_service(&server, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    assert!(files.read().await.contains_key(&file_id(&done)));
+    Ok(())
+}
+
+#[actix_rt::test]
+async fn test_cancel_expired_uploads() -> std::io::Result<()> {
+    let (_raft_dir, raft) = test_raft().await;
+    let started = FileMetadata::create_file("a.txt".into(), 10, vec![Uuid::new_v4()]);
+    let chunk_id = Uuid::new_v4();
+    let mut open = FileMetadata::create_file("b.txt".into(), 0, vec![chunk_id]);
+    if let FileInfo::File { status, .. } = &mut open.file_info {
+        *status = FileStatus::Open;
+    }
+    let chunk = Chunk::new(chunk_id, file_id(&open), Uuid::new_v4());
+    let done = completed(FileMetadata::create_file("c.txt".into(), 10, vec![]));
+    let files: FilesMap = Arc::new(RwLock::new(
+        vec![&started, &open, &done]
+            .into_iter()
+            .map(|file| (file_id(file), ("/".to_string(), file.clone())))
+            .collect(),
+    ));
+    let chunks: ChunksMap = Arc::new(RwLock::new(
+        vec![(chunk.id, vec![chunk].into_iter().collect())]
+            .into_iter()
+            .collect(),
+    ));
+    let deletion_queue: DeletionQueue = Arc::new(RwLock::new(HashSet::new()));
+    let tree = Arc::new(RwLock::new(FileMetadata::create_root()));
+
+    let mut leases = UploadLeases::default();
+    let count = cancel_expired_uploads(
+        &mut leases,
+        Duration::zero(),
+        &tree,
+        &files,
+        &chunks,
+        &raft,
+        &deletion_queue,
+    )
+    .await
+    .unwrap();
+    assert_eq!(count, 2);
+    let files = files.read().await;
+    assert_eq!(files.len(), 1);
+    assert!(files.contains_key(&file_id(&done)));
+    assert!(chunks.read().await.is_empty());
+    assert!(deletion_queue.read().await.contains(&chunk));
+    Ok(())
+}
@@ -0,0 +1,56 @@
+use metadata_server::oplog::OperationLog;
+use metadata_server::placement::{Placement, PlacementPolicyKind};
+use metadata_server::raft::{Peer, RaftConfig, RaftMessage, RaftNode};
+use metadata_server::{Raft, DEFAULT_REPLICATION_FACTOR};
+use std::path::Path;
+use std::sync::Arc;
+use tempfile::{tempdir, TempDir};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
+use tokio::sync::Mutex;
+use tokio::time::Duration;
+
+pub fn raft_config(dir: &Path, node_id: u64, peers: &[u64]) -> RaftConfig {
+    RaftConfig {
+        node_id,
+        peers: peers
+            .iter()
+            .map(|id| Peer {
+                id: *id,
+                address: format!("http://node{}:4000", id),
+            })
+            .collect(),
+        heartbeat_interval: Duration::from_millis(50),
+        election_timeout: Duration::from_millis(300),
+        snapshot_path: dir.join(format!("snapshot{}", node_id)),
+        state_path: dir.join(format!("snapshot{}.raft", node_id)),
+    }
+}
+
+/// Creates a raft node with an empty log in the dir
+#[allow(dead_code)]
+pub async fn test_node(
+    config: RaftConfig,
+    dir: &Path,
+) -> (RaftNode, UnboundedReceiver<(u64, RaftMessage)>) {
+    let oplog_path = dir.join(format!("snapshot{}.oplog", config.node_id));
+    let oplog = OperationLog::open(&oplog_path, 0, 0).await.unwrap();
+    let (outbox, receiver) = unbounded_channel();
+    let node = RaftNode::new(config, oplog, outbox).await.unwrap();
+    (node, receiver)
+}
+
+/// Creates a single node cluster in a temp dir, which is removed when the `TempDir` is dropped
+#[allow(dead_code)]
+pub async fn test_raft() -> (TempDir, Raft) {
+    let temp = tempdir().unwrap();
+    let (node, _) = test_node(raft_config(temp.path(), 1, &[]), temp.path()).await;
+    (temp, Arc::new(Mutex::new(node)))
+}
+
+#[allow(dead_code)]
+pub fn test_placement() -> Placement {
+    Placement {
+        policy: PlacementPolicyKind::Balanced.policy(),
+        replication_factor: DEFAULT_REPLICATION_FACTOR,
+    }
+}
@@ -0,0 +1,218 @@
+mod utils;
+
+use actix_http::http::StatusCode;
+use actix_web::{test, web, App};
+use ccfs_commons::{Chunk, FileInfo, FileMetadata};
+use metadata_server::operations::{complete_chunk, create_file, remove_file, set_kept_versions};
+use metadata_server::routes::api::{get_file, set_file_versions};
+use metadata_server::{Chunks, ChunksMap, DeletionQueue, Files, FilesMap};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use test::{call_service, init_service, read_response_json, TestRequest};
+use tokio::sync::RwLock;
+use utils::test_raft;
+use uuid::Uuid;
+
+/// Uploads the file with a single chunk into the dir, and returns the chunk replica
+/// and the replicas of the dropped versions
+fn upload(
+    tree: &mut FileMetadata,
+    files: &mut Files,
+    chunks: &mut Chunks,
+    path: &str,
+) -> (Chunk, Vec<Chunk>) {
+    let chunk_id = Uuid::new_v4();
+    let file = FileMetadata::create_file("test.txt".into(), 10, vec![chunk_id]);
+    let file_id = match &file.file_info {
+        FileInfo::File { id, .. } => *id,
+        _ => unreachable!(),
+    };
+    create_file(tree, files, path.into(), file).unwrap();
+    let chunk = Chunk::new(chunk_id, file_id, Uuid::new_v4());
+    let removed = complete_chunk(tree, files, chunks, chunk).unwrap();
+    (chunk, removed)
+}
+
+fn versions(file: &FileMetadata) -> Vec<usize> {
+    match &file.file_info {
+        FileInfo::File { versions, .. } => versions.iter().map(|v| v.version).collect(),
+        _ => unreachable!(),
+    }
+}
+
+#[test]
+fn test_overwrite_bumps_version() {
+    let mut tree = FileMetadata::create_root();
+    tree.insert_dir("dir").unwrap();
+    let (mut files, mut chunks) = (HashMap::new(), HashMap::new());
+    let (first, removed) = upload(&mut tree, &mut files, &mut chunks, "/dir");
+    assert!(removed.is_empty());
+    assert_eq!(tree.traverse("/dir/test.txt").unwrap().version, 1);
+
+    // the previous version stays in the tree until the new one is completed
+    let new_file = FileMetadata::create_file("test.txt".into(), 10, vec![Uuid::new_v4()]);
+    let new_id = match &new_file.file_info {
+        FileInfo::File { id, .. } => *id,
+        _ => unreachable!(),
+    };
+    create_file(&mut tree, &mut files, "/dir".into(), new_file).unwrap();
+    assert_eq!(files[&new_id].1.version, 2);
+    assert_eq!(tree.traverse("/dir/test.txt").unwrap().version, 1);
+
+    let new_chunk = Chunk::new(
+        files[&new_id].1.chunks().unwrap()[0],
+        new_id,
+        Uuid::new_v4(),
+    );
+    let removed = complete_chunk(&mut tree, &mut files, &mut chunks, new_chunk).unwrap();
+    // no previous versions are kept by default
+    assert_eq!(removed, vec![first]);
+    let file = tree.traverse("/dir/test.txt").unwrap();
+    assert_eq!(file.version, 2);
+    assert!(versions(file).is_empty());
+    assert!(!files.contains_key(&first.file_id));
+    assert!(!chunks.contains_key(&first.id));
+}
+
+#[test]
+fn test_kept_versions() {
+    let mut tree = FileMetadata::create_root();
+    let (mut files, mut chunks) = (HashMap::new(), HashMap::new());
+    let (first, _) = upload(&mut tree, &mut files, &mut chunks, "");
+    set_kept_versions(&mut tree, &mut files, &mut chunks, "/test.txt", 1).unwrap();
+
+    let (second, removed) = upload(&mut tree, &mut files, &mut chunks, "");
+    assert!(removed.is_empty());
+    let file = tree.traverse("/test.txt").unwrap();
+    assert_eq!(versions(file), vec![1]);
+    assert_eq!(file.version(1).unwrap().chunks().unwrap(), &vec![first.id]);
+    assert!(files.contains_key(&first.file_id));
+
+    // the setting is inherited by the new versions, the oldest version is dropped
+    let (third, removed) = upload(&mut tree, &mut files, &mut chunks, "");
+    assert_eq!(removed, vec![first]);
+    assert_eq!(versions(tree.traverse("/test.txt").unwrap()), vec![2]);
+    assert!(!files.contains_key(&first.file_id));
+
+    let removed = set_kept_versions(&mut tree, &mut files, &mut chunks, "/test.txt", 0).unwrap();
+    assert_eq!(removed, vec![second]);
+    assert!(versions(tree.traverse("/test.txt").unwrap()).is_empty());
+
+    // the kept versions are removed with the file
+    set_kept_versions(&mut tree, &mut files, &mut chunks, "/test.txt", 1).unwrap();
+    let (fourth, _) = upload(&mut tree, &mut files, &mut chunks, "");
+    let (_, removed) = remove_file(&mut tree, &mut files, &mut chunks, "/test.txt").unwrap();
+    assert_eq!(
+        removed.into_iter().collect::<HashSet<_>>(),
+        vec![third, fourth].into_iter().collect()
+    );
+    assert!(files.is_empty());
+    assert!(chunks.is_empty());
+}
+
+#[actix_rt::test]
+async fn test_get_and_set_file_versions() -> std::io::Result<()> {
+    let (_raft_dir, raft) = test_raft().await;
+    let mut tree = FileMetadata::create_root();
+    let (mut files_map, mut chunks_map) = (HashMap::new(), HashMap::new());
+    let (first, _) = upload(&mut tree, &mut files_map, &mut chunks_map, "");
+    set_kept_versions(&mut tree, &mut files_map, &mut chunks_map, "/test.txt", 1).unwrap();
+    let (second, _) = upload(&mut tree, &mut files_map, &mut chunks_map, "");
+    let previous = tree
+        .traverse("/test.txt")
+        .unwrap()
+        .version(1)
+        .unwrap()
+        .clone();
+
+    let files: FilesMap = Arc::new(RwLock::new(files_map));
+    let chunks: ChunksMap = Arc::new(RwLock::new(chunks_map));
+    let deletion_queue: DeletionQueue = Arc::new(RwLock::new(HashSet::new()));
+    let metadata_tree = Arc::new(RwLock::new(tree));
+    let server = init_service(
+        App::new()
+            .data(raft)
+            .data(files.clone())
+            .data(chunks)
+            .data(deletion_queue.clone())
+            .data(metadata_tree.clone())
+            .service(
+                web::scope("/api")
+                    .service(get_file)
+                    .service(set_file_versions),
+            ),
+    )
+    .await;
+
+    let req = TestRequest::get()
+        .uri("/api/files?path=/test.txt&version=1")
+        .to_request();
+    let data: FileMetadata = read_response_json(&server, req).await;
+    assert_eq!(data, previous);
+    let req = TestRequest::get()
+        .uri("/api/files?path=/test.txt&version=2")
+        .to_request();
+    let data: FileMetadata = read_response_json(&server, req).await;
+    assert_eq!(data.chunks().unwrap(), &vec![second.id]);
+    for version in ["3", "latest"].iter() {
+        let req = TestRequest::get()
+            .uri(&format!("/api/files?path=/test.txt&version={}", version))
+            .to_request();
+        let resp = call_service(&server, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    let req = TestRequest::put()
+        .uri("/api/files/versions?path=/test.txt&versions=0")
+        .to_request();
+    let data: FileMetadata = read_response_json(&server, req).await;
+    assert!(versions(&data).is_empty());
+    assert!(data.version(1).is_none());
+    assert_eq!(
+        *deletion_queue.read().await,
+        vec![first].into_iter().collect::<HashSet<_>>()
+    );
+    assert!(!files.read().await.contains_key(&first.file_id));
+
+    let req = TestRequest::put()
+        .uri("/api/files/versions?path=/test.txt")
+        .to_request();
+    let resp = call_service(&server, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    Ok(())
+}
+
+#[test]
+fn test_names_keep_their_kind() {
+    let mut tree = FileMetadata::create_root();
+    tree.insert_dir("dir").unwrap();
+    let (mut files, mut chunks) = (HashMap::new(), HashMap::new());
+    upload(&mut tree, &mut files, &mut chunks, "/dir");
+
+    // creating an existing directory keeps its content
+    let dir = FileMetadata::create_dir("dir".into());
+    create_file(&mut tree, &mut files, "".into(), dir).unwrap();
+    assert!(tree.traverse("/dir/test.txt").is_ok());
+
+    let dir = FileMetadata::create_dir("test.txt".into());
+    assert!(create_file(&mut tree, &mut files, "/dir".into(), dir).is_err());
+    let file = FileMetadata::create_file("dir".into(), 10, vec![Uuid::new_v4()]);
+    assert!(create_file(&mut tree, &mut files, "".into(), file).is_err());
+
+    // the directory created while the file was being uploaded isn't replaced
+    let chunk_id = Uuid::new_v4();
+    let file = FileMetadata::create_file("sub".into(), 10, vec![chunk_id]);
+    let file_id = match &file.file_info {
+        FileInfo::File { id, .. } => *id,
+        _ => unreachable!(),
+    };
+    create_file(&mut tree, &mut files, "/dir".into(), file).unwrap();
+    tree.traverse_mut("/dir")
+        .unwrap()
+        .insert_dir("sub")
+        .unwrap();
+    let chunk = Chunk::new(chunk_id, file_id, Uuid::new_v4());
+    assert!(complete_chunk(&mut tree, &mut files, &mut chunks, chunk).is_err());
+    let sub = tree.traverse("/dir/sub").unwrap();
+    assert!(matches!(sub.file_info, FileInfo::Directory { .. }));
+}